@@ -0,0 +1,36 @@
+use debs2021::AnalysisLocations;
+use debs2021::gen::challenger::Locations;
+use debs2021::gen::index::LocationsIndex;
+use debs2021::persist::source_hash;
+
+use bencher::{Bencher,benchmark_main,benchmark_group};
+use prost::Message;
+
+fn load_locations_dump() -> Vec<u8> {
+    let root = std::env::var("DEBS_DATA_ROOT").expect("DEBS_DATA_ROOT not set!");
+    std::fs::read(format!("{}/locations_dump.bin", root)).expect("Failed to read locations dump")
+}
+
+fn bench_rebuild_locations(bench: &mut Bencher) {
+    let dump = load_locations_dump();
+    bench.iter(|| {
+        let locations = Locations::decode(dump.as_slice()).expect("Failed to decode locations");
+        AnalysisLocations::new(locations)
+    });
+}
+
+fn bench_load_index(bench: &mut Bencher) {
+    let dump = load_locations_dump();
+    let hash = source_hash(&dump);
+    let locations = AnalysisLocations::new(Locations::decode(dump.as_slice()).expect("Failed to decode locations"));
+    let index = locations.to_index(hash);
+    let mut buf = Vec::with_capacity(index.encoded_len());
+    index.encode(&mut buf).expect("Buffer should have sufficient capacity");
+    bench.iter(|| {
+        let index = LocationsIndex::decode(buf.as_slice()).expect("Failed to decode index");
+        AnalysisLocations::from_index(index, hash).expect("Index should be valid")
+    });
+}
+
+benchmark_group!(bench_load_all, bench_rebuild_locations, bench_load_index);
+benchmark_main!(bench_load_all);
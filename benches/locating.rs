@@ -1,4 +1,5 @@
 use debs2021::AnalysisLocations;
+use debs2021::grid::GridLocations;
 use debs2021::io::{load_locations,load_batch_from};
 
 use bencher::{Bencher,benchmark_main,benchmark_group};
@@ -35,5 +36,15 @@ fn bench_locating(bench: &mut Bencher) {
     });
 }
 
-benchmark_group!(bench_locate_all, bench_locating);
+fn bench_locating_grid(bench: &mut Bencher) {
+    let (locations, testlocations) = load_testlocation_data();
+    let locations = GridLocations::new(locations, 0.01);
+    bench.iter(|| {
+        for (lat, lng) in &testlocations {
+            let _location = locations.localize(*lat, *lng);
+        }
+    });
+}
+
+benchmark_group!(bench_locate_all, bench_locating, bench_locating_grid);
 benchmark_main!(bench_locate_all);
@@ -1,6 +1,6 @@
 fn main() -> Result<(), Box<dyn std::error::Error>> {
 	tonic_build::configure()
         .out_dir("src/gen")
-        .compile(&["proto/challenger.proto"], &["proto/"])?;
+        .compile(&["proto/challenger.proto", "proto/index.proto"], &["proto/"])?;
     Ok(())
 }
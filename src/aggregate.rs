@@ -1,4 +1,15 @@
-use crate::CityId;
+use crate::{CityId,LocationId};
+use crate::region::RegionId;
+use crate::aqi::{AQIReport,AQIStandard,AQITableVersion,AQIValue,OutOfScalePolicy,Pollutant,PollutantAQIs};
+
+/// Identifies a sensor by its exact coordinates,
+/// see [sensor_id].
+pub type SensorId = u64;
+
+/// Calculates the SensorId of the sensor at the given coordinates.
+pub fn sensor_id(latitude: f32, longitude: f32) -> SensorId {
+    (u64::from(latitude.to_bits()) << 32) | u64::from(longitude.to_bits())
+}
 
 /// Contains the information of a measurement,
 /// but with city id and batch seq id instead
@@ -10,16 +21,31 @@ pub struct LocalizedMeasurement {
     /// The city id which can be used to look up
     /// the city name using [AnalysisLocations::lookup](crate::AnalysisLocations::lookup)
     pub cityid: CityId,
+    /// The location id which can be used to look up
+    /// the zipcode using [AnalysisLocations::zipcode](crate::AnalysisLocations::zipcode)
+    pub locationid: LocationId,
+    /// The id of the sensor which measured the values
+    pub sensorid: SensorId,
     /// The timestamp of the measurement.
     /// Contrary to the generated source,
     /// 1. it is not an Option since
     ///    the timestamp is always present
     /// 2. only the seconds are used
     pub timestamp_seconds: i64,
-    ///Particles < 10µm (particulate matter)
+    ///Particles < 10µm (particulate matter),
+    /// corrected for humidity if enabled (see [HumidityCorrection](crate::humidity::HumidityCorrection))
     pub p1: f32,
-    ///Particles < 2.5µm (ultrafine particles)
+    ///Particles < 2.5µm (ultrafine particles),
+    /// corrected for humidity if enabled
     pub p2: f32,
+    /// The p1 value as measured (the same as p1 if not corrected)
+    pub raw_p1: f32,
+    /// The p2 value as measured (the same as p2 if not corrected)
+    pub raw_p2: f32,
+    /// The relative humidity in %, if provided by the sensor
+    pub humidity: Option<f32>,
+    /// The temperature in °C, if provided by the sensor
+    pub temperature: Option<f32>,
 }
 
 /// An Iterator which partitions [LocalizedMeasurement]s by their timestamp.
@@ -386,17 +412,57 @@ mod window_test {
 }
 
 
+/// An aggregate of p1/p2 measurements, which supports adding and removing
+/// other aggregates, so it can be used in sliding windows.
+/// The pipeline can be run using any Aggregate, e.g. [ParticleAggregate]
+/// (the mean) or the robust aggregates in [crate::robust].
+pub trait Aggregate:
+    std::fmt::Debug + Default + Clone + Send + Sync
+    + std::ops::AddAssign + std::ops::SubAssign
+    + std::iter::FromIterator<(f32, f32)> + std::iter::FromIterator<Self>
+{
+    /// Creates a new aggregate based on a single measurement.
+    fn from_measurement(init: (f32, f32)) -> Self;
+    /// Adds a single measurement to the aggregate.
+    fn add_measurement(&mut self, val: (f32, f32));
+    /// Calculates the final p1 value of the aggregate.
+    fn p1(&self) -> f32;
+    /// Calculates the final p2 value of the aggregate.
+    fn p2(&self) -> f32;
+    /// Returns the number of measurements added to the aggregate.
+    fn count(&self) -> usize;
+}
+
+/// The number of fixed-point units per µg/m³ used by [ParticleAggregate] (2^64).
+/// As a power of two, every f32 value above 2^-64 µg/m³ is represented exactly,
+/// so the means equal those calculated from f64 sums (as long as the f64 sums are exact,
+/// which holds for the measurements of the dataset with at most 24 significant bits).
+pub const FIXED_POINT_SCALE: f64 = 18_446_744_073_709_551_616.0;
+
+/// The largest value (in µg/m³) a single measurement can contribute
+/// to a [ParticleAggregate], larger values (e.g. infinity) are clamped.
+const MAX_FIXED_POINT_VALUE: f64 = 1e9;
+
+/// Converts a value in µg/m³ into fixed-point units, see [FIXED_POINT_SCALE].
+fn to_fixed_point(value: f32) -> u128 {
+    (f64::from(value).min(MAX_FIXED_POINT_VALUE) * FIXED_POINT_SCALE).round() as u128
+}
+
 /// ParticleAggregate is used to aggregate multiple measurements
 /// and calculate the mean p1/p2. It provides some additional
 /// integrity checks, such as checking that input values are
 /// positive and p1 and p2 are added at the same time.
-/// To avoid rounding errors, values are internally stored as f64.
-#[derive(Debug,Default,Copy,Clone)]
+/// To avoid rounding errors, values are internally stored as fixed-point
+/// integers (µg/m³×2^64), so adding and removing aggregates is exact
+/// and sliding windows do not drift, no matter how many windows are calculated.
+/// The sums use wrapping arithmetic, so even an overflowing intermediate sum
+/// is restored exactly once the values causing it are removed.
+#[derive(Debug,Default,Copy,Clone,PartialEq,Eq)]
 pub struct ParticleAggregate {
-    /// The sum of all p1 values added to this aggregate
-    sum_p1: f64,
-    /// The sum of all p2 values added to this aggregate
-    sum_p2: f64,
+    /// The sum of all p1 values added to this aggregate (in fixed-point units)
+    sum_p1: u128,
+    /// The sum of all p2 values added to this aggregate (in fixed-point units)
+    sum_p2: u128,
     /// The count of all p1 and p2 values added to this aggregate
     denom: usize,
 }
@@ -406,28 +472,52 @@ impl ParticleAggregate {
     pub fn new(init: (f32, f32)) -> Self {
         debug_assert!(init.0 >= 0.0 && init.1 >= 0.0);
         Self {
-            sum_p1: init.0 as f64,
-            sum_p2: init.1 as f64,
+            sum_p1: to_fixed_point(init.0),
+            sum_p2: to_fixed_point(init.1),
             denom: 1,
         }
     }
 
     /// Adds a single measurement to the aggregate.
     pub fn add(&mut self, val: (f32, f32)) {
-        debug_assert!(val.0 >= 0.0 && val.1 >= 0.0);
-        self.sum_p1 += val.0 as f64;
-        self.sum_p2 += val.1 as f64;
-        self.denom += 1;
+        *self += Self::new(val);
+    }
+
+    /// Returns the number of measurements added to the aggregate.
+    pub fn count(&self) -> usize {
+        self.denom
     }
 
     /// Calculates the final (mean) p1 value of the aggregate.
     pub fn p1(&self) -> f32 {
-        (self.sum_p1 / (self.denom as f64)) as f32
+        (self.sum_p1 as f64 / FIXED_POINT_SCALE / (self.denom as f64)) as f32
     }
 
     /// Calculates the final (mean) p2 value of the aggregate.
     pub fn p2(&self) -> f32 {
-        (self.sum_p2 / (self.denom as f64)) as f32
+        (self.sum_p2 as f64 / FIXED_POINT_SCALE / (self.denom as f64)) as f32
+    }
+}
+
+impl Aggregate for ParticleAggregate {
+    fn from_measurement(init: (f32, f32)) -> Self {
+        Self::new(init)
+    }
+
+    fn add_measurement(&mut self, val: (f32, f32)) {
+        self.add(val)
+    }
+
+    fn p1(&self) -> f32 {
+        ParticleAggregate::p1(self)
+    }
+
+    fn p2(&self) -> f32 {
+        ParticleAggregate::p2(self)
+    }
+
+    fn count(&self) -> usize {
+        ParticleAggregate::count(self)
     }
 }
 
@@ -435,8 +525,8 @@ impl std::ops::Add for ParticleAggregate {
     type Output=Self;
     fn add(self, rhs: ParticleAggregate) -> Self {
         Self {
-            sum_p1: self.sum_p1 + rhs.sum_p1,
-            sum_p2: self.sum_p2 + rhs.sum_p2,
+            sum_p1: self.sum_p1.wrapping_add(rhs.sum_p1),
+            sum_p2: self.sum_p2.wrapping_add(rhs.sum_p2),
             denom: self.denom + rhs.denom,
         }
     }
@@ -444,11 +534,7 @@ impl std::ops::Add for ParticleAggregate {
 
 impl std::ops::AddAssign for ParticleAggregate {
     fn add_assign(&mut self, rhs: ParticleAggregate) {
-        *self = Self {
-            sum_p1: self.sum_p1 + rhs.sum_p1,
-            sum_p2: self.sum_p2 + rhs.sum_p2,
-            denom: self.denom + rhs.denom,
-        };
+        *self = *self + rhs;
     }
 }
 
@@ -456,64 +542,32 @@ impl std::ops::Sub for ParticleAggregate {
     type Output=Self;
     fn sub(self, rhs: ParticleAggregate) -> Self {
         debug_assert!(self.denom >= rhs.denom, "Removing {} units from aggregate with only {} added (self: {:#?}, rhs: {:#?})", rhs.denom, self.denom, self, rhs);
-        let new_denom = self.denom - rhs.denom;
-        if new_denom == 0 {
-            // when no values are left, use all-zeroes instead
-            // off subtracting to avoid/reduce rounding errors
-            Self::default()   
-        } else {
-            debug_assert!((self.sum_p1 - rhs.sum_p1) >= -f64::EPSILON, "Removing {} p1 from aggregate with only {} p1 added (self: {:#?}, rhs: {:#?})", rhs.sum_p1, self.sum_p1, self, rhs);
-            debug_assert!((self.sum_p2 - rhs.sum_p2) >= -f64::EPSILON, "Removing {} p2 from aggregate with only {} p2 added (self: {:#?}, rhs: {:#?})", rhs.sum_p2, self.sum_p2, self, rhs);
-            Self {
-                sum_p1: self.sum_p1 - rhs.sum_p1,
-                sum_p2: self.sum_p2 - rhs.sum_p2,
-                denom: new_denom,
-            }
-        }
+        let result = Self {
+            sum_p1: self.sum_p1.wrapping_sub(rhs.sum_p1),
+            sum_p2: self.sum_p2.wrapping_sub(rhs.sum_p2),
+            denom: self.denom - rhs.denom,
+        };
+        // as the sums are exact, nothing may be left once all values are removed
+        debug_assert!(result.denom > 0 || result == Self::default(), "Removed all units, but sums are left (self: {:#?}, rhs: {:#?})", self, rhs);
+        result
     }
 }
 
 impl std::ops::SubAssign for ParticleAggregate {
     fn sub_assign(&mut self, rhs: ParticleAggregate) {
-        debug_assert!(self.denom >= rhs.denom, "Removing {} units from aggregate with only {} added (self: {:#?}, rhs: {:#?})", rhs.denom, self.denom, self, rhs);
-        *self = {
-            let new_denom = self.denom - rhs.denom;
-            if new_denom == 0 {
-                // when no values are left, use all-zeroes instead
-                // off subtracting to avoid/reduce rounding errors
-                Self::default()   
-            } else {
-                debug_assert!((self.sum_p1 - rhs.sum_p1) >= -f64::EPSILON, "Removing {} p1 from aggregate with only {} p1 added (self: {:#?}, rhs: {:#?})", rhs.sum_p1, self.sum_p1, self, rhs);
-                debug_assert!((self.sum_p2 - rhs.sum_p2) >= -f64::EPSILON, "Removing {} p2 from aggregate with only {} p2 added (self: {:#?}, rhs: {:#?})", rhs.sum_p2, self.sum_p2, self, rhs);
-                Self {
-                    sum_p1: self.sum_p1 - rhs.sum_p1,
-                    sum_p2: self.sum_p2 - rhs.sum_p2,
-                    denom: new_denom,
-                }
-            }
-        }
+        *self = *self - rhs;
     }
 }
 
 impl std::iter::FromIterator<ParticleAggregate> for ParticleAggregate {
     fn from_iter<T: IntoIterator<Item = ParticleAggregate>>(iter: T) -> Self {
-        let (sum_p1, sum_p2, denom) = iter
-            .into_iter()
-            .fold((0.0, 0.0, 0), |acc, x| (acc.0 + x.sum_p1, acc.1 + x.sum_p2, acc.2 + x.denom));
-        Self {
-            sum_p1, sum_p2, denom
-        }
+        iter.into_iter().fold(Self::default(), |acc, x| acc + x)
     }
 }
 
 impl std::iter::FromIterator<(f32, f32)> for ParticleAggregate {
     fn from_iter<T: IntoIterator<Item = (f32, f32)>>(iter: T) -> Self {
-        let (sum_p1, sum_p2, denom) = iter
-            .into_iter()
-            .fold((0.0, 0.0, 0), |acc, (p1, p2)| (acc.0 + p1 as f64, acc.1 + p2 as f64, acc.2 + 1));
-        Self {
-            sum_p1, sum_p2, denom
-        }
+        iter.into_iter().fold(Self::default(), |acc, x| acc + Self::new(x))
     }
 }
 
@@ -544,6 +598,52 @@ mod particle_aggregate_test {
         // = 2.6 / 5
         assert!((agg.p2() - 0.52).abs() < 0.0000001);
     }
+
+    #[test]
+    fn test_sliding_sums_do_not_drift() {
+        // values which can not be represented exactly as binary floats
+        let aggregates = (0..100_000)
+            .map(|i| vec![(0.1 + (i % 7) as f32 * 0.01, 0.3f32), (1e6, 0.7)].into_iter().collect::<ParticleAggregate>())
+            .collect::<Vec<_>>();
+        let mut window = aggregates[..100].iter().copied().collect::<ParticleAggregate>();
+        for i in 100..aggregates.len() {
+            window += aggregates[i];
+            window -= aggregates[i - 100];
+        }
+        let recomputed = aggregates[aggregates.len() - 100..].iter().copied().collect::<ParticleAggregate>();
+        assert_eq!(window, recomputed);
+        for aggregate in &aggregates[aggregates.len() - 100..] {
+            window -= *aggregate;
+        }
+        assert_eq!(window, ParticleAggregate::default());
+    }
+
+    #[test]
+    fn test_means_equal_f64_sums() {
+        // values with two decimals, like the measurements of the dataset
+        let values = (0..10_000u32)
+            .map(|i| ((i * 7919 % 100_000) as f32 / 100.0, (i * 104_729 % 50_000) as f32 / 100.0))
+            .collect::<Vec<_>>();
+        // the mean as calculated before using fixed-point sums
+        let f64_mean = |values: &[(f32, f32)]| {
+            let (sum_p1, sum_p2) = values.iter().fold((0.0f64, 0.0f64), |(p1, p2), value| (p1 + f64::from(value.0), p2 + f64::from(value.1)));
+            ((sum_p1 / values.len() as f64) as f32, (sum_p2 / values.len() as f64) as f32)
+        };
+        for len in [1, 2, 3, 7, 288, 1440, 10_000] {
+            let aggregate = values[..len].iter().copied().collect::<ParticleAggregate>();
+            let (p1, p2) = f64_mean(&values[..len]);
+            assert_eq!(aggregate.p1().to_bits(), p1.to_bits());
+            assert_eq!(aggregate.p2().to_bits(), p2.to_bits());
+        }
+        // also after sliding a window
+        let mut window = values[..288].iter().copied().collect::<ParticleAggregate>();
+        for i in 288..values.len() {
+            window.add(values[i]);
+            window -= ParticleAggregate::new(values[i - 288]);
+            let (p1, p2) = f64_mean(&values[i - 287..=i]);
+            assert_eq!((window.p1().to_bits(), window.p2().to_bits()), (p1.to_bits(), p2.to_bits()));
+        }
+    }
 }
 
 use std::collections::HashSet;
@@ -580,26 +680,59 @@ impl std::ops::BitOr for ActiveCities {
     }
 }
 
-/// A map, mapping each CityId to the corresponding ParticleAggregate
-pub type CityParticleMap = std::collections::HashMap<CityId, ParticleAggregate>;
+/// A map, mapping each RegionId (of a single [RegionLevel](crate::region::RegionLevel))
+/// to the corresponding ParticleAggregate (or another [Aggregate])
+pub type RegionParticleMap<A = ParticleAggregate> = std::collections::HashMap<RegionId, A>;
+
+/// A map, mapping each CityId to the corresponding ParticleAggregate (or another [Aggregate])
+pub type CityParticleMap<A = ParticleAggregate> = RegionParticleMap<A>;
 // type CityParticleMap = std::collections::BTreeMap<CityId, ParticleAggregate>;
 
+/// A map, mapping each sensor (and the city it belongs to)
+/// to the corresponding ParticleAggregate (or another [Aggregate])
+pub type SensorParticleMap<A = ParticleAggregate> = std::collections::HashMap<(CityId, SensorId), A>;
+
+/// Calculates the mean of the sensor means for each city, i.e. every sensor
+/// has the same weight, regardless of how many measurements it sent.
+/// The count of each resulting aggregate is the number of sensors.
+/// Sensors without any measurements are skipped.
+pub fn sensor_means<A: Aggregate>(sensors: &SensorParticleMap<A>) -> CityParticleMap<A> {
+    let mut result = CityParticleMap::default();
+    for ((cityid, _), aggregate) in sensors.iter().filter(|(_, aggregate)| aggregate.count() > 0) {
+        let mean = (aggregate.p1(), aggregate.p2());
+        result
+            .entry(*cityid)
+            .and_modify(|city: &mut A| city.add_measurement(mean))
+            .or_insert_with(|| A::from_measurement(mean));
+    }
+    result
+}
+
+/// Counts the sensors with measurements for each city.
+pub fn sensor_counts<A: Aggregate>(sensors: &SensorParticleMap<A>) -> std::collections::HashMap<CityId, usize> {
+    let mut result = std::collections::HashMap::new();
+    for ((cityid, _), _) in sensors.iter().filter(|(_, aggregate)| aggregate.count() > 0) {
+        *result.entry(*cityid).or_insert(0) += 1;
+    }
+    result
+}
+
 /// Calculates a\[k\] += b\[k]\ for every key k in b
-pub(crate) fn map_add(a: &mut CityParticleMap, b: &CityParticleMap) {
+pub(crate) fn map_add<K: std::hash::Hash + Eq + Copy, A: Aggregate>(a: &mut std::collections::HashMap<K, A>, b: &std::collections::HashMap<K, A>) {
     for (k, v2) in b {
         match a.get_mut(&k) {
-            Some(v) => *v += *v2,
-            None => assert!(a.insert(*k, *v2).is_none()),
+            Some(v) => *v += v2.clone(),
+            None => assert!(a.insert(*k, v2.clone()).is_none()),
         }
     }
 }
 
 /// Calculates a\[k\] -= b\[k\] for every key k in b
-pub(crate) fn map_sub(a: &mut CityParticleMap, b: &CityParticleMap) {
+pub(crate) fn map_sub<K: std::hash::Hash + Eq + Copy + std::fmt::Debug, A: Aggregate>(a: &mut std::collections::HashMap<K, A>, b: &std::collections::HashMap<K, A>) {
     for (k, v2) in b {
         match a.get_mut(k) {
-            Some(v) => *v -= *v2,
-            None => panic!("Removing city w/ cityid {} without having added it previously", *k),
+            Some(v) => *v -= v2.clone(),
+            None => panic!("Removing key {:?} without having added it previously", *k),
         }
     }
 }
@@ -607,43 +740,79 @@ pub(crate) fn map_sub(a: &mut CityParticleMap, b: &CityParticleMap) {
 /// A struct which holds data describing a preaggregate (Fünf-Minuten-Aggregat).
 /// Generated by [PreAggregate]
 #[derive(Clone)]
-pub struct PreAggregateData {
+pub struct PreAggregateData<A = ParticleAggregate> {
     /// The values of each city
-    pub values: CityParticleMap,
+    pub values: CityParticleMap<A>,
+    /// The values of each zipcode, which can be rolled up
+    /// to other levels using [RegionHierarchy::rollup](crate::region::RegionHierarchy::rollup),
+    /// only filled if enabled (see [PreAggregate::with_zipcodes])
+    pub zipcodes: RegionParticleMap<A>,
+    /// The values of each sensor, only filled if enabled
+    /// (see [WithPreAggregate::preaggregate_sensors])
+    pub sensors: SensorParticleMap<A>,
     /// The maximum batch id of data used.
     /// None if the window contains no measurements
     pub maxbatch: Option<i64>,
+    /// The timestamp (in seconds) of the newest measurement.
+    /// None if the window contains no measurements
+    pub timestamp: Option<i64>,
 }
 
 /// An iterator which preaggregates a Vec<[LocalizedMeasurement]>
-/// into a [CityParticleMap].
+/// into a [CityParticleMap] and a [RegionParticleMap] of zipcodes.
 #[derive(Clone)]
-pub struct PreAggregate<I>
+pub struct PreAggregate<I, A = ParticleAggregate>
 where
     I : Iterator<Item = Vec<LocalizedMeasurement>> {
-    inner: I
+    inner: I,
+    /// Whether the values of each sensor are aggregated as well
+    sensors: bool,
+    /// Whether the values of each zipcode are aggregated as well
+    zipcodes: bool,
+    /// The type of the aggregates
+    aggregate: std::marker::PhantomData<A>,
 }
 
-impl<I> Iterator for PreAggregate<I> 
+impl<I, A: Aggregate> Iterator for PreAggregate<I, A> 
 where
     I : Iterator<Item = Vec<LocalizedMeasurement>> {
-    type Item = PreAggregateData;
+    type Item = PreAggregateData<A>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let mut map : CityParticleMap = Default::default();
+        let mut map : CityParticleMap<A> = Default::default();
+        let mut zipcodes : RegionParticleMap<A> = Default::default();
+        let mut sensors : SensorParticleMap<A> = Default::default();
         let measurements = self.inner.next()?;
         let maxbatch = measurements
             .last()
             .map(|x| x.batch_seq_id);
+        let timestamp = measurements
+            .last()
+            .map(|x| x.timestamp_seconds);
         for m in measurements {
             match map.get_mut(&m.cityid) {
-                Some(aggregate) => aggregate.add((m.p1, m.p2)),
-                None => assert!(map.insert(m.cityid, ParticleAggregate::new((m.p1, m.p2))).is_none()),
+                Some(aggregate) => aggregate.add_measurement((m.p1, m.p2)),
+                None => assert!(map.insert(m.cityid, A::from_measurement((m.p1, m.p2))).is_none()),
+            }
+            if self.zipcodes {
+                zipcodes
+                    .entry(m.locationid)
+                    .and_modify(|aggregate| aggregate.add_measurement((m.p1, m.p2)))
+                    .or_insert_with(|| A::from_measurement((m.p1, m.p2)));
+            }
+            if self.sensors {
+                sensors
+                    .entry((m.cityid, m.sensorid))
+                    .and_modify(|aggregate| aggregate.add_measurement((m.p1, m.p2)))
+                    .or_insert_with(|| A::from_measurement((m.p1, m.p2)));
             }
         }
         Some(PreAggregateData {
             values: map,
+            zipcodes,
+            sensors,
             maxbatch,
+            timestamp,
         })
     }
 }
@@ -653,8 +822,550 @@ where
 pub trait WithPreAggregate : Iterator<Item = Vec<LocalizedMeasurement>> {
     /// (Pre)aggregrates Vec<[LocalizedMeasurement]>s into a CityParticleMap.
     fn preaggregate(self) -> PreAggregate<Self> where Self: Sized {
-        PreAggregate { inner: self }
+        PreAggregate { inner: self, sensors: false, zipcodes: false, aggregate: std::marker::PhantomData }
+    }
+
+    /// Like [WithPreAggregate::preaggregate], but using the [Aggregate] A.
+    /// If sensors is set, the values of each sensor are aggregated as well.
+    fn preaggregate_sensors<A: Aggregate>(self, sensors: bool) -> PreAggregate<Self, A> where Self: Sized {
+        PreAggregate { inner: self, sensors, zipcodes: false, aggregate: std::marker::PhantomData }
+    }
+}
+
+impl<I, A> PreAggregate<I, A>
+where
+    I : Iterator<Item = Vec<LocalizedMeasurement>> {
+    /// If zipcodes is set, the values of each zipcode are aggregated as well
+    /// (an additional map insert per measurement, so only enable it if needed).
+    pub fn with_zipcodes(mut self, zipcodes: bool) -> Self {
+        self.zipcodes = zipcodes;
+        self
     }
 }
 
 impl<T: Sized> WithPreAggregate for T where T: Iterator<Item = Vec<LocalizedMeasurement>> {}
+
+use std::borrow::Cow;
+
+/// A value which can be combined with other values of the same type,
+/// e.g. to aggregate the values of a window in a [SlidingAggregator].
+pub trait Monoid: Clone {
+    /// Returns the identity, i.e. the value which does not change
+    /// any value it is combined with (e.g. an empty aggregate).
+    fn identity() -> Self;
+    /// Combines self with other, which contains newer values.
+    /// Needs to be associative, but not necessarily commutative.
+    fn combine(&mut self, other: &Self);
+}
+
+/// A [Monoid] whose combination can be undone, e.g. sums or means.
+/// A [SlidingAggregator] evicts old values of such values by simply subtracting them.
+pub trait Group: Monoid {
+    /// Removes other, which has previously been combined with self
+    /// as its oldest value.
+    fn uncombine(&mut self, other: &Self);
+}
+
+impl Monoid for ParticleAggregate {
+    fn identity() -> Self {
+        Self::default()
+    }
+
+    fn combine(&mut self, other: &Self) {
+        *self += *other;
+    }
+}
+
+impl Group for ParticleAggregate {
+    fn uncombine(&mut self, other: &Self) {
+        *self -= *other;
+    }
+}
+
+/// The number of fixed-point units per unit of concentration used by [PollutantAggregate],
+/// i.e. a resolution of 1e-6 units, which is enough for O3 measured in ppm with 3 decimals.
+const POLLUTANT_FIXED_POINT_SCALE: f64 = 1_000_000.0;
+
+/// The largest value a single measurement can contribute to a [PollutantAggregate].
+const MAX_POLLUTANT_VALUE: f64 = 1e6;
+
+/// Aggregates measurements of any [Pollutant] and calculates the mean of each pollutant.
+/// Contrary to [ParticleAggregate], a measurement contains a single pollutant,
+/// so the number of measurements is counted separately for each pollutant.
+/// Like [ParticleAggregate], fixed-point sums are used, so measurements can be removed exactly.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PollutantAggregate {
+    /// The sum of the measurements of each pollutant, see [POLLUTANT_FIXED_POINT_SCALE]
+    sums: [u64; Pollutant::ALL.len()],
+    /// The number of measurements of each pollutant
+    counts: [u32; Pollutant::ALL.len()],
+}
+
+impl PollutantAggregate {
+    /// Adds a single measurement of pollutant, negative values are counted as 0.
+    pub fn add(&mut self, pollutant: Pollutant, value: f32) {
+        let value = (f64::from(value).min(MAX_POLLUTANT_VALUE) * POLLUTANT_FIXED_POINT_SCALE).round() as u64;
+        self.sums[pollutant as usize] = self.sums[pollutant as usize].wrapping_add(value);
+        self.counts[pollutant as usize] += 1;
+    }
+
+    /// Returns the number of measurements of pollutant.
+    pub fn count(&self, pollutant: Pollutant) -> usize {
+        self.counts[pollutant as usize] as usize
+    }
+
+    /// Returns the mean of the measurements of pollutant, None if there are none.
+    pub fn mean(&self, pollutant: Pollutant) -> Option<f32> {
+        match self.count(pollutant) {
+            0 => None,
+            count => Some((self.sums[pollutant as usize] as f64 / POLLUTANT_FIXED_POINT_SCALE / count as f64) as f32),
+        }
+    }
+
+    /// Calculates the AQIs of all pollutants with measurements using the given version of the EPA tables,
+    /// handling concentrations above the scale as defined by policy.
+    /// The aggregate needs to contain the measurements of the averaging period of each pollutant
+    /// (see [Pollutant::averaging_hours]).
+    pub fn aqis(&self, version: AQITableVersion, policy: OutOfScalePolicy) -> PollutantAQIs {
+        let mut aqis = PollutantAQIs::default();
+        for pollutant in Pollutant::ALL.iter().copied() {
+            aqis.set(pollutant, self.mean(pollutant).and_then(|mean| version.aqi(pollutant, mean, policy).ok()));
+        }
+        aqis
+    }
+}
+
+impl Monoid for PollutantAggregate {
+    fn identity() -> Self {
+        Self::default()
+    }
+
+    fn combine(&mut self, other: &Self) {
+        for pollutant in 0..Pollutant::ALL.len() {
+            self.sums[pollutant] = self.sums[pollutant].wrapping_add(other.sums[pollutant]);
+            self.counts[pollutant] += other.counts[pollutant];
+        }
+    }
+}
+
+impl Group for PollutantAggregate {
+    fn uncombine(&mut self, other: &Self) {
+        for pollutant in 0..Pollutant::ALL.len() {
+            self.sums[pollutant] = self.sums[pollutant].wrapping_sub(other.sums[pollutant]);
+            self.counts[pollutant] -= other.counts[pollutant];
+        }
+    }
+}
+
+#[cfg(test)]
+mod pollutant_test {
+    use super::{Group,Monoid,PollutantAggregate};
+    use crate::aqi::{AQITableVersion,OutOfScalePolicy,Pollutant};
+
+    #[test]
+    fn pollutants_are_aggregated_separately() {
+        let mut aggregate = PollutantAggregate::identity();
+        aggregate.add(Pollutant::O3, 0.05);
+        aggregate.add(Pollutant::O3, 0.07);
+        aggregate.add(Pollutant::NO2, 40.0);
+        assert_eq!(aggregate.count(Pollutant::O3), 2);
+        assert_eq!(aggregate.mean(Pollutant::O3), Some(0.06));
+        assert_eq!(aggregate.mean(Pollutant::NO2), Some(40.0));
+        assert_eq!(aggregate.mean(Pollutant::CO), None);
+
+        let aqis = aggregate.aqis(AQITableVersion::Epa2016, OutOfScalePolicy::Reject);
+        assert_eq!(aqis.get(Pollutant::NO2).map(|aqi| aqi.get()), Some(38));
+        assert_eq!(aqis.get(Pollutant::CO), None);
+        assert_eq!(aqis.dominant().map(|(pollutant, _)| pollutant), Some(Pollutant::O3));
+
+        // removing measurements is exact
+        let mut other = PollutantAggregate::default();
+        other.add(Pollutant::O3, 0.07);
+        other.add(Pollutant::CO, 1.3);
+        let mut combined = aggregate;
+        combined.combine(&other);
+        assert_eq!(combined.mean(Pollutant::CO), Some(1.3));
+        combined.uncombine(&other);
+        assert_eq!(combined, aggregate);
+    }
+}
+
+/// The state of a single window of a [SlidingAggregator].
+#[derive(Debug, Clone)]
+struct SlidingWindow<T> {
+    /// The maximum number of values in the window
+    length: usize,
+    /// The number of values currently in the window
+    count: usize,
+    /// If values are evicted by uncombining them, the aggregate of all values,
+    /// otherwise the aggregate of the newer values not contained in front
+    back: T,
+    /// Only used if values can not be uncombined: the aggregates
+    /// of the older values, where the last item aggregates all of them,
+    /// the second to last all but the oldest and so on (i.e. the second of two stacks)
+    front: Vec<T>,
+}
+
+/// Combines values of type V with aggregates of type T, so a [SlidingAggregator]
+/// can aggregate values owned by someone else (e.g. the queue of an [AnalysisWindow])
+/// without converting and storing them as aggregates first.
+pub trait Combiner<V, T> {
+    /// Combines aggregate with value, which contains newer values.
+    fn combine(&self, aggregate: &mut T, value: &V);
+}
+
+/// A [Combiner] whose combination can be undone, like a [Group].
+pub trait Uncombiner<V, T>: Combiner<V, T> {
+    /// Removes value, which has previously been combined with aggregate
+    /// as its oldest value.
+    fn uncombine(&self, aggregate: &mut T, value: &V);
+}
+
+/// Combines the values of a [Monoid] (or [Group]) with each other.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct MonoidCombiner;
+
+impl<T: Monoid> Combiner<T, T> for MonoidCombiner {
+    fn combine(&self, aggregate: &mut T, value: &T) {
+        aggregate.combine(value);
+    }
+}
+
+impl<T: Group> Uncombiner<T, T> for MonoidCombiner {
+    fn uncombine(&self, aggregate: &mut T, value: &T) {
+        aggregate.uncombine(value);
+    }
+}
+
+/// Aggregates the newest values of several (nested) windows of different lengths,
+/// e.g. the last day and the last five days, of a single queue of values of type V
+/// (combined with the aggregates of type T using a [Combiner] C).
+/// The values are not stored, instead the queue is passed when values are evicted,
+/// so it needs to contain the values of the longest window.
+/// Values which can be uncombined (see [SlidingAggregator::invertible]) are evicted by
+/// uncombining them, all other monoids (e.g. maxima) use the two-stacks algorithm,
+/// both of which need amortized O(1) combinations per value and window.
+#[derive(Debug, Clone)]
+pub struct SlidingAggregator<T, V = T, C = MonoidCombiner> {
+    /// The windows, in the order of their lengths passed to the constructor
+    windows: Vec<SlidingWindow<T>>,
+    /// Combines the values with the aggregates
+    combiner: C,
+    /// Evicts a value from an aggregate, if supported by C
+    uncombine: Option<fn(&C, &mut T, &V)>,
+}
+
+impl<T: Monoid, V, C: Combiner<V, T>> SlidingAggregator<T, V, C> {
+    /// Creates a new, empty SlidingAggregator with windows of the given lengths,
+    /// using the two-stacks algorithm to evict values.
+    pub fn new(lengths: &[usize], combiner: C) -> Self {
+        Self::with_uncombine(lengths, combiner, None)
+    }
+
+    /// Creates a new, empty SlidingAggregator with windows of the given lengths,
+    /// evicting values by uncombining them.
+    pub fn invertible(lengths: &[usize], combiner: C) -> Self where C: Uncombiner<V, T> {
+        Self::with_uncombine(lengths, combiner, Some(C::uncombine))
+    }
+
+    fn with_uncombine(lengths: &[usize], combiner: C, uncombine: Option<fn(&C, &mut T, &V)>) -> Self {
+        assert!(lengths.iter().all(|length| *length > 0), "Window lengths need to be positive");
+        Self {
+            windows: lengths
+                .iter()
+                .map(|length| SlidingWindow { length: *length, count: 0, back: T::identity(), front: vec![] })
+                .collect(),
+            combiner,
+            uncombine,
+        }
+    }
+
+    /// Adds the newest value to all windows.
+    /// Full windows need to be evicted first (see [SlidingAggregator::evict]).
+    pub fn push(&mut self, value: &V) {
+        for window in &mut self.windows {
+            assert!(window.count < window.length, "Full windows need to be evicted before pushing");
+            self.combiner.combine(&mut window.back, value);
+            window.count += 1;
+        }
+    }
+
+    /// Evicts the oldest value from every full window, making room for the next value.
+    /// values is the queue of values (oldest first) ending with the newest pushed value,
+    /// which needs to contain the values of all full windows.
+    pub fn evict<'a, I>(&mut self, values: I)
+    where
+        I: DoubleEndedIterator<Item = &'a V> + ExactSizeIterator + Clone,
+        V: 'a {
+        for window in &mut self.windows {
+            if window.count < window.length {
+                continue;
+            }
+            assert!(values.len() >= window.count, "The values of full windows need to be passed when evicting");
+            // the window contains the newest count values
+            let oldest = values.len() - window.count;
+            match self.uncombine {
+                Some(uncombine) => uncombine(&self.combiner, &mut window.back, values.clone().nth(oldest).unwrap()),
+                None => {
+                    if window.front.is_empty() {
+                        // move all values to the front, aggregating them from the newest to the oldest
+                        let mut suffix = T::identity();
+                        for value in values.clone().skip(oldest).rev() {
+                            let mut aggregate = T::identity();
+                            self.combiner.combine(&mut aggregate, value);
+                            aggregate.combine(&suffix);
+                            window.front.push(aggregate.clone());
+                            suffix = aggregate;
+                        }
+                        window.back = T::identity();
+                    }
+                    window.front.pop();
+                },
+            }
+            window.count -= 1;
+        }
+    }
+
+    /// Returns the number of values in the window with the given index.
+    pub fn count(&self, window: usize) -> usize {
+        self.windows[window].count
+    }
+
+    /// Returns the aggregate of all values in the window with the given index.
+    pub fn aggregate(&self, window: usize) -> Cow<'_, T> {
+        let window = &self.windows[window];
+        match window.front.last() {
+            Some(front) => {
+                let mut aggregate = front.clone();
+                aggregate.combine(&window.back);
+                Cow::Owned(aggregate)
+            },
+            None => Cow::Borrowed(&window.back),
+        }
+    }
+}
+
+#[cfg(test)]
+mod sliding_test {
+    use super::{Monoid,MonoidCombiner,ParticleAggregate,SlidingAggregator};
+
+    /// The maximum of the values, which can not be uncombined
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    struct Max(i32);
+
+    impl Monoid for Max {
+        fn identity() -> Self {
+            Max(i32::MIN)
+        }
+
+        fn combine(&mut self, other: &Self) {
+            self.0 = self.0.max(other.0);
+        }
+    }
+
+    fn values() -> Vec<i32> {
+        (0..50).map(|i| (i * 37) % 23 - 11).collect()
+    }
+
+    #[test]
+    fn nested_windows_match_recomputation() {
+        let lengths = [3, 7, 1];
+        let mut maxima = SlidingAggregator::new(&lengths, MonoidCombiner);
+        let mut means = SlidingAggregator::invertible(&lengths, MonoidCombiner);
+        let values = values();
+        let maxima_values = values.iter().map(|value| Max(*value)).collect::<Vec<_>>();
+        let means_values = values.iter().map(|value| ParticleAggregate::new((*value as f32 + 11.0, 1.0))).collect::<Vec<_>>();
+        for i in 0..values.len() {
+            // the aggregators only need the values of the longest window
+            let queue = i.saturating_sub(7)..i;
+            maxima.evict(maxima_values[queue.clone()].iter());
+            means.evict(means_values[queue].iter());
+            maxima.push(&maxima_values[i]);
+            means.push(&means_values[i]);
+            for (window, length) in lengths.iter().enumerate() {
+                let contained = &values[(i + 1).saturating_sub(*length)..=i];
+                assert_eq!(maxima.count(window), contained.len());
+                assert_eq!(*maxima.aggregate(window), Max(*contained.iter().max().unwrap()));
+                let mean = contained.iter().map(|value| (*value as f32 + 11.0, 1.0)).collect::<ParticleAggregate>();
+                assert_eq!(*means.aggregate(window), mean);
+            }
+        }
+    }
+
+    #[test]
+    fn empty_windows_are_identity() {
+        let maxima = SlidingAggregator::<Max>::new(&[2], MonoidCombiner);
+        assert_eq!(maxima.count(0), 0);
+        assert_eq!(*maxima.aggregate(0), Max(i32::MIN));
+        let means = SlidingAggregator::<ParticleAggregate>::invertible(&[2], MonoidCombiner);
+        assert_eq!(*means.aggregate(0), ParticleAggregate::default());
+    }
+}
+
+/// The AQI of a city during a single 5 minute window and when it occurred.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AQIPeak {
+    /// The AQI of the window, the maximum of the p1 and p2 AQI
+    pub aqi: AQIValue,
+    /// The timestamp (in seconds) of the newest measurement of the window
+    pub timestamp_seconds: i64,
+}
+
+/// The highest and lowest 5 minute AQI of every city, which can be aggregated
+/// over several windows using a (two-stacks) [SlidingAggregator] (see [ExtremesCombiner]).
+/// If several windows have the same AQI, the newest one is kept.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct CityExtremes {
+    /// The window with the highest AQI of each city
+    pub max: std::collections::HashMap<CityId, AQIPeak>,
+    /// The window with the lowest AQI of each city
+    pub min: std::collections::HashMap<CityId, AQIPeak>,
+}
+
+impl CityExtremes {
+    /// Calculates the AQI of every city of a single 5 minute window.
+    /// Values above the AQI scale are handled as defined by policy,
+    /// cities with values outside of the AQI scale are skipped.
+    pub fn from_preaggregate<A: Aggregate>(preaggregate: &PreAggregateData<A>, standard: AQIStandard, policy: OutOfScalePolicy) -> Self {
+        let timestamp_seconds = match preaggregate.timestamp {
+            Some(timestamp) => timestamp,
+            None => return Self::default(),
+        };
+        let peaks = preaggregate.values
+            .iter()
+            .filter_map(|(cityid, aggregate)| {
+                let aqi = AQIReport::from_particles_in(standard, aggregate.p1(), aggregate.p2(), policy).ok()?.aqi();
+                Some((*cityid, AQIPeak { aqi, timestamp_seconds }))
+            })
+            .collect::<std::collections::HashMap<_, _>>();
+        Self {
+            max: peaks.clone(),
+            min: peaks,
+        }
+    }
+
+    /// Recalculates the extremes of the given cities during the preaggregated
+    /// 5 minute windows (oldest first) from scratch, to check the aggregated extremes.
+    #[cfg(test)]
+    pub fn of_cities<'a, A: Aggregate + 'a>(preaggregates: impl Iterator<Item = &'a PreAggregateData<A>>, cities: &[CityId], standard: AQIStandard, policy: OutOfScalePolicy) -> Self {
+        let mut extremes = Self::default();
+        for preaggregate in preaggregates {
+            let timestamp_seconds = match preaggregate.timestamp {
+                Some(timestamp) => timestamp,
+                None => continue,
+            };
+            for cityid in cities {
+                let aqi = match preaggregate.values.get(cityid).map(|aggregate| AQIReport::from_particles_in(standard, aggregate.p1(), aggregate.p2(), policy)) {
+                    Some(Ok(report)) => report.aqi(),
+                    _ => continue,
+                };
+                let peak = AQIPeak { aqi, timestamp_seconds };
+                extremes.max
+                    .entry(*cityid)
+                    .and_modify(|max| if peak.aqi >= max.aqi { *max = peak })
+                    .or_insert(peak);
+                extremes.min
+                    .entry(*cityid)
+                    .and_modify(|min| if peak.aqi <= min.aqi { *min = peak })
+                    .or_insert(peak);
+            }
+        }
+        extremes
+    }
+}
+
+impl Monoid for CityExtremes {
+    fn identity() -> Self {
+        Self::default()
+    }
+
+    fn combine(&mut self, other: &Self) {
+        for (cityid, peak) in &other.max {
+            self.max
+                .entry(*cityid)
+                .and_modify(|max| if peak.aqi >= max.aqi { *max = *peak })
+                .or_insert(*peak);
+        }
+        for (cityid, peak) in &other.min {
+            self.min
+                .entry(*cityid)
+                .and_modify(|min| if peak.aqi <= min.aqi { *min = *peak })
+                .or_insert(*peak);
+        }
+    }
+}
+
+/// Combines the extremes of single 5 minute windows (see [CityExtremes::from_preaggregate])
+/// with aggregated extremes, so a [SlidingAggregator] can aggregate them from a queue of preaggregates.
+#[derive(Debug, Clone, Copy)]
+pub struct ExtremesCombiner {
+    /// The air quality index used to calculate the AQIs
+    pub standard: AQIStandard,
+    /// How values above the AQI scale are handled
+    pub policy: OutOfScalePolicy,
+}
+
+impl<A: Aggregate> Combiner<PreAggregateData<A>, CityExtremes> for ExtremesCombiner {
+    fn combine(&self, aggregate: &mut CityExtremes, value: &PreAggregateData<A>) {
+        aggregate.combine(&CityExtremes::from_preaggregate(value, self.standard, self.policy));
+    }
+}
+
+#[cfg(test)]
+mod extremes_test {
+    use super::{CityExtremes,ExtremesCombiner,ParticleAggregate,PreAggregateData,SlidingAggregator};
+    use crate::aqi::{AQIStandard,OutOfScalePolicy};
+
+    /// Creates a preaggregate containing a single measurement of city 0 at the given time
+    fn preaggregate(p1: f32, p2: f32, timestamp: i64) -> PreAggregateData {
+        PreAggregateData {
+            values: vec![(0, ParticleAggregate::new((p1, p2)))].into_iter().collect(),
+            zipcodes: Default::default(),
+            sensors: Default::default(),
+            maxbatch: Some(0),
+            timestamp: Some(timestamp),
+        }
+    }
+
+    #[test]
+    fn peaks_are_tracked_in_window() {
+        let (standard, policy) = (AQIStandard::UsEpa, OutOfScalePolicy::Reject);
+        let mut extremes = SlidingAggregator::new(&[3], ExtremesCombiner { standard, policy });
+        let mut preaggregates = [(10.0, 5.0), (400.0, 5.0), (20.0, 50.0), (10.0, 5.0), (30.0, 10.0), (5.0, 1.0)]
+            .iter()
+            .enumerate()
+            .map(|(i, (p1, p2))| preaggregate(*p1, *p2, i as i64 * 300))
+            .collect::<Vec<_>>();
+        // a second city, which is only measured in some windows
+        preaggregates[2].values.insert(1, ParticleAggregate::new((50.0, 20.0)));
+        preaggregates[3].values.insert(1, ParticleAggregate::new((5.0, 2.0)));
+        for i in 0..preaggregates.len() {
+            extremes.evict(preaggregates[..i].iter());
+            extremes.push(&preaggregates[i]);
+            let window = extremes.aggregate(0);
+            // the aggregated extremes match the extremes recalculated from scratch
+            let recalculated = CityExtremes::of_cities(preaggregates[i.saturating_sub(2)..=i].iter(), &[0, 1], standard, policy);
+            assert_eq!(*window, recalculated);
+            let (max, min) = (window.max[&0], window.min[&0]);
+            match i {
+                // the p1 peak of 400 µg/m³ is the highest AQI
+                1 | 2 | 3 => assert_eq!(max.timestamp_seconds, 300),
+                // the p2 peak of 50 µg/m³ is the highest one left after evicting 400 µg/m³
+                4 => assert_eq!(max.timestamp_seconds, 600),
+                _ => {},
+            }
+            assert!(max.aqi >= min.aqi);
+            if i == 3 {
+                // same AQI as the first window, which has been evicted
+                assert_eq!(min.timestamp_seconds, 900);
+            }
+        }
+        // of two equal lows, the newest is kept
+        assert_eq!(CityExtremes::of_cities(preaggregates[..4].iter(), &[0], standard, policy).min[&0].timestamp_seconds, 900);
+        // only the given cities are recalculated
+        assert_eq!(CityExtremes::of_cities(preaggregates.iter(), &[2], standard, policy), CityExtremes::default());
+        // windows without measurements contain no peaks
+        let empty = PreAggregateData::<ParticleAggregate> { timestamp: None, ..preaggregate(0.0, 0.0, 0) };
+        assert_eq!(CityExtremes::from_preaggregate(&empty, standard, policy), CityExtremes::default());
+    }
+}
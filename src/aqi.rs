@@ -1,13 +1,15 @@
 /// Represents an AQI value calculated from
 /// particle concentrations
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, PartialOrd)]
 pub struct AQIValue(f32);
 
 use std::ops::RangeInclusive;
+use thiserror::Error;
 
 // Code based on data & formula
 // from https://www.airnow.gov/sites/default/files/2018-05/aqi-technical-assistance-document-may2016.pdf
-// pages 14/15
+// pages 14/15, and the revised breakpoints of
+// https://www.airnow.gov/sites/default/files/2024-05/aqi-technical-assistance-document-may2024.pdf
 
 // The data for these table Rows
 // is based on Table 6
@@ -19,8 +21,8 @@ struct AQITableRow {
     BP: RangeInclusive<f32>,
 }
 
-/// The values for PM2.5, 24-hour 
-const PM25_ROWS : [AQITableRow; 7] = [
+/// The values for PM2.5, 24-hour (2016)
+const PM25_ROWS_2016 : [AQITableRow; 7] = [
     AQITableRow {
         I: 0.0..=50.0,
         BP: 0.0..=12.0,
@@ -51,8 +53,8 @@ const PM25_ROWS : [AQITableRow; 7] = [
     },
 ];
 
-/// The values for PM10, 24-hour 
-const PM10_ROWS : [AQITableRow; 7] = [
+/// The values for PM10, 24-hour (2016)
+const PM10_ROWS_2016 : [AQITableRow; 7] = [
     AQITableRow {
         I: 0.0..=50.0,
         BP: 0.0..=54.0
@@ -83,26 +85,525 @@ const PM10_ROWS : [AQITableRow; 7] = [
     },
 ];
 
+/// The values for PM2.5, 24-hour (2024),
+/// with a lower upper bound of "Good" and a single "Hazardous" band
+const PM25_ROWS_2024 : [AQITableRow; 6] = [
+    AQITableRow {
+        I: 0.0..=50.0,
+        BP: 0.0..=9.0,
+    },
+    AQITableRow {
+        I: 51.0..=100.0,
+        BP: 9.1..=35.4,
+    },
+    AQITableRow {
+        I: 101.0..=150.0,
+        BP: 35.5..=55.4,
+    },
+    AQITableRow {
+        I: 151.0..=200.0,
+        BP: 55.5..=125.4,
+    },
+    AQITableRow {
+        I: 201.0..=300.0,
+        BP: 125.5..=225.4,
+    },
+    AQITableRow {
+        I: 301.0..=500.0,
+        BP: 225.5..=325.4,
+    },
+];
+
+/// The values for PM10, 24-hour (2024),
+/// only differing from 2016 by the single "Hazardous" band
+const PM10_ROWS_2024 : [AQITableRow; 6] = [
+    AQITableRow {
+        I: 0.0..=50.0,
+        BP: 0.0..=54.0
+    },
+    AQITableRow {
+        I: 51.0..=100.0,
+        BP: 55.0..=154.0,
+    },
+    AQITableRow {
+        I: 101.0..=150.0,
+        BP: 155.0..=254.0,
+    },
+    AQITableRow {
+        I: 151.0..=200.0,
+        BP: 255.0..=354.0,
+    },
+    AQITableRow {
+        I: 201.0..=300.0,
+        BP: 355.0..=424.0,
+    },
+    AQITableRow {
+        I: 301.0..=500.0,
+        BP: 425.0..=604.0,
+    },
+];
+
+/// The values for O3 (ppm), 8-hour (2016 and 2024).
+/// The 8-hour values are not used above 300 (the 1-hour values are used instead, which are not supported)
+const O3_ROWS : [AQITableRow; 5] = [
+    AQITableRow {
+        I: 0.0..=50.0,
+        BP: 0.0..=0.054,
+    },
+    AQITableRow {
+        I: 51.0..=100.0,
+        BP: 0.055..=0.070,
+    },
+    AQITableRow {
+        I: 101.0..=150.0,
+        BP: 0.071..=0.085,
+    },
+    AQITableRow {
+        I: 151.0..=200.0,
+        BP: 0.086..=0.105,
+    },
+    AQITableRow {
+        I: 201.0..=300.0,
+        BP: 0.106..=0.200,
+    },
+];
+
+/// The values for CO (ppm), 8-hour (2016)
+const CO_ROWS_2016 : [AQITableRow; 7] = [
+    AQITableRow {
+        I: 0.0..=50.0,
+        BP: 0.0..=4.4,
+    },
+    AQITableRow {
+        I: 51.0..=100.0,
+        BP: 4.5..=9.4,
+    },
+    AQITableRow {
+        I: 101.0..=150.0,
+        BP: 9.5..=12.4,
+    },
+    AQITableRow {
+        I: 151.0..=200.0,
+        BP: 12.5..=15.4,
+    },
+    AQITableRow {
+        I: 201.0..=300.0,
+        BP: 15.5..=30.4,
+    },
+    AQITableRow {
+        I: 301.0..=400.0,
+        BP: 30.5..=40.4,
+    },
+    AQITableRow {
+        I: 401.0..=500.0,
+        BP: 40.5..=50.4,
+    },
+];
+
+/// The values for CO (ppm), 8-hour (2024)
+const CO_ROWS_2024 : [AQITableRow; 6] = [
+    AQITableRow {
+        I: 0.0..=50.0,
+        BP: 0.0..=4.4,
+    },
+    AQITableRow {
+        I: 51.0..=100.0,
+        BP: 4.5..=9.4,
+    },
+    AQITableRow {
+        I: 101.0..=150.0,
+        BP: 9.5..=12.4,
+    },
+    AQITableRow {
+        I: 151.0..=200.0,
+        BP: 12.5..=15.4,
+    },
+    AQITableRow {
+        I: 201.0..=300.0,
+        BP: 15.5..=30.4,
+    },
+    AQITableRow {
+        I: 301.0..=500.0,
+        BP: 30.5..=50.4,
+    },
+];
+
+/// The values for SO2 (ppb), 1-hour (2016).
+/// The EPA uses the 24-hour average above 304 ppb, which is approximated by the 1-hour average
+const SO2_ROWS_2016 : [AQITableRow; 7] = [
+    AQITableRow {
+        I: 0.0..=50.0,
+        BP: 0.0..=35.0,
+    },
+    AQITableRow {
+        I: 51.0..=100.0,
+        BP: 36.0..=75.0,
+    },
+    AQITableRow {
+        I: 101.0..=150.0,
+        BP: 76.0..=185.0,
+    },
+    AQITableRow {
+        I: 151.0..=200.0,
+        BP: 186.0..=304.0,
+    },
+    AQITableRow {
+        I: 201.0..=300.0,
+        BP: 305.0..=604.0,
+    },
+    AQITableRow {
+        I: 301.0..=400.0,
+        BP: 605.0..=804.0,
+    },
+    AQITableRow {
+        I: 401.0..=500.0,
+        BP: 805.0..=1004.0,
+    },
+];
+
+/// The values for SO2 (ppb), 1-hour (2024), see [SO2_ROWS_2016]
+const SO2_ROWS_2024 : [AQITableRow; 6] = [
+    AQITableRow {
+        I: 0.0..=50.0,
+        BP: 0.0..=35.0,
+    },
+    AQITableRow {
+        I: 51.0..=100.0,
+        BP: 36.0..=75.0,
+    },
+    AQITableRow {
+        I: 101.0..=150.0,
+        BP: 76.0..=185.0,
+    },
+    AQITableRow {
+        I: 151.0..=200.0,
+        BP: 186.0..=304.0,
+    },
+    AQITableRow {
+        I: 201.0..=300.0,
+        BP: 305.0..=604.0,
+    },
+    AQITableRow {
+        I: 301.0..=500.0,
+        BP: 605.0..=1004.0,
+    },
+];
+
+/// The values for NO2 (ppb), 1-hour (2016)
+const NO2_ROWS_2016 : [AQITableRow; 7] = [
+    AQITableRow {
+        I: 0.0..=50.0,
+        BP: 0.0..=53.0,
+    },
+    AQITableRow {
+        I: 51.0..=100.0,
+        BP: 54.0..=100.0,
+    },
+    AQITableRow {
+        I: 101.0..=150.0,
+        BP: 101.0..=360.0,
+    },
+    AQITableRow {
+        I: 151.0..=200.0,
+        BP: 361.0..=649.0,
+    },
+    AQITableRow {
+        I: 201.0..=300.0,
+        BP: 650.0..=1249.0,
+    },
+    AQITableRow {
+        I: 301.0..=400.0,
+        BP: 1250.0..=1649.0,
+    },
+    AQITableRow {
+        I: 401.0..=500.0,
+        BP: 1650.0..=2049.0,
+    },
+];
+
+/// The values for NO2 (ppb), 1-hour (2024)
+const NO2_ROWS_2024 : [AQITableRow; 6] = [
+    AQITableRow {
+        I: 0.0..=50.0,
+        BP: 0.0..=53.0,
+    },
+    AQITableRow {
+        I: 51.0..=100.0,
+        BP: 54.0..=100.0,
+    },
+    AQITableRow {
+        I: 101.0..=150.0,
+        BP: 101.0..=360.0,
+    },
+    AQITableRow {
+        I: 151.0..=200.0,
+        BP: 361.0..=649.0,
+    },
+    AQITableRow {
+        I: 201.0..=300.0,
+        BP: 650.0..=1249.0,
+    },
+    AQITableRow {
+        I: 301.0..=500.0,
+        BP: 1250.0..=2049.0,
+    },
+];
+
+/// The versions of the EPA AQI tables.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum AQITableVersion {
+    /// The tables of the technical assistance document of May 2016,
+    /// as required by the DEBS challenge
+    #[default]
+    Epa2016,
+    /// The tables of the technical assistance document of May 2024,
+    /// with revised PM2.5 breakpoints
+    Epa2024,
+}
+
+impl AQITableVersion {
+    /// All versions, oldest first.
+    pub const ALL: [AQITableVersion; 2] = [AQITableVersion::Epa2016, AQITableVersion::Epa2024];
+
+    /// returns the year the tables were published
+    pub fn year(self) -> u16 {
+        match self {
+            AQITableVersion::Epa2016 => 2016,
+            AQITableVersion::Epa2024 => 2024,
+        }
+    }
+
+    /// returns the table for PM2.5
+    fn pm25_rows(self) -> &'static [AQITableRow] {
+        match self {
+            AQITableVersion::Epa2016 => &PM25_ROWS_2016,
+            AQITableVersion::Epa2024 => &PM25_ROWS_2024,
+        }
+    }
+
+    /// returns the table for PM10
+    fn pm10_rows(self) -> &'static [AQITableRow] {
+        match self {
+            AQITableVersion::Epa2016 => &PM10_ROWS_2016,
+            AQITableVersion::Epa2024 => &PM10_ROWS_2024,
+        }
+    }
+
+    /// returns the table for pollutant
+    fn rows(self, pollutant: Pollutant) -> &'static [AQITableRow] {
+        match (pollutant, self) {
+            (Pollutant::PM10, _) => self.pm10_rows(),
+            (Pollutant::PM25, _) => self.pm25_rows(),
+            (Pollutant::O3, _) => &O3_ROWS,
+            (Pollutant::CO, AQITableVersion::Epa2016) => &CO_ROWS_2016,
+            (Pollutant::CO, AQITableVersion::Epa2024) => &CO_ROWS_2024,
+            (Pollutant::SO2, AQITableVersion::Epa2016) => &SO2_ROWS_2016,
+            (Pollutant::SO2, AQITableVersion::Epa2024) => &SO2_ROWS_2024,
+            (Pollutant::NO2, AQITableVersion::Epa2016) => &NO2_ROWS_2016,
+            (Pollutant::NO2, AQITableVersion::Epa2024) => &NO2_ROWS_2024,
+        }
+    }
+
+    /// calculate the AQI from a concentration of pollutant (in the unit of [Pollutant::unit],
+    /// averaged over [Pollutant::averaging_hours]) using this version of the table,
+    /// handling concentrations above the highest breakpoint as defined by policy.
+    /// The concentration is rounded to the precision of the breakpoints ([Pollutant::decimals]) first.
+    pub fn aqi(self, pollutant: Pollutant, concentration: f32, policy: OutOfScalePolicy) -> Result<AQIValue, OutOfScale> {
+        let scale = 10.0f32.powi(pollutant.decimals());
+        let concentration = (concentration * scale).round() / scale;
+        aqi_from_table_with(self.rows(pollutant), concentration, policy)
+    }
+
+    /// calculate the AQI from a PM2.5 concentration (p2) using this version of the table,
+    /// handling concentrations above the highest breakpoint as defined by policy
+    pub fn pm25(self, pm25: f32, policy: OutOfScalePolicy) -> Result<AQIValue, OutOfScale> {
+        self.aqi(Pollutant::PM25, pm25, policy)
+    }
+
+    /// calculate the AQI from a PM10 concentration (p1) using this version of the table,
+    /// handling concentrations above the highest breakpoint as defined by policy
+    pub fn pm10(self, pm10: f32, policy: OutOfScalePolicy) -> Result<AQIValue, OutOfScale> {
+        self.aqi(Pollutant::PM10, pm10, policy)
+    }
+}
+
+/// Defines how concentrations are rounded before looking up the breakpoints
+/// in the exact calculation (see [AQITableVersion::pm25_asdebs]).
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum InputRounding {
+    /// Round to the nearest value (halfway cases away from zero),
+    /// as done by the DEBS reference and [AQIValue::from_pm25]/[AQIValue::from_pm10]
+    #[default]
+    Round,
+    /// Truncate, as required by the EPA technical assistance document
+    Truncate,
+}
+
+impl InputRounding {
+    /// rounds value (in units of the precision of the breakpoints)
+    fn apply(self, value: f64) -> f64 {
+        match self {
+            InputRounding::Round => value.round(),
+            InputRounding::Truncate => value.trunc(),
+        }
+    }
+}
+
+/// converts a breakpoint into tenths of µg/m³
+fn to_tenths(breakpoint: f32) -> i64 {
+    (f64::from(breakpoint) * 10.0).round() as i64
+}
+
+/// calculates the AQI in DEBS format (see [AQIValue::get_asdebs]) of the concentration
+/// (in tenths of µg/m³, already rounded) using exact integer arithmetic,
+/// handling concentrations above the highest breakpoint as defined by policy.
+fn aqi_asdebs_from_table(table: &[AQITableRow], tenths: f64, policy: OutOfScalePolicy) -> Result<i32, OutOfScale> {
+    let out_of_scale = OutOfScale { concentration: (tenths / 10.0) as f32 };
+    let top = table.last().expect("AQI table is empty");
+    let saturated = (*top.I.end() as i64 * 1000) as i32;
+    // NaN, negative or so large that the AQI would overflow
+    if !(0.0..=f64::from(i32::MAX)).contains(&tenths) {
+        return match policy {
+            OutOfScalePolicy::Saturate if tenths > 0.0 => Ok(saturated),
+            OutOfScalePolicy::Extrapolate if tenths > 0.0 && tenths.is_finite() => Ok(OUT_OF_SCALE_ASDEBS - 1),
+            _ => Err(out_of_scale),
+        };
+    }
+    let tenths = tenths as i64;
+    let row = match table.iter().find(|row| (to_tenths(*row.BP.start())..=to_tenths(*row.BP.end())).contains(&tenths)) {
+        Some(row) => row,
+        None if tenths > to_tenths(*top.BP.end()) => match policy {
+            OutOfScalePolicy::Extrapolate => top,
+            OutOfScalePolicy::Saturate => return Ok(saturated),
+            OutOfScalePolicy::Reject => return Err(out_of_scale),
+        },
+        None => return Err(out_of_scale),
+    };
+    let (i_lo, i_hi) = (*row.I.start() as i64, *row.I.end() as i64);
+    let (bp_lo, bp_hi) = (to_tenths(*row.BP.start()), to_tenths(*row.BP.end()));
+    // 1000 * (I_Hi - I_Lo) / (BP_Hi - BP_Lo) * (C_p - BP_Lo), rounded half away from zero
+    let numerator = 1000 * (i_hi - i_lo) * (tenths - bp_lo);
+    let denominator = bp_hi - bp_lo;
+    let asdebs = 1000 * i_lo + (2 * numerator + denominator) / (2 * denominator);
+    // extrapolated AQIs are clamped below OUT_OF_SCALE_ASDEBS, as AQIValue::get_asdebs does
+    Ok(asdebs.min(i64::from(OUT_OF_SCALE_ASDEBS - 1)) as i32)
+}
+
+impl AQITableVersion {
+    /// calculates the AQI in DEBS format of a PM2.5 concentration (p2) exactly,
+    /// using integer arithmetic instead of f32 (see [AQITableVersion::pm25]).
+    /// The concentration is rounded to one decimal as defined by rounding,
+    /// concentrations above the highest breakpoint are handled as defined by policy.
+    pub fn pm25_asdebs(self, pm25: f32, rounding: InputRounding, policy: OutOfScalePolicy) -> Result<i32, OutOfScale> {
+        // the product of an f32 and 10 is exact in f64
+        let tenths = rounding.apply(f64::from(pm25) * 10.0);
+        aqi_asdebs_from_table(self.pm25_rows(), tenths, policy)
+    }
+
+    /// calculates the AQI in DEBS format of a PM10 concentration (p1) exactly,
+    /// using integer arithmetic instead of f32 (see [AQITableVersion::pm10]).
+    /// The concentration is rounded to an integer as defined by rounding,
+    /// concentrations above the highest breakpoint are handled as defined by policy.
+    pub fn pm10_asdebs(self, pm10: f32, rounding: InputRounding, policy: OutOfScalePolicy) -> Result<i32, OutOfScale> {
+        let tenths = rounding.apply(f64::from(pm10)) * 10.0;
+        aqi_asdebs_from_table(self.pm10_rows(), tenths, policy)
+    }
+}
+
+impl std::str::FromStr for AQITableVersion {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        AQITableVersion::ALL
+            .iter()
+            .copied()
+            .find(|version| version.year().to_string() == name)
+            .ok_or_else(|| format!("Unknown AQI table version {}", name))
+    }
+}
+
+
+/// The AQI in DEBS format (see [AQIValue::get_asdebs]) used
+/// in results for AQIs outside the scale.
+pub const OUT_OF_SCALE_ASDEBS: i32 = i32::MAX;
+
+/// The error returned for concentrations outside the AQI scale,
+/// i.e. negative (or NaN) concentrations and, depending on the
+/// [OutOfScalePolicy], concentrations above the highest breakpoint.
+#[derive(Error, Debug, Copy, Clone, PartialEq)]
+#[error("Concentration {concentration} is outside the AQI scale")]
+pub struct OutOfScale {
+    /// The (rounded) concentration
+    pub concentration: f32,
+}
+
+/// Defines how concentrations above the highest breakpoint of the AQI table are handled.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum OutOfScalePolicy {
+    /// Concentrations above the highest breakpoint are [OutOfScale],
+    /// reported as [OUT_OF_SCALE_ASDEBS] in the DEBS results
+    #[default]
+    Reject,
+    /// The highest band of the table is extrapolated linearly,
+    /// resulting in AQIs above 500 ("beyond the index")
+    Extrapolate,
+    /// The AQI is capped at 500
+    Saturate,
+}
+
+impl std::str::FromStr for OutOfScalePolicy {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "reject" => Ok(OutOfScalePolicy::Reject),
+            "extrapolate" => Ok(OutOfScalePolicy::Extrapolate),
+            "saturate" => Ok(OutOfScalePolicy::Saturate),
+            _ => Err(format!("Unknown out of scale policy {}", name)),
+        }
+    }
+}
+
+/// calculates the AQI value of the concentration C_p
+/// using the given row of the lookup table.
+/// Variable names correspond to those in Eqation 1.
+#[allow(non_snake_case)]
+#[inline]
+fn aqi_from_row(row: &AQITableRow, C_p: f32) -> AQIValue {
+    let I_Lo = row.I.start();
+    let I_Hi = row.I.end();
+    let BP_Lo = row.BP.start();
+    let BP_Hi = row.BP.end();
+    let aqi = (I_Hi - I_Lo) / (BP_Hi - BP_Lo) * (C_p - BP_Lo) + I_Lo;
+    debug_assert!(aqi >= 0.0);
+    AQIValue(aqi)
+}
 
 /// calculates the AQI value using the given lookup table and concentration.
 /// and returns it.
 /// returns None if C_p is outside the valid Range
-/// Variable names correspond to those in Eqation 1.
 #[allow(non_snake_case)]
 #[inline]
 fn aqi_from_table(table: &[AQITableRow], C_p: f32) -> Option<AQIValue> {
-    for row in table {
-        if row.BP.contains(&C_p) {
-            let I_Lo = row.I.start();
-            let I_Hi = row.I.end();
-            let BP_Lo = row.BP.start();
-            let BP_Hi = row.BP.end();
-            let aqi = (I_Hi - I_Lo) / (BP_Hi - BP_Lo) * (C_p - BP_Lo) + I_Lo;
-            debug_assert!(aqi >= 0.0);
-            return Some(AQIValue(aqi));
-        }
+    table
+        .iter()
+        .find(|row| row.BP.contains(&C_p))
+        .map(|row| aqi_from_row(row, C_p))
+}
+
+/// calculates the AQI value like [aqi_from_table], but handles
+/// concentrations above the highest breakpoint as defined by policy.
+#[allow(non_snake_case)]
+fn aqi_from_table_with(table: &[AQITableRow], C_p: f32, policy: OutOfScalePolicy) -> Result<AQIValue, OutOfScale> {
+    if let Some(aqi) = aqi_from_table(table, C_p) {
+        return Ok(aqi);
+    }
+    let top = table.last().expect("AQI table is empty");
+    match policy {
+        OutOfScalePolicy::Extrapolate if C_p > *top.BP.end() => Ok(aqi_from_row(top, C_p)),
+        OutOfScalePolicy::Saturate if C_p > *top.BP.end() => Ok(AQIValue(*top.I.end())),
+        _ => Err(OutOfScale { concentration: C_p }),
     }
-    return None;
 }
 
 impl AQIValue {
@@ -112,27 +613,669 @@ impl AQIValue {
     }
 
     /// returns the AQI value, rounded to 3 digits
-    /// and multiplited by 1000
+    /// and multiplited by 1000. Extrapolated AQIs too large for the DEBS format
+    /// are clamped below [OUT_OF_SCALE_ASDEBS], so they are not mistaken for AQIs outside the scale.
     pub fn get_asdebs(self) -> i32 {
-        (self.0 * 1000.0).round() as i32
+        let asdebs = (self.0 * 1000.0).round();
+        if asdebs >= OUT_OF_SCALE_ASDEBS as f32 {
+            OUT_OF_SCALE_ASDEBS - 1
+        } else {
+            asdebs as i32
+        }
     }
 
     /// calculate the AQI from a PM2.5 concentration (p2)
     pub fn from_pm25(pm25: f32) -> Option<AQIValue> {
-        let pm25 = (pm25 * 10.0).round() / 10.0;
-        aqi_from_table(&PM25_ROWS, pm25)
+        Self::from_pm25_with(pm25, OutOfScalePolicy::Reject).ok()
     }
     
     /// calculate the AQI from a PM10 concentration (p1)
     pub fn from_pm10(pm10: f32) -> Option<AQIValue> {
-        let pm10 = pm10.round();
-        aqi_from_table(&PM10_ROWS, pm10)
+        Self::from_pm10_with(pm10, OutOfScalePolicy::Reject).ok()
+    }
+
+    /// calculate the AQI from a PM2.5 concentration (p2) using the 2016 table,
+    /// handling concentrations above 500.4 as defined by policy
+    pub fn from_pm25_with(pm25: f32, policy: OutOfScalePolicy) -> Result<AQIValue, OutOfScale> {
+        AQITableVersion::Epa2016.pm25(pm25, policy)
+    }
+
+    /// calculate the AQI from a PM10 concentration (p1) using the 2016 table,
+    /// handling concentrations above 604 as defined by policy
+    pub fn from_pm10_with(pm10: f32, policy: OutOfScalePolicy) -> Result<AQIValue, OutOfScale> {
+        AQITableVersion::Epa2016.pm10(pm10, policy)
+    }
+
+    /// converts an AQI value in DEBS format (see [AQIValue::get_asdebs])
+    /// back into an AQIValue, returns None for negative values and [OUT_OF_SCALE_ASDEBS]
+    pub fn from_asdebs(aqi: i32) -> Option<AQIValue> {
+        if (0..OUT_OF_SCALE_ASDEBS).contains(&aqi) {
+            Some(AQIValue(aqi as f32 / 1000.0))
+        } else {
+            None
+        }
+    }
+}
+
+/// The AQI categories defined by the EPA (Table 5 of the technical assistance document).
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum AQICategory {
+    /// AQI 0 to 50
+    Good,
+    /// AQI 51 to 100
+    Moderate,
+    /// AQI 101 to 150
+    UnhealthyForSensitiveGroups,
+    /// AQI 151 to 200
+    Unhealthy,
+    /// AQI 201 to 300
+    VeryUnhealthy,
+    /// AQI 301 and above
+    Hazardous,
+}
+
+impl AQICategory {
+    /// returns the category of the (rounded) AQI value
+    pub fn from_aqi(aqi: AQIValue) -> Self {
+        match aqi.get() {
+            0..=50 => AQICategory::Good,
+            51..=100 => AQICategory::Moderate,
+            101..=150 => AQICategory::UnhealthyForSensitiveGroups,
+            151..=200 => AQICategory::Unhealthy,
+            201..=300 => AQICategory::VeryUnhealthy,
+            _ => AQICategory::Hazardous,
+        }
+    }
+
+    /// returns the name of the category as used by the EPA
+    pub fn name(self) -> &'static str {
+        match self {
+            AQICategory::Good => "Good",
+            AQICategory::Moderate => "Moderate",
+            AQICategory::UnhealthyForSensitiveGroups => "Unhealthy for Sensitive Groups",
+            AQICategory::Unhealthy => "Unhealthy",
+            AQICategory::VeryUnhealthy => "Very Unhealthy",
+            AQICategory::Hazardous => "Hazardous",
+        }
+    }
+
+    /// returns the colour of the category as hex RGB code
+    pub fn color(self) -> &'static str {
+        match self {
+            AQICategory::Good => "#00E400",
+            AQICategory::Moderate => "#FFFF00",
+            AQICategory::UnhealthyForSensitiveGroups => "#FF7E00",
+            AQICategory::Unhealthy => "#FF0000",
+            AQICategory::VeryUnhealthy => "#8F3F97",
+            AQICategory::Hazardous => "#7E0023",
+        }
+    }
+
+    /// returns the health message of the category
+    pub fn health_message(self) -> &'static str {
+        match self {
+            AQICategory::Good => "Air quality is satisfactory, and air pollution poses little or no risk.",
+            AQICategory::Moderate => "Air quality is acceptable. However, there may be a risk for some people, particularly those who are unusually sensitive to air pollution.",
+            AQICategory::UnhealthyForSensitiveGroups => "Members of sensitive groups may experience health effects. The general public is less likely to be affected.",
+            AQICategory::Unhealthy => "Some members of the general public may experience health effects; members of sensitive groups may experience more serious health effects.",
+            AQICategory::VeryUnhealthy => "Health alert: The risk of health effects is increased for everyone.",
+            AQICategory::Hazardous => "Health warning of emergency conditions: everyone is more likely to be affected.",
+        }
+    }
+}
+
+/// The pollutants the AQI is calculated from.
+/// The DEBS data only contains particulate matter (PM10 and PM2.5),
+/// measurements of the other pollutants can be imported from other sensor networks
+/// (see [pollutant_measurements_from_csv](crate::import::pollutant_measurements_from_csv)).
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Pollutant {
+    /// Particles < 10µm (p1)
+    PM10,
+    /// Particles < 2.5µm (p2)
+    PM25,
+    /// Ozone
+    O3,
+    /// Carbon monoxide
+    CO,
+    /// Sulfur dioxide
+    SO2,
+    /// Nitrogen dioxide
+    NO2,
+}
+
+impl Pollutant {
+    /// All pollutants, in the order of their discriminants.
+    pub const ALL: [Pollutant; 6] = [
+        Pollutant::PM10,
+        Pollutant::PM25,
+        Pollutant::O3,
+        Pollutant::CO,
+        Pollutant::SO2,
+        Pollutant::NO2,
+    ];
+
+    /// returns the usual name of the pollutant
+    pub fn name(self) -> &'static str {
+        match self {
+            Pollutant::PM10 => "PM10",
+            Pollutant::PM25 => "PM2.5",
+            Pollutant::O3 => "O3",
+            Pollutant::CO => "CO",
+            Pollutant::SO2 => "SO2",
+            Pollutant::NO2 => "NO2",
+        }
+    }
+
+    /// returns the unit of the concentrations of the pollutant
+    pub fn unit(self) -> &'static str {
+        match self {
+            Pollutant::PM10 | Pollutant::PM25 => "µg/m³",
+            Pollutant::O3 | Pollutant::CO => "ppm",
+            Pollutant::SO2 | Pollutant::NO2 => "ppb",
+        }
+    }
+
+    /// returns the number of hours the concentrations are averaged over by the EPA AQI
+    pub fn averaging_hours(self) -> u32 {
+        match self {
+            Pollutant::PM10 | Pollutant::PM25 => 24,
+            Pollutant::O3 | Pollutant::CO => 8,
+            Pollutant::SO2 | Pollutant::NO2 => 1,
+        }
+    }
+
+    /// returns the number of decimals of the breakpoints of the EPA AQI
+    fn decimals(self) -> i32 {
+        match self {
+            Pollutant::PM10 | Pollutant::SO2 | Pollutant::NO2 => 0,
+            Pollutant::PM25 | Pollutant::CO => 1,
+            Pollutant::O3 => 3,
+        }
+    }
+}
+
+impl std::str::FromStr for Pollutant {
+    type Err = String;
+
+    /// parses the name of the pollutant (see [Pollutant::name]), ignoring case,
+    /// PM2.5 may also be written as PM25
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        let name = name.trim().to_ascii_uppercase();
+        Pollutant::ALL
+            .iter()
+            .copied()
+            .find(|pollutant| pollutant.name() == name || (*pollutant == Pollutant::PM25 && name == "PM25"))
+            .ok_or_else(|| format!("Unknown pollutant {}", name))
+    }
+}
+
+/// The AQIs of multiple pollutants, e.g. of a city.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct PollutantAQIs([Option<AQIValue>; Pollutant::ALL.len()]);
+
+impl PollutantAQIs {
+    /// returns the AQI of pollutant, if known
+    pub fn get(&self, pollutant: Pollutant) -> Option<AQIValue> {
+        self.0[pollutant as usize]
+    }
+
+    /// sets the AQI of pollutant
+    pub fn set(&mut self, pollutant: Pollutant, aqi: Option<AQIValue>) {
+        self.0[pollutant as usize] = aqi;
+    }
+
+    /// returns the pollutants with known AQIs and their AQIs
+    pub fn iter(&self) -> impl Iterator<Item = (Pollutant, AQIValue)> + '_ {
+        Pollutant::ALL
+            .iter()
+            .filter_map(move |pollutant| self.get(*pollutant).map(|aqi| (*pollutant, aqi)))
+    }
+
+    /// returns the pollutant with the highest AQI (the overall AQI) and its AQI,
+    /// None if no AQI is known
+    pub fn dominant(&self) -> Option<(Pollutant, AQIValue)> {
+        self.iter().fold(None, |dominant, (pollutant, aqi)| match dominant {
+            Some((_, max)) if max >= aqi => dominant,
+            _ => Some((pollutant, aqi)),
+        })
+    }
+}
+
+/// The AQI of both pollutants, along with the overall AQI (the maximum of both)
+/// and its category.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct AQIReport {
+    /// The AQI of the PM10 concentration (p1)
+    pub aqip1: AQIValue,
+    /// The AQI of the PM2.5 concentration (p2)
+    pub aqip2: AQIValue,
+}
+
+impl AQIReport {
+    /// creates a report from the AQIs of both pollutants
+    pub fn new(aqip1: AQIValue, aqip2: AQIValue) -> Self {
+        Self { aqip1, aqip2 }
+    }
+
+    /// calculates the report from the p1 and p2 concentrations,
+    /// returns None if either is outside the AQI scale
+    pub fn from_particles(p1: f32, p2: f32) -> Option<Self> {
+        Self::from_particles_with(p1, p2, OutOfScalePolicy::Reject).ok()
+    }
+
+    /// calculates the report from the p1 and p2 concentrations,
+    /// handling concentrations above the AQI scale as defined by policy
+    pub fn from_particles_with(p1: f32, p2: f32, policy: OutOfScalePolicy) -> Result<Self, OutOfScale> {
+        Self::from_particles_in(AQIStandard::UsEpa, p1, p2, policy)
+    }
+
+    /// calculates the report from the p1 and p2 concentrations using the index of standard,
+    /// handling concentrations above its scale as defined by policy.
+    /// Note that [AQIReport::category] and [AQIReport::is_good_air] always use the EPA categories,
+    /// use [AirQualityIndex::category] for the categories of other indices.
+    pub fn from_particles_in(standard: AQIStandard, p1: f32, p2: f32, policy: OutOfScalePolicy) -> Result<Self, OutOfScale> {
+        let index = standard.index();
+        Ok(Self::new(index.pm10(p1, policy)?, index.pm25(p2, policy)?))
+    }
+
+    /// returns the pollutant with the higher AQI (PM2.5 if both are equal)
+    pub fn dominant_pollutant(&self) -> Pollutant {
+        if self.aqip1.0 > self.aqip2.0 {
+            Pollutant::PM10
+        } else {
+            Pollutant::PM25
+        }
+    }
+
+    /// returns the overall AQI, i.e. the AQI of the dominant pollutant
+    pub fn aqi(&self) -> AQIValue {
+        if self.dominant_pollutant() == Pollutant::PM10 {
+            self.aqip1
+        } else {
+            self.aqip2
+        }
+    }
+
+    /// returns the category of the overall AQI
+    pub fn category(&self) -> AQICategory {
+        AQICategory::from_aqi(self.aqi())
+    }
+
+    /// returns whether the air is good, i.e. the overall AQI
+    /// (and therefore the AQI of both pollutants) is in the category [AQICategory::Good].
+    /// This is the definition of good air used by query 2 of the DEBS challenge.
+    pub fn is_good_air(&self) -> bool {
+        self.category() == AQICategory::Good
     }
 }
 
+/// An air quality index calculated from PM10 and PM2.5 concentrations,
+/// e.g. the US EPA AQI used by the DEBS challenge or a European index.
+/// The index of both pollutants is returned as [AQIValue] (on the scale of the index),
+/// where larger values always mean worse air.
+pub trait AirQualityIndex: std::fmt::Debug + Send + Sync {
+    /// returns the name of the index
+    fn name(&self) -> &'static str;
+
+    /// returns the number of hours the concentrations are averaged over,
+    /// e.g. 24 for indices based on the (running) daily mean
+    fn averaging_hours(&self) -> u32;
+
+    /// calculates the index of a PM10 concentration (p1) averaged over [AirQualityIndex::averaging_hours],
+    /// handling concentrations above the scale as defined by policy (if the scale is bounded)
+    fn pm10(&self, pm10: f32, policy: OutOfScalePolicy) -> Result<AQIValue, OutOfScale>;
+
+    /// calculates the index of a PM2.5 concentration (p2) averaged over [AirQualityIndex::averaging_hours],
+    /// handling concentrations above the scale as defined by policy (if the scale is bounded)
+    fn pm25(&self, pm25: f32, policy: OutOfScalePolicy) -> Result<AQIValue, OutOfScale>;
+
+    /// returns the name of the band (category) of the index value
+    fn category(&self, aqi: AQIValue) -> &'static str;
+}
+
+/// The US EPA AQI of the technical assistance document (24-hour means)
+/// using the given version of the tables (2016 is required by the DEBS challenge).
+#[derive(Debug, Copy, Clone, Default)]
+pub struct UsEpa(pub AQITableVersion);
+
+impl AirQualityIndex for UsEpa {
+    fn name(&self) -> &'static str {
+        match self.0 {
+            AQITableVersion::Epa2016 => "US EPA AQI (2016)",
+            AQITableVersion::Epa2024 => "US EPA AQI (2024)",
+        }
+    }
+
+    fn averaging_hours(&self) -> u32 {
+        24
+    }
+
+    fn pm10(&self, pm10: f32, policy: OutOfScalePolicy) -> Result<AQIValue, OutOfScale> {
+        self.0.pm10(pm10, policy)
+    }
+
+    fn pm25(&self, pm25: f32, policy: OutOfScalePolicy) -> Result<AQIValue, OutOfScale> {
+        self.0.pm25(pm25, policy)
+    }
+
+    fn category(&self, aqi: AQIValue) -> &'static str {
+        AQICategory::from_aqi(aqi).name()
+    }
+}
+
+/// The hourly grid of the Common Air Quality Index (CAQI) for
+/// PM10, 1-hour mean, where an index above 100 is "beyond the index"
+const CAQI_PM10_ROWS : [AQITableRow; 4] = [
+    AQITableRow { I: 0.0..=25.0, BP: 0.0..=25.0 },
+    AQITableRow { I: 25.0..=50.0, BP: 25.0..=50.0 },
+    AQITableRow { I: 50.0..=75.0, BP: 50.0..=90.0 },
+    AQITableRow { I: 75.0..=100.0, BP: 90.0..=180.0 },
+];
+
+/// The hourly grid of the CAQI for PM2.5, 1-hour mean
+const CAQI_PM25_ROWS : [AQITableRow; 4] = [
+    AQITableRow { I: 0.0..=25.0, BP: 0.0..=15.0 },
+    AQITableRow { I: 25.0..=50.0, BP: 15.0..=30.0 },
+    AQITableRow { I: 50.0..=75.0, BP: 30.0..=55.0 },
+    AQITableRow { I: 75.0..=100.0, BP: 55.0..=110.0 },
+];
+
+/// The European Common Air Quality Index (CAQI) using its hourly grid,
+/// a continuous index from 0 to 100, where values above 100
+/// are outside the scale (see [OutOfScalePolicy]).
+#[derive(Debug, Copy, Clone, Default)]
+pub struct Caqi;
+
+impl AirQualityIndex for Caqi {
+    fn name(&self) -> &'static str {
+        "CAQI"
+    }
+
+    fn averaging_hours(&self) -> u32 {
+        1
+    }
+
+    fn pm10(&self, pm10: f32, policy: OutOfScalePolicy) -> Result<AQIValue, OutOfScale> {
+        aqi_from_table_with(&CAQI_PM10_ROWS, pm10, policy)
+    }
+
+    fn pm25(&self, pm25: f32, policy: OutOfScalePolicy) -> Result<AQIValue, OutOfScale> {
+        aqi_from_table_with(&CAQI_PM25_ROWS, pm25, policy)
+    }
+
+    fn category(&self, aqi: AQIValue) -> &'static str {
+        match aqi.0 {
+            x if x < 25.0 => "Very low",
+            x if x < 50.0 => "Low",
+            x if x < 75.0 => "Medium",
+            x if x <= 100.0 => "High",
+            _ => "Very high",
+        }
+    }
+}
+
+/// calculates the index of a banded index, where bands\[i\] is the highest
+/// (rounded) concentration of index i + 1, and all higher concentrations
+/// belong to the index bands.len() + 1.
+fn index_from_bands(bands: &[f32], concentration: f32) -> Result<AQIValue, OutOfScale> {
+    let concentration = concentration.round();
+    if concentration.is_nan() || concentration < 0.0 {
+        return Err(OutOfScale { concentration });
+    }
+    let band = bands
+        .iter()
+        .position(|upper| concentration <= *upper)
+        .unwrap_or(bands.len());
+    Ok(AQIValue((band + 1) as f32))
+}
+
+/// The upper bounds of the bands 1 to 9 of the DAQI for PM10 (24-hour running mean)
+const DAQI_PM10_BANDS : [f32; 9] = [16.0, 33.0, 50.0, 58.0, 66.0, 75.0, 83.0, 91.0, 100.0];
+
+/// The upper bounds of the bands 1 to 9 of the DAQI for PM2.5 (24-hour running mean)
+const DAQI_PM25_BANDS : [f32; 9] = [11.0, 23.0, 35.0, 41.0, 47.0, 53.0, 58.0, 64.0, 70.0];
+
+/// The UK Daily Air Quality Index (DAQI), an index from 1 to 10
+/// based on the 24-hour running mean.
+/// As the highest band is open, no concentration is above the scale.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct Daqi;
+
+impl AirQualityIndex for Daqi {
+    fn name(&self) -> &'static str {
+        "DAQI"
+    }
+
+    fn averaging_hours(&self) -> u32 {
+        24
+    }
+
+    fn pm10(&self, pm10: f32, _policy: OutOfScalePolicy) -> Result<AQIValue, OutOfScale> {
+        index_from_bands(&DAQI_PM10_BANDS, pm10)
+    }
+
+    fn pm25(&self, pm25: f32, _policy: OutOfScalePolicy) -> Result<AQIValue, OutOfScale> {
+        index_from_bands(&DAQI_PM25_BANDS, pm25)
+    }
+
+    fn category(&self, aqi: AQIValue) -> &'static str {
+        match aqi.get() {
+            0..=3 => "Low",
+            4..=6 => "Moderate",
+            7..=9 => "High",
+            _ => "Very High",
+        }
+    }
+}
+
+/// The upper bounds of the categories 1 to 4 of the UBA index for PM10 (24-hour running mean)
+const UBA_PM10_BANDS : [f32; 4] = [20.0, 35.0, 50.0, 100.0];
+
+/// The upper bounds of the categories 1 to 4 of the UBA index for PM2.5 (24-hour running mean)
+const UBA_PM25_BANDS : [f32; 4] = [10.0, 20.0, 25.0, 50.0];
+
+/// The air quality index (Luftqualitätsindex) of the German
+/// Federal Environment Agency (Umweltbundesamt, UBA),
+/// with the categories 1 ("sehr gut") to 5 ("sehr schlecht")
+/// based on the 24-hour running mean.
+/// As the highest category is open, no concentration is above the scale.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct UbaIndex;
+
+impl AirQualityIndex for UbaIndex {
+    fn name(&self) -> &'static str {
+        "UBA Luftqualitätsindex"
+    }
+
+    fn averaging_hours(&self) -> u32 {
+        24
+    }
+
+    fn pm10(&self, pm10: f32, _policy: OutOfScalePolicy) -> Result<AQIValue, OutOfScale> {
+        index_from_bands(&UBA_PM10_BANDS, pm10)
+    }
+
+    fn pm25(&self, pm25: f32, _policy: OutOfScalePolicy) -> Result<AQIValue, OutOfScale> {
+        index_from_bands(&UBA_PM25_BANDS, pm25)
+    }
+
+    fn category(&self, aqi: AQIValue) -> &'static str {
+        match aqi.get() {
+            0..=1 => "sehr gut",
+            2 => "gut",
+            3 => "mäßig",
+            4 => "schlecht",
+            _ => "sehr schlecht",
+        }
+    }
+}
+
+/// The air quality indices which can be selected in the pipeline.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum AQIStandard {
+    /// The US EPA AQI ([UsEpa]) using the 2016 tables, as required by the DEBS challenge
+    #[default]
+    UsEpa,
+    /// The US EPA AQI ([UsEpa]) using the revised 2024 tables
+    UsEpa2024,
+    /// The European CAQI ([Caqi])
+    Caqi,
+    /// The UK DAQI ([Daqi])
+    Daqi,
+    /// The German UBA index ([UbaIndex])
+    Uba,
+}
+
+impl AQIStandard {
+    /// returns the implementation of the index
+    pub fn index(self) -> &'static dyn AirQualityIndex {
+        match self {
+            AQIStandard::UsEpa => &UsEpa(AQITableVersion::Epa2016),
+            AQIStandard::UsEpa2024 => &UsEpa(AQITableVersion::Epa2024),
+            AQIStandard::Caqi => &Caqi,
+            AQIStandard::Daqi => &Daqi,
+            AQIStandard::Uba => &UbaIndex,
+        }
+    }
+
+    /// returns the version of the EPA tables used by the index, None for other indices
+    pub fn table_version(self) -> Option<AQITableVersion> {
+        match self {
+            AQIStandard::UsEpa => Some(AQITableVersion::Epa2016),
+            AQIStandard::UsEpa2024 => Some(AQITableVersion::Epa2024),
+            _ => None,
+        }
+    }
+
+    /// returns whether the index uses the EPA categories ([AQICategory])
+    pub fn is_epa(self) -> bool {
+        self.table_version().is_some()
+    }
+
+    /// calculates the AQI in DEBS format of a PM2.5 concentration (p2), exactly for the
+    /// EPA indices (see [AQITableVersion::pm25_asdebs]), from the f32 AQI for the others.
+    /// The exact AQI differs from the f32 AQI by at most 1 (0.001 AQI), unless f32 rounds
+    /// a concentration almost halfway between two tenths to the other tenth.
+    pub fn pm25_asdebs(self, pm25: f32, policy: OutOfScalePolicy) -> Result<i32, OutOfScale> {
+        match self.table_version() {
+            Some(version) => version.pm25_asdebs(pm25, InputRounding::Round, policy),
+            None => self.index().pm25(pm25, policy).map(AQIValue::get_asdebs),
+        }
+    }
+
+    /// calculates the AQI in DEBS format of a PM10 concentration (p1), see [AQIStandard::pm25_asdebs]
+    pub fn pm10_asdebs(self, pm10: f32, policy: OutOfScalePolicy) -> Result<i32, OutOfScale> {
+        match self.table_version() {
+            Some(version) => version.pm10_asdebs(pm10, InputRounding::Round, policy),
+            None => self.index().pm10(pm10, policy).map(AQIValue::get_asdebs),
+        }
+    }
+}
+
+impl std::str::FromStr for AQIStandard {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "epa" | "epa2016" => Ok(AQIStandard::UsEpa),
+            "epa2024" => Ok(AQIStandard::UsEpa2024),
+            "caqi" => Ok(AQIStandard::Caqi),
+            "daqi" => Ok(AQIStandard::Daqi),
+            "uba" => Ok(AQIStandard::Uba),
+            _ => Err(format!("Unknown AQI standard {}", name)),
+        }
+    }
+}
+
+/// The number of hourly averages used by the NowCast.
+pub const NOWCAST_HOURS: usize = 12;
+
+/// calculates the NowCast concentration used by the EPA for real-time reporting
+/// from the hourly averages of (up to) the last [NOWCAST_HOURS] hours, newest first,
+/// where None marks hours without measurements.
+/// Recent hours are weighted more if the concentrations vary strongly:
+/// the weight factor is the ratio of the smallest and largest concentration,
+/// but at least 0.5 (the factor for particulate matter).
+/// Returns None if less than two of the three newest hours have measurements.
+pub fn nowcast(hourly: &[Option<f32>]) -> Option<f32> {
+    let hourly = &hourly[..hourly.len().min(NOWCAST_HOURS)];
+    if hourly.iter().take(3).flatten().count() < 2 {
+        return None;
+    }
+    let (min, max) = hourly
+        .iter()
+        .flatten()
+        .fold((f32::INFINITY, 0.0f32), |(min, max), value| (min.min(*value), max.max(*value)));
+    let weight = if max > 0.0 { (min / max).max(0.5) } else { 1.0 };
+    let (sum, weights) = hourly
+        .iter()
+        .enumerate()
+        .filter_map(|(hour, value)| value.map(|value| (value, weight.powi(hour as i32))))
+        .fold((0.0, 0.0), |(sum, weights), (value, weight)| (sum + value * weight, weights + weight));
+    Some(sum / weights)
+}
+
+/// Generates the SQL reference tables and functions (`sql/aqi.sql`)
+/// from the EPA tables of all versions, so the SQL calculation never drifts
+/// from the Rust one. Like [AQITableVersion::pm10]/[AQITableVersion::pm25],
+/// p1 is rounded to integers and p2 to one decimal before looking up the breakpoints.
+/// The functions take the year of the version as optional last argument (2016 by default).
+pub fn aqi_sql() -> String {
+    let values = |rows: fn(AQITableVersion) -> &'static [AQITableRow]| AQITableVersion::ALL
+        .iter()
+        .flat_map(|version| rows(*version)
+            .iter()
+            .map(move |row| format!("({}, {:?}, {:?}, {:?}, {:?})",
+                version.year(), row.I.start(), row.I.end(), row.BP.start(), row.BP.end())))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let table = |name: &str, pollutant: &str, rows: fn(AQITableVersion) -> &'static [AQITableRow]| format!("\
+-- Create table with values to calculate the {pollutant} AQI of each version
+CREATE TABLE {name}(version integer NOT NULL, imin double precision NOT NULL, imax double precision NOT NULL, bpmin double precision NOT NULL, bpmax double precision NOT NULL);
+INSERT INTO {name}(version, imin, imax, bpmin, bpmax) VALUES {values};
+",
+        name = name, pollutant = pollutant, values = values(rows));
+    let function = |name: &str, param: &str, table: &str, rounded: &str| format!("\
+CREATE OR REPLACE FUNCTION {name}(\"{param}\" NUMERIC, \"table_version\" INTEGER DEFAULT {default})
+RETURNS DOUBLE PRECISION
+LANGUAGE SQL
+AS $$
+SELECT ((imax - imin) / (bpmax - bpmin) * ({rounded} - bpmin) + imin) as aqi
+FROM {table}
+WHERE {table}.version = \"table_version\" AND {rounded} between bpmin AND bpmax;
+$$;
+",
+        name = name, param = param, table = table, rounded = rounded, default = AQITableVersion::default().year());
+    format!("\
+-- Generated from the tables in src/aqi.rs by the aqi2sql binary, do not edit.
+
+{pm10_table}
+{pm25_table}
+-- Calculate p1 AQI using the pm10_rows table
+{aqi_p1}
+
+-- Calculate p2 AQI using the pm25_rows table
+-- (same as the p1 function, but rounded to one decimal)
+{aqi_p2}
+-- Calculates the aqi by using two particle concentrations
+CREATE OR REPLACE FUNCTION aqi(\"p1\" NUMERIC, \"p2\" NUMERIC, \"table_version\" INTEGER DEFAULT {default})
+RETURNS DOUBLE PRECISION
+LANGUAGE SQL
+AS $$
+SELECT greatest(aqi_p1(\"p1\", \"table_version\"), aqi_p2(\"p2\", \"table_version\")) as aqi
+$$;
+",
+        pm10_table = table("pm10_rows", "p1 (PM10)", AQITableVersion::pm10_rows),
+        pm25_table = table("pm25_rows", "p2 (PM2.5)", AQITableVersion::pm25_rows),
+        aqi_p1 = function("aqi_p1", "p1", "pm10_rows", "round(\"p1\"::numeric)"),
+        aqi_p2 = function("aqi_p2", "p2", "pm25_rows", "round(\"p2\"::numeric,1)"),
+        default = AQITableVersion::default().year())
+}
+
 #[cfg(test)]
 mod tests {
-    use super::AQIValue;
+    use super::{AQICategory,AQIReport,AQIStandard,AQITableRow,AQITableVersion,AQIValue,InputRounding,OUT_OF_SCALE_ASDEBS,OutOfScale,OutOfScalePolicy,Pollutant,PollutantAQIs,aqi_sql,nowcast,to_tenths};
 
     #[test]
     fn test_all_pm25_aqis_work() {
@@ -167,4 +1310,325 @@ mod tests {
         assert_eq!(AQIValue::from_pm10(7.9).map(AQIValue::get_asdebs), Some(7407));
         assert_eq!(AQIValue::from_pm10(30.263).map(AQIValue::get_asdebs), Some(27778));
     }
+
+    #[test]
+    fn test_aqi_report() {
+        // PM10 AQI 46, PM2.5 AQI 21
+        let report = AQIReport::from_particles(50.0, 5.0).unwrap();
+        assert_eq!(report.dominant_pollutant(), Pollutant::PM10);
+        assert_eq!(report.aqi().get(), 46);
+        assert_eq!(report.category(), AQICategory::Good);
+        assert!(report.is_good_air());
+
+        // PM10 AQI 46, PM2.5 AQI 101
+        let report = AQIReport::from_particles(50.0, 35.5).unwrap();
+        assert_eq!(report.dominant_pollutant(), Pollutant::PM25);
+        assert_eq!(report.category(), AQICategory::UnhealthyForSensitiveGroups);
+        assert_eq!(report.category().color(), "#FF7E00");
+        assert!(!report.is_good_air());
+
+        assert!(AQIReport::from_particles(700.0, 5.0).is_none());
+        assert_eq!(AQICategory::from_aqi(AQIValue::from_pm25(500.4).unwrap()), AQICategory::Hazardous);
+        assert_eq!(AQICategory::from_aqi(AQIValue::from_pm25(12.0).unwrap()), AQICategory::Good);
+        assert_eq!(AQICategory::from_aqi(AQIValue::from_pm25(12.1).unwrap()), AQICategory::Moderate);
+        assert_eq!(AQIValue::from_asdebs(151052).map(AQIValue::get_asdebs), Some(151052));
+        assert!(AQIValue::from_asdebs(i32::MAX).is_none());
+    }
+
+    #[test]
+    fn test_out_of_scale_policies() {
+        let policies = [OutOfScalePolicy::Reject, OutOfScalePolicy::Extrapolate, OutOfScalePolicy::Saturate];
+        for policy in policies.iter().copied() {
+            // the highest breakpoints (after rounding) are within the scale
+            assert_eq!(AQIValue::from_pm25_with(500.44, policy).map(AQIValue::get), Ok(500));
+            assert_eq!(AQIValue::from_pm10_with(604.49, policy).map(AQIValue::get), Ok(500));
+            // negative concentrations are always outside the scale
+            assert_eq!(AQIValue::from_pm25_with(-0.1, policy), Err(OutOfScale { concentration: -0.1 }));
+            assert_eq!(AQIValue::from_pm10_with(-1.0, policy), Err(OutOfScale { concentration: -1.0 }));
+            assert!(AQIValue::from_pm25_with(f32::NAN, policy).is_err());
+        }
+
+        // the first concentrations above the highest breakpoints
+        assert_eq!(AQIValue::from_pm25_with(500.45, OutOfScalePolicy::Reject), Err(OutOfScale { concentration: 500.5 }));
+        assert_eq!(AQIValue::from_pm10_with(604.5, OutOfScalePolicy::Reject), Err(OutOfScale { concentration: 605.0 }));
+        assert_eq!(AQIValue::from_pm25_with(500.5, OutOfScalePolicy::Saturate).map(AQIValue::get_asdebs), Ok(500000));
+        assert_eq!(AQIValue::from_pm10_with(10000.0, OutOfScalePolicy::Saturate).map(AQIValue::get_asdebs), Ok(500000));
+        assert_eq!(AQIValue::from_pm25_with(500.5, OutOfScalePolicy::Extrapolate).map(AQIValue::get_asdebs), Ok(500066));
+        // (500 - 401) / (604 - 505) = 1 AQI per µg/m³
+        assert_eq!(AQIValue::from_pm10_with(700.0, OutOfScalePolicy::Extrapolate).map(AQIValue::get), Ok(596));
+        // extrapolated AQIs too large for the DEBS format do not collide with OUT_OF_SCALE_ASDEBS
+        let huge = AQIValue::from_pm10_with(1e10, OutOfScalePolicy::Extrapolate).unwrap().get_asdebs();
+        assert_eq!(huge, OUT_OF_SCALE_ASDEBS - 1);
+        assert!(AQIValue::from_asdebs(huge).is_some());
+        assert_eq!(AQITableVersion::Epa2016.pm10_asdebs(1e8, InputRounding::Round, OutOfScalePolicy::Extrapolate), Ok(OUT_OF_SCALE_ASDEBS - 1));
+
+        let report = AQIReport::from_particles_with(1000.0, 5.0, OutOfScalePolicy::Saturate).unwrap();
+        assert_eq!(report.dominant_pollutant(), Pollutant::PM10);
+        assert_eq!(report.category(), AQICategory::Hazardous);
+        assert!(AQIReport::from_particles_with(1000.0, 5.0, OutOfScalePolicy::Reject).is_err());
+        assert_eq!("saturate".parse(), Ok(OutOfScalePolicy::Saturate));
+        assert!("unknown".parse::<OutOfScalePolicy>().is_err());
+    }
+
+    #[test]
+    fn test_index_standards() {
+        let policy = OutOfScalePolicy::Reject;
+        let index = |standard: AQIStandard, pm10: f32, pm25: f32| {
+            let index = standard.index();
+            (index.pm10(pm10, policy).map(AQIValue::get), index.pm25(pm25, policy).map(AQIValue::get))
+        };
+        assert_eq!(index(AQIStandard::UsEpa, 54.0, 12.0), (Ok(50), Ok(50)));
+
+        // CAQI: 1-hour means, 50 µg/m³ PM10 and 30 µg/m³ PM2.5 are the upper bound of "Low"
+        let caqi = AQIStandard::Caqi.index();
+        assert_eq!(caqi.averaging_hours(), 1);
+        assert_eq!(index(AQIStandard::Caqi, 50.0, 30.0), (Ok(50), Ok(50)));
+        assert_eq!(index(AQIStandard::Caqi, 70.0, 82.5), (Ok(63), Ok(88)));
+        assert_eq!(index(AQIStandard::Caqi, 180.0, 110.0), (Ok(100), Ok(100)));
+        assert!(caqi.pm10(181.0, policy).is_err());
+        assert_eq!(caqi.pm25(165.0, OutOfScalePolicy::Extrapolate).map(AQIValue::get), Ok(125));
+        assert_eq!(caqi.category(caqi.pm10(70.0, policy).unwrap()), "Medium");
+
+        // DAQI: bands 1 to 10 of the 24-hour running mean
+        let daqi = AQIStandard::Daqi.index();
+        assert_eq!(daqi.averaging_hours(), 24);
+        assert_eq!(index(AQIStandard::Daqi, 16.0, 11.0), (Ok(1), Ok(1)));
+        assert_eq!(index(AQIStandard::Daqi, 17.0, 12.0), (Ok(2), Ok(2)));
+        assert_eq!(index(AQIStandard::Daqi, 75.4, 53.0), (Ok(6), Ok(6)));
+        assert_eq!(index(AQIStandard::Daqi, 101.0, 1000.0), (Ok(10), Ok(10)));
+        assert_eq!(daqi.category(daqi.pm25(60.0, policy).unwrap()), "High");
+
+        // UBA: categories 1 to 5 of the 24-hour running mean
+        let uba = AQIStandard::Uba.index();
+        assert_eq!(index(AQIStandard::Uba, 20.0, 10.0), (Ok(1), Ok(1)));
+        assert_eq!(index(AQIStandard::Uba, 35.0, 20.4), (Ok(2), Ok(2)));
+        assert_eq!(index(AQIStandard::Uba, 50.0, 25.0), (Ok(3), Ok(3)));
+        assert_eq!(index(AQIStandard::Uba, 51.0, 50.0), (Ok(4), Ok(4)));
+        assert_eq!(index(AQIStandard::Uba, 101.0, 51.0), (Ok(5), Ok(5)));
+        assert_eq!(uba.category(uba.pm10(40.0, policy).unwrap()), "mäßig");
+        assert!(uba.pm10(-1.0, policy).is_err());
+
+        assert_eq!("uba".parse(), Ok(AQIStandard::Uba));
+        assert!("unknown".parse::<AQIStandard>().is_err());
+    }
+
+    #[test]
+    fn test_table_versions() {
+        let policy = OutOfScalePolicy::Reject;
+        // the upper bound of "Good" PM2.5 was lowered from 12.0 to 9.0
+        assert_eq!(AQITableVersion::Epa2016.pm25(12.0, policy).map(AQIValue::get), Ok(50));
+        assert_eq!(AQITableVersion::Epa2024.pm25(9.0, policy).map(AQIValue::get), Ok(50));
+        assert_eq!(AQITableVersion::Epa2024.pm25(12.0, policy).map(AQIValue::get), Ok(56));
+        assert_eq!(AQITableVersion::Epa2024.pm25(35.4, policy).map(AQIValue::get), Ok(100));
+        assert_eq!(AQITableVersion::Epa2024.pm25(125.4, policy).map(AQIValue::get), Ok(200));
+        assert_eq!(AQITableVersion::Epa2024.pm25(225.5, policy).map(AQIValue::get), Ok(301));
+        assert_eq!(AQITableVersion::Epa2024.pm25(325.4, policy).map(AQIValue::get), Ok(500));
+        assert!(AQITableVersion::Epa2024.pm25(325.5, policy).is_err());
+        // PM10 only differs in the merged "Hazardous" band
+        for pm10 in 0..=424 {
+            assert_eq!(AQITableVersion::Epa2016.pm10(pm10 as f32, policy), AQITableVersion::Epa2024.pm10(pm10 as f32, policy));
+        }
+        assert_eq!(AQITableVersion::Epa2024.pm10(604.0, policy).map(AQIValue::get), Ok(500));
+        // the default version is the one required by the DEBS challenge
+        assert_eq!(AQITableVersion::default().pm25(55.56, policy), AQIValue::from_pm25_with(55.56, policy));
+        assert_eq!(AQIStandard::UsEpa2024.index().pm25(12.0, policy).map(AQIValue::get), Ok(56));
+        assert_eq!("2024".parse(), Ok(AQITableVersion::Epa2024));
+        assert_eq!("epa2024".parse(), Ok(AQIStandard::UsEpa2024));
+        assert!("2020".parse::<AQITableVersion>().is_err());
+    }
+
+    #[test]
+    fn test_sql_is_generated_from_tables() {
+        let sql = aqi_sql();
+        assert!(sql.contains("(2016, 0.0, 50.0, 0.0, 12.0)"));
+        assert!(sql.contains("(2024, 301.0, 500.0, 225.5, 325.4)"));
+        assert_eq!(include_str!("../sql/aqi.sql"), sql, "sql/aqi.sql is outdated, regenerate it using the aqi2sql binary");
+    }
+
+    #[test]
+    fn test_nowcast() {
+        // constant concentrations
+        assert_eq!(nowcast(&[Some(10.0); 12]), Some(10.0));
+        assert_eq!(nowcast(&[Some(0.0), Some(0.0)]), Some(0.0));
+        // strongly varying concentrations use the minimum weight factor of 0.5
+        assert_eq!(nowcast(&[Some(20.0), Some(10.0)]), Some(25.0 / 1.5));
+        // otherwise the ratio of the minimum and maximum is used
+        let weight = 10.0 / 12.0;
+        assert_eq!(nowcast(&[Some(10.0), Some(12.0)]), Some((10.0 + weight * 12.0) / (1.0 + weight)));
+        // hours without measurements are skipped
+        assert_eq!(nowcast(&[None, Some(10.0), Some(20.0)]), Some((0.5 * 10.0 + 0.25 * 20.0) / 0.75));
+        // only the last 12 hours are used
+        let mut hours = vec![Some(10.0); 12];
+        hours.push(Some(1000.0));
+        assert_eq!(nowcast(&hours), Some(10.0));
+        // at least two of the three newest hours need measurements
+        assert_eq!(nowcast(&[Some(10.0), None, None, Some(10.0)]), None);
+        assert_eq!(nowcast(&[Some(10.0)]), None);
+        assert_eq!(nowcast(&[]), None);
+    }
+
+    /// The tolerance (in DEBS format, i.e. 0.001 AQI) between the exact and the f32 calculation
+    /// of concentrations rounded to the same breakpoint precision, which bounds how much
+    /// the DEBS output of the EPA standards changed by using the exact calculation.
+    const EXACT_TOLERANCE: i32 = 1;
+
+    #[test]
+    fn test_exact_aqis() {
+        let policy = OutOfScalePolicy::Reject;
+        for version in AQITableVersion::ALL.iter().copied() {
+            assert_eq!(version.pm25_asdebs(0.0, InputRounding::Round, policy), Ok(0));
+            assert_eq!(version.pm10_asdebs(604.0, InputRounding::Round, policy), Ok(500000));
+            assert!(version.pm25_asdebs(-0.1, InputRounding::Round, policy).is_err());
+            assert!(version.pm10_asdebs(f32::NAN, InputRounding::Round, policy).is_err());
+        }
+        let epa2016 = AQITableVersion::Epa2016;
+        // 1000 * (200 - 151) / (150.4 - 55.5) * (55.6 - 55.5) + 151000 = 151051.63...
+        assert_eq!(epa2016.pm25_asdebs(55.56, InputRounding::Round, policy), Ok(151052));
+        // 1000 * 50 / 54 * 8 = 7407.4...
+        assert_eq!(epa2016.pm10_asdebs(7.9, InputRounding::Round, policy), Ok(7407));
+        // EPA truncation keeps 12.09 in "Good", rounding moves it to "Moderate"
+        assert_eq!(epa2016.pm25_asdebs(12.09, InputRounding::Truncate, policy), Ok(50000));
+        assert_eq!(epa2016.pm25_asdebs(12.09, InputRounding::Round, policy), Ok(51000));
+        assert_eq!(epa2016.pm10_asdebs(54.9, InputRounding::Truncate, policy), Ok(50000));
+        // concentrations above the scale
+        assert_eq!(epa2016.pm25_asdebs(500.5, InputRounding::Round, policy), Err(OutOfScale { concentration: 500.5 }));
+        assert_eq!(epa2016.pm25_asdebs(500.5, InputRounding::Round, OutOfScalePolicy::Saturate), Ok(500000));
+        assert_eq!(epa2016.pm25_asdebs(f32::INFINITY, InputRounding::Round, OutOfScalePolicy::Saturate), Ok(500000));
+        assert!(epa2016.pm25_asdebs(f32::INFINITY, InputRounding::Round, OutOfScalePolicy::Extrapolate).is_err());
+        assert_eq!(epa2016.pm25_asdebs(500.5, InputRounding::Round, OutOfScalePolicy::Extrapolate), Ok(500066));
+        assert_eq!(epa2016.pm10_asdebs(700.0, InputRounding::Round, OutOfScalePolicy::Extrapolate), Ok(596000));
+        assert_eq!(epa2016.pm10_asdebs(1e10, InputRounding::Round, OutOfScalePolicy::Extrapolate), Ok(OUT_OF_SCALE_ASDEBS - 1));
+    }
+
+    #[test]
+    fn test_exact_aqis_match_f32() {
+        let policies = [OutOfScalePolicy::Reject, OutOfScalePolicy::Extrapolate, OutOfScalePolicy::Saturate];
+        let check = |version: AQITableVersion, pm10: f32, pm25: f32, policy: OutOfScalePolicy| {
+            let exact = version.pm10_asdebs(pm10, InputRounding::Round, policy);
+            let float = version.pm10(pm10, policy).map(AQIValue::get_asdebs);
+            match (exact, float) {
+                (Ok(exact), Ok(float)) => assert!((exact - float).abs() <= EXACT_TOLERANCE, "PM10 {}: {} != {}", pm10, exact, float),
+                (exact, float) => assert_eq!(exact.is_ok(), float.is_ok(), "PM10 {}", pm10),
+            }
+            // f32 may round the concentration to another tenth if it is very close to halfway between two tenths,
+            // in which case the AQIs agree for the concentration as rounded by f32
+            let rounded = (pm25 * 10.0).round() / 10.0;
+            let exact = if f64::from((pm25 * 10.0).round()) != (f64::from(pm25) * 10.0).round() {
+                version.pm25_asdebs(rounded, InputRounding::Round, policy)
+            } else {
+                version.pm25_asdebs(pm25, InputRounding::Round, policy)
+            };
+            let float = version.pm25(pm25, policy).map(AQIValue::get_asdebs);
+            match (exact, float) {
+                (Ok(exact), Ok(float)) => assert!((exact - float).abs() <= EXACT_TOLERANCE, "PM2.5 {}: {} != {}", pm25, exact, float),
+                (exact, float) => assert_eq!(exact.is_ok(), float.is_ok(), "PM2.5 {}", pm25),
+            }
+        };
+        for version in AQITableVersion::ALL.iter().copied() {
+            for policy in policies.iter().copied() {
+                // all breakpoints and the concentrations between them
+                for tenths in 0..=7000 {
+                    check(version, tenths as f32 / 10.0, tenths as f32 / 10.0, policy);
+                }
+                // random concentrations (xorshift, so failures are reproducible)
+                let mut state = 0x2545_f491_4f6c_dd1du64;
+                for _ in 0..100_000 {
+                    state ^= state << 13;
+                    state ^= state >> 7;
+                    state ^= state << 17;
+                    let pm10 = (state % 700_000) as f32 / 1000.0;
+                    let pm25 = ((state >> 32) % 550_000) as f32 / 1000.0;
+                    check(version, pm10, pm25, policy);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_exact_aqis_on_breakpoints() {
+        let policies = [OutOfScalePolicy::Reject, OutOfScalePolicy::Extrapolate, OutOfScalePolicy::Saturate];
+        // reference: asdebs is the exact AQI (in 0.001 AQI) rounded half up, i.e.
+        // asdebs - 1/2 <= 1000 * (I_lo + (I_hi - I_lo) * (C - BP_lo) / (BP_hi - BP_lo)) < asdebs + 1/2
+        let check = |row: &AQITableRow, tenths: i64, step: i64, asdebs: i32| {
+            let (i_lo, i_hi) = (*row.I.start() as i128, *row.I.end() as i128);
+            let (bp_lo, bp_hi) = (to_tenths(*row.BP.start()) / step, to_tenths(*row.BP.end()) / step);
+            let denominator = bp_hi - bp_lo;
+            let numerator = 1000 * (i_lo * denominator as i128 + (i_hi - i_lo) * (tenths / step - bp_lo) as i128);
+            let (asdebs, denominator) = (asdebs as i128, denominator as i128);
+            assert!(2 * asdebs * denominator - denominator <= 2 * numerator, "{} tenths: {} too large", tenths, asdebs);
+            assert!(2 * numerator < 2 * asdebs * denominator + denominator, "{} tenths: {} too small", tenths, asdebs);
+        };
+        for version in AQITableVersion::ALL.iter().copied() {
+            for policy in policies.iter().copied() {
+                for row in version.pm25_rows() {
+                    let (bp_lo, bp_hi) = (to_tenths(*row.BP.start()), to_tenths(*row.BP.end()));
+                    // the breakpoints map exactly to the ends of the AQI range
+                    assert_eq!(version.pm25_asdebs(*row.BP.start(), InputRounding::Round, policy), Ok(*row.I.start() as i32 * 1000));
+                    assert_eq!(version.pm25_asdebs(*row.BP.end(), InputRounding::Round, policy), Ok(*row.I.end() as i32 * 1000));
+                    for tenths in bp_lo..=bp_hi {
+                        let asdebs = version.pm25_asdebs(tenths as f32 / 10.0, InputRounding::Round, policy);
+                        check(row, tenths, 1, asdebs.unwrap());
+                    }
+                }
+                for row in version.pm10_rows() {
+                    let (bp_lo, bp_hi) = (to_tenths(*row.BP.start()), to_tenths(*row.BP.end()));
+                    assert_eq!(version.pm10_asdebs(*row.BP.start(), InputRounding::Round, policy), Ok(*row.I.start() as i32 * 1000));
+                    assert_eq!(version.pm10_asdebs(*row.BP.end(), InputRounding::Round, policy), Ok(*row.I.end() as i32 * 1000));
+                    // PM10 breakpoints are integers
+                    for tenths in (bp_lo..=bp_hi).step_by(10) {
+                        let asdebs = version.pm10_asdebs(tenths as f32 / 10.0, InputRounding::Round, policy);
+                        check(row, tenths, 10, asdebs.unwrap());
+                    }
+                }
+            }
+        }
+        // the EPA standards use the exact calculation, the others the f32 AQI
+        let policy = OutOfScalePolicy::Reject;
+        assert_eq!(AQIStandard::UsEpa.pm25_asdebs(55.56, policy), AQITableVersion::Epa2016.pm25_asdebs(55.56, InputRounding::Round, policy));
+        assert_eq!(AQIStandard::UsEpa2024.pm10_asdebs(7.9, policy), AQITableVersion::Epa2024.pm10_asdebs(7.9, InputRounding::Round, policy));
+        assert_eq!(AQIStandard::Caqi.pm25_asdebs(20.0, policy), AQIStandard::Caqi.index().pm25(20.0, policy).map(AQIValue::get_asdebs));
+    }
+
+    #[test]
+    fn test_other_pollutants() {
+        let policy = OutOfScalePolicy::Reject;
+        let aqi = |version: AQITableVersion, pollutant: Pollutant, concentration: f32| version
+            .aqi(pollutant, concentration, policy)
+            .map(AQIValue::get);
+        for version in AQITableVersion::ALL.iter().copied() {
+            // the upper breakpoints of "Good" and "Moderate"
+            assert_eq!(aqi(version, Pollutant::O3, 0.054), Ok(50));
+            assert_eq!(aqi(version, Pollutant::O3, 0.0704), Ok(100));
+            assert_eq!(aqi(version, Pollutant::CO, 4.4), Ok(50));
+            assert_eq!(aqi(version, Pollutant::CO, 9.4), Ok(100));
+            assert_eq!(aqi(version, Pollutant::SO2, 35.0), Ok(50));
+            assert_eq!(aqi(version, Pollutant::SO2, 75.4), Ok(100));
+            assert_eq!(aqi(version, Pollutant::NO2, 53.0), Ok(50));
+            assert_eq!(aqi(version, Pollutant::NO2, 100.0), Ok(100));
+            // 8-hour O3 is only defined up to 0.200 ppm
+            assert!(aqi(version, Pollutant::O3, 0.201).is_err());
+            assert_eq!(aqi(version, Pollutant::NO2, 2049.0), Ok(500));
+        }
+        // the "Hazardous" bands were merged in 2024
+        assert_eq!(aqi(AQITableVersion::Epa2016, Pollutant::CO, 40.4), Ok(400));
+        assert_eq!(aqi(AQITableVersion::Epa2024, Pollutant::CO, 40.4), Ok(400));
+        assert_eq!(aqi(AQITableVersion::Epa2016, Pollutant::SO2, 700.0), Ok(348));
+        assert_eq!(aqi(AQITableVersion::Epa2024, Pollutant::SO2, 700.0), Ok(348));
+        assert_eq!(aqi(AQITableVersion::Epa2016, Pollutant::NO2, 1649.0), Ok(400));
+        assert_eq!(aqi(AQITableVersion::Epa2024, Pollutant::NO2, 1649.0), Ok(400));
+        // the particulate matter tables are the same as used for the DEBS data
+        assert_eq!(AQITableVersion::Epa2016.aqi(Pollutant::PM25, 55.56, policy), AQIValue::from_pm25_with(55.56, policy));
+
+        assert_eq!("pm2.5".parse(), Ok(Pollutant::PM25));
+        assert_eq!("PM25".parse(), Ok(Pollutant::PM25));
+        assert_eq!(" no2".parse(), Ok(Pollutant::NO2));
+        assert!("CO2".parse::<Pollutant>().is_err());
+
+        let mut aqis = PollutantAQIs::default();
+        assert_eq!(aqis.dominant(), None);
+        aqis.set(Pollutant::O3, AQITableVersion::Epa2016.aqi(Pollutant::O3, 0.06, policy).ok());
+        aqis.set(Pollutant::NO2, AQITableVersion::Epa2016.aqi(Pollutant::NO2, 20.0, policy).ok());
+        assert_eq!(aqis.iter().map(|(pollutant, _)| pollutant).collect::<Vec<_>>(), vec![Pollutant::O3, Pollutant::NO2]);
+        assert_eq!(aqis.dominant().map(|(pollutant, aqi)| (pollutant, aqi.get())), Some((Pollutant::O3, 67)));
+    }
 }
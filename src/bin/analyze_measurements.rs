@@ -1,5 +1,6 @@
 use debs2021::gen::challenger::*;
-use debs2021::io::load_batch;
+use debs2021::io::{load_batch,load_locations};
+use debs2021::AnalysisLocations;
 
 
 use prost_types::Timestamp;
@@ -60,7 +61,7 @@ struct MeasurementInfo {
     outside_germany: usize,
 }
 
-fn analyze_one(batch: &Batch) -> MeasurementInfo {
+fn analyze_one(batch: &Batch, locations: &AnalysisLocations) -> MeasurementInfo {
     let current_timestamps = batch.current
             .iter()
             .flat_map(|x| x.timestamp.clone())
@@ -75,7 +76,7 @@ fn analyze_one(batch: &Batch) -> MeasurementInfo {
     let last_complete = batch.lastyear.len() == last_timestamps.len();
     let last_tsinfo = analyze_timestamps(last_timestamps.iter());
     let outside_germany = batch.lastyear.iter().chain(batch.current.iter()) 
-        .filter(|measurement| measurement.latitude < 47.40724 || measurement.latitude > 54.9079 || measurement.longitude < 5.98815 || measurement.longitude > 14.98853)
+        .filter(|measurement| !locations.in_bounds(measurement.latitude, measurement.longitude))
         .count();
     MeasurementInfo {
         current_tsinfo,
@@ -89,9 +90,12 @@ fn analyze_one(batch: &Batch) -> MeasurementInfo {
 #[tokio::main]
 pub async fn main() {
     let root = std::env::var("DEBS_DATA_ROOT").expect("DEBS_DATA_ROOT not set!");
+    let locations = AnalysisLocations::new(load_locations(&root)
+        .await
+        .expect("Failed to load locations"));
     for i in 0..100000 {
         let batch = load_batch(&root, i).await.expect("Loading of batch failed");
-        let analyzed = analyze_one(&batch);
+        let analyzed = analyze_one(&batch, &locations);
         println!("Batch {}: {:?}", i, analyzed);
     }
 }
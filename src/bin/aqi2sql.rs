@@ -0,0 +1,10 @@
+use debs2021::aqi::aqi_sql;
+
+/// Writes the SQL reference tables and functions generated
+/// from the AQI tables into `sql/aqi.sql` (or the given file).
+fn main() -> std::io::Result<()> {
+    let filename = std::env::args().nth(1).unwrap_or_else(|| "sql/aqi.sql".to_owned());
+    std::fs::write(&filename, aqi_sql())?;
+    println!("Wrote AQI tables and functions to {}", filename);
+    Ok(())
+}
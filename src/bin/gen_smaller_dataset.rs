@@ -1,5 +1,6 @@
 use debs2021::gen::challenger::*;
-use debs2021::io::load_batch;
+use debs2021::io::{load_batch,load_locations};
+use debs2021::AnalysisLocations;
 use prost::Message;
 
 use tokio::fs::File;
@@ -15,14 +16,20 @@ async fn save_batch(root : &str, batch: &Batch) -> Result<(), Box<dyn std::error
     Ok(())
 }
 
-fn area_filter(m: &Measurement) -> bool {
-    m.latitude < (47.40724 + 54.9079) / 4.0
-        && m.longitude < (5.98815 + 14.98853) / 4.0
+fn area_filter(bounds: &geo::Rect<f64>, m: &Measurement) -> bool {
+    let (min_longitude, min_latitude) = bounds.min().x_y();
+    let (max_longitude, max_latitude) = bounds.max().x_y();
+    f64::from(m.latitude) < (min_latitude + max_latitude) / 4.0
+        && f64::from(m.longitude) < (min_longitude + max_longitude) / 4.0
 }
 
 #[tokio::main]
 pub async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let root = std::env::var("DEBS_DATA_ROOT").expect("DEBS_DATA_ROOT not set!");
+    let bounds = AnalysisLocations::new(load_locations(&root)
+        .await
+        .expect("Failed to load locations"))
+        .bounds();
     let mut new_batch = load_batch(&root, 0).await.expect("Loading of batch failed");
     new_batch.current.clear();
     new_batch.lastyear.clear();
@@ -30,11 +37,11 @@ pub async fn main() -> Result<(), Box<dyn std::error::Error>> {
         let batch = load_batch(&root, i).await.expect("Loading of batch failed");
         let mut additional_current : Vec<Measurement> = batch.current
             .into_iter()
-            .filter(area_filter)
+            .filter(|m| area_filter(&bounds, m))
             .collect();
         let mut additional_lastyear : Vec<Measurement> = batch.lastyear
             .into_iter()
-            .filter(area_filter)
+            .filter(|m| area_filter(&bounds, m))
             .collect();
         new_batch.current.append(&mut additional_current);
         new_batch.lastyear.append(&mut additional_lastyear);
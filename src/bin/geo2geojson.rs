@@ -0,0 +1,14 @@
+use debs2021::io::load_locations;
+use debs2021::export::locations_to_geojson;
+use tokio::fs::File;
+use tokio::io::{AsyncWriteExt,BufWriter};
+
+#[tokio::main]
+pub async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let root = std::env::var("DEBS_DATA_ROOT").expect("DEBS_DATA_ROOT not set!");
+    let locations = load_locations(&root).await?;
+    let mut out = BufWriter::new(File::create(format!("{}/locations.geojson", &root)).await?);
+    out.write_all(locations_to_geojson(&locations).to_string().as_bytes()).await?;
+    out.flush().await?;
+    Ok(())
+}
@@ -7,12 +7,18 @@ fn make_polygon_string(polys: &[Polygon]) -> String {
     let polys = polys
         .iter()
         .map(|poly| {
-            assert_eq!(poly.points.first(), poly.points.last());
-            let points = poly.points
-                .iter()
-                .map(|p| format!("{} {}", p.latitude, p.longitude))
+            let rings = std::iter::once(&poly.points)
+                .chain(poly.interiors.iter().map(|ring| &ring.points))
+                .map(|points| {
+                    assert_eq!(points.first(), points.last());
+                    let points = points
+                        .iter()
+                        .map(|p| format!("{} {}", p.latitude, p.longitude))
+                        .collect::<Vec<String>>();
+                    format!("({})", points.join(","))
+                })
                 .collect::<Vec<String>>();
-            format!("(({}))", points.join(","))
+            format!("({})", rings.join(","))
         })
         .collect::<Vec<String>>();
     format!("multipolygon( {} )", polys.join(","))
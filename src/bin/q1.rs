@@ -1,11 +1,14 @@
-use debs2021::io::load_locations;
-use debs2021::pipeline::run_pipeline;
-use debs2021::AnalysisLocations;
+use debs2021::io::{load_analysis_locations,load_pollutant_measurements,load_state_table,store_locations_index};
+use debs2021::pipeline::{PipelineOptions,run_pipeline};
+use debs2021::export::result_to_geojson;
+use debs2021::import::ParticleRows;
+use debs2021::region::RegionHierarchy;
+use debs2021::validate::{QUARANTINE_HEADER,ValidationOptions};
 
 use debs2021::io::LoadError;
 use std::fs::File;
 use debs2021::gen::challenger::Batch;
-use std::io::Read;
+use std::io::{Read,Write};
 use bytes::Bytes;
 use prost::Message;
 fn load_batch(root: &str, num: usize) -> Result<Batch, LoadError> {
@@ -48,9 +51,100 @@ pub async fn main() {
         .into_iter()
         .map(|b| b.expect("Loading of batch failed"));
 
-    let locations = load_locations(&root)
+    let (locations, source_hash, indexed) = load_analysis_locations(&root)
         .await
         .expect("Failed to load locations");
-    let locations = AnalysisLocations::new(locations);
-    run_pipeline(locations, batch_iter);
+    // if set, cities are assigned to states using this CSV file (columns city and state)
+    let mut regions = RegionHierarchy::new(&locations);
+    if let Ok(filename) = std::env::var("DEBS_STATE_TABLE") {
+        let table = load_state_table(&filename, "city", "state")
+            .await
+            .expect("Failed to load state table");
+        regions = regions.with_states(&locations, table);
+    }
+    let options = PipelineOptions {
+        // if set, cities are ranked using this mode (debs, population or density)
+        ranking: std::env::var("DEBS_RANKING")
+            .map(|name| name.parse().expect("Invalid ranking mode"))
+            .unwrap_or_default(),
+        // if set, measurements are aggregated using this statistic (mean, median, trimmed or tukey)
+        statistic: std::env::var("DEBS_STATISTIC")
+            .map(|name| name.parse().expect("Invalid statistic"))
+            .unwrap_or_default(),
+        // if set, cities are averaged using the means of their sensors
+        sensor_means: std::env::var("DEBS_SENSOR_MEANS").is_ok(),
+        // if set, only cities with at least this many sensors are ranked
+        min_sensors: std::env::var("DEBS_MIN_SENSORS")
+            .map(|count| count.parse().expect("Invalid minimum number of sensors"))
+            .unwrap_or(0),
+        // if set, implausible measurements are rejected (not only negative ones)
+        validation: std::env::var("DEBS_VALIDATE").ok().map(|_| ValidationOptions::default()),
+        // if set, the incremental aggregates are verified every this many windows
+        verify_interval: std::env::var("DEBS_VERIFY_INTERVAL")
+            .map(|interval| interval.parse().expect("Invalid verify interval"))
+            .unwrap_or(0),
+        // if set, concentrations above the AQI scale are handled using this policy (reject, extrapolate or saturate)
+        out_of_scale: std::env::var("DEBS_OUT_OF_SCALE")
+            .map(|name| name.parse().expect("Invalid out of scale policy"))
+            .unwrap_or_default(),
+        // if set, AQIs are calculated using this index (epa, caqi, daqi or uba)
+        standard: std::env::var("DEBS_AQI_STANDARD")
+            .map(|name| name.parse().expect("Invalid AQI standard"))
+            .unwrap_or_default(),
+        // if set, the current AQI is the NowCast of the last 12 hours
+        nowcast: std::env::var("DEBS_NOWCAST").is_ok(),
+        // if set, the zipcodes with the highest AQI are printed
+        zipcode_hotspots: std::env::var("DEBS_ZIPCODE_HOTSPOTS").is_ok(),
+        // if set, measurements providing the humidity are corrected (optionally using this hygroscopicity κ)
+        humidity_correction: std::env::var("DEBS_HUMIDITY_CORRECTION")
+            .ok()
+            .map(|kappa| kappa.parse().expect("Invalid hygroscopicity")),
+    };
+    // if set, the AQIs of other pollutants are calculated from this CSV file
+    // (columns timestamp, latitude, longitude, pollutant and value),
+    // rows with particles are only accepted if DEBS_POLLUTANT_PARTICLES is set
+    let particles = match std::env::var("DEBS_POLLUTANT_PARTICLES") {
+        Ok(_) => ParticleRows::Accept,
+        Err(_) => ParticleRows::Reject,
+    };
+    let pollutants = match std::env::var("DEBS_POLLUTANTS") {
+        Ok(filename) => load_pollutant_measurements(&filename, particles)
+            .await
+            .expect("Failed to load pollutant measurements"),
+        Err(_) => vec![],
+    };
+    // if set, rejected measurements are written into this CSV file
+    let mut quarantine = std::env::var("DEBS_QUARANTINE").ok().map(|filename| {
+        let mut file = std::io::BufWriter::new(File::create(&filename).expect("Failed to create quarantine file"));
+        writeln!(file, "{}", QUARANTINE_HEADER).expect("Failed to write quarantine file");
+        file
+    });
+    // if set, the result of every window is exported as GeoJSON into this directory
+    let geojson_out = std::env::var("DEBS_GEOJSON_OUT").ok();
+    // the geometries of the cities are only dissolved once
+    let cities = match geojson_out {
+        Some(_) => locations.cities(),
+        None => vec![],
+    };
+    run_pipeline(&locations, &regions, options, batch_iter, &pollutants, |result, details| {
+        if let Some(dir) = &geojson_out {
+            let filename = format!("{}/result_{}.geojson", dir, result.batch_seq_id);
+            std::fs::write(&filename, result_to_geojson(result, details, &locations, &cities, options.standard).to_string())
+                .expect("Failed to write GeoJSON result");
+        }
+    }, |rejected| {
+        if let Some(file) = &mut quarantine {
+            writeln!(file, "{}", rejected).expect("Failed to write quarantine file");
+        }
+    });
+    if let Some(mut file) = quarantine {
+        file.flush().expect("Failed to write quarantine file");
+    }
+    // if set, the index including the warmed up caches is stored for the next run,
+    // unless a matching index was loaded
+    if std::env::var("DEBS_STORE_INDEX").is_ok() && !indexed {
+        if let Err(e) = store_locations_index(&root, &locations.to_index(source_hash)).await {
+            eprintln!("Failed to store locations index: {}", e);
+        }
+    }
 }
@@ -0,0 +1,287 @@
+use crate::{AnalysisLocations,CityId};
+
+use geo::{LineString,MultiPolygon,Point,Polygon,prelude::{Area,Contains}};
+use std::collections::HashMap;
+
+/// Information about a city, aggregated over all its zipcodes.
+#[derive(Debug, PartialEq)]
+pub struct CityInfo<'a> {
+    /// The id of the city
+    pub cityid: CityId,
+    /// The name of the city
+    pub name: &'a str,
+    /// The zipcodes belonging to the city
+    pub zipcodes: Vec<&'a str>,
+    /// The total area of all zipcodes in km²
+    pub qkm: f64,
+    /// The total number of inhabitants of all zipcodes
+    pub population: i64,
+    /// The union of the polygons of all zipcodes, see [dissolve]
+    pub geometry: MultiPolygon<f64>,
+}
+
+impl AnalysisLocations {
+    /// Returns information about the city with the given id,
+    /// or None if there is no such city.
+    pub fn city(&self, cityid: CityId) -> Option<CityInfo<'_>> {
+        if cityid as usize >= self.known_cities.len() {
+            return None;
+        }
+        let multipolys = self.locations
+            .iter()
+            .filter(|(location, _)| location.cityid == cityid)
+            .collect::<Vec<_>>();
+        Some(self.city_info(cityid, &multipolys))
+    }
+
+    /// Returns information about all cities, ordered by their id.
+    pub fn cities(&self) -> Vec<CityInfo<'_>> {
+        let mut grouped = vec![vec![]; self.known_cities.len()];
+        for location in &self.locations {
+            grouped[location.0.cityid as usize].push(location);
+        }
+        grouped
+            .iter()
+            .enumerate()
+            .map(|(cityid, locations)| self.city_info(cityid as CityId, locations))
+            .collect()
+    }
+
+    /// Returns the total population and area (in km²) of every city, ordered by their id.
+    /// Contrary to [AnalysisLocations::cities], the geometries are not dissolved.
+    pub fn city_totals(&self) -> Vec<(i64, f64)> {
+        let mut totals = vec![(0, 0.0); self.known_cities.len()];
+        for (location, _) in &self.locations {
+            let total = &mut totals[location.cityid as usize];
+            total.0 += i64::from(location.population);
+            total.1 += location.qkm;
+        }
+        totals
+    }
+
+    /// Aggregates the given locations, which all belong to cityid.
+    fn city_info<'a>(&'a self, cityid: CityId, locations: &[&'a (crate::AnalysisLocation, MultiPolygon<f64>)]) -> CityInfo<'a> {
+        CityInfo {
+            cityid,
+            name: self.lookup(cityid),
+            zipcodes: locations.iter().map(|(location, _)| location.zipcode.as_str()).collect(),
+            qkm: locations.iter().map(|(location, _)| location.qkm).sum(),
+            population: locations.iter().map(|(location, _)| i64::from(location.population)).sum(),
+            geometry: dissolve(locations.iter().map(|(_, multipoly)| multipoly)),
+        }
+    }
+}
+
+/// Identifies a coordinate by the bits of its x and y value.
+type CoordKey = (u64, u64);
+
+fn coord_key(coord: (f64, f64)) -> CoordKey {
+    (coord.0.to_bits(), coord.1.to_bits())
+}
+
+/// A directed edge between two coordinates.
+type Edge = ((f64, f64), (f64, f64));
+
+/// Appends the directed edges of a ring to edges, oriented
+/// counterclockwise if exterior is set, clockwise otherwise.
+fn push_ring_edges(ring: &LineString<f64>, exterior: bool, edges: &mut Vec<Edge>) {
+    let mut points = ring.0.iter().map(|coord| (coord.x, coord.y)).collect::<Vec<_>>();
+    if points.first() != points.last() {
+        points.push(points[0]);
+    }
+    let ccw = Polygon::new(LineString::from(points.clone()), vec![]).signed_area() > 0.0;
+    if ccw != exterior {
+        points.reverse();
+    }
+    edges.extend(points
+        .windows(2)
+        .filter(|edge| edge[0] != edge[1])
+        .map(|edge| (edge[0], edge[1])));
+}
+
+/// Merges the polygons of several MultiPolygons into one MultiPolygon.
+/// Edges shared by two polygons (in opposite direction, after orienting
+/// all exteriors counterclockwise) cancel each other out and the
+/// remaining edges are stitched together into new rings.
+/// Rings enclosing an area become exteriors, all other rings
+/// become holes of the smallest exterior containing them.
+///
+/// Only edges with exactly matching coordinates are merged, so polygons
+/// which touch without sharing vertices are kept as separate rings.
+/// The polygons must not overlap.
+pub fn dissolve<'a>(multipolys: impl Iterator<Item = &'a MultiPolygon<f64>>) -> MultiPolygon<f64> {
+    let mut edges = vec![];
+    for poly in multipolys.flat_map(|multipoly| multipoly.0.iter()) {
+        push_ring_edges(poly.exterior(), true, &mut edges);
+        for interior in poly.interiors() {
+            push_ring_edges(interior, false, &mut edges);
+        }
+    }
+
+    // cancel out edges which are used in both directions
+    let mut removed = vec![false; edges.len()];
+    let mut open_edges: HashMap<(CoordKey, CoordKey), Vec<usize>> = HashMap::new();
+    for (index, &(from, to)) in edges.iter().enumerate() {
+        let reverse = (coord_key(to), coord_key(from));
+        match open_edges.get_mut(&reverse).and_then(|indices| indices.pop()) {
+            Some(other) => {
+                removed[index] = true;
+                removed[other] = true;
+            },
+            None => open_edges.entry((coord_key(from), coord_key(to))).or_default().push(index),
+        }
+    }
+
+    // stitch the remaining edges together, starting rings in the original edge order
+    let mut outgoing: HashMap<CoordKey, Vec<usize>> = HashMap::new();
+    for (index, &(from, _)) in edges.iter().enumerate().rev() {
+        if !removed[index] {
+            outgoing.entry(coord_key(from)).or_default().push(index);
+        }
+    }
+    let mut exteriors = vec![];
+    let mut holes = vec![];
+    for start in 0..edges.len() {
+        if removed[start] {
+            continue;
+        }
+        let mut ring = vec![edges[start].0];
+        let mut current = start;
+        loop {
+            removed[current] = true;
+            let (_, to) = edges[current];
+            ring.push(to);
+            if coord_key(to) == coord_key(ring[0]) {
+                break;
+            }
+            let next = outgoing
+                .get_mut(&coord_key(to))
+                .and_then(|indices| {
+                    while let Some(index) = indices.pop() {
+                        if !removed[index] {
+                            return Some(index);
+                        }
+                    }
+                    None
+                });
+            match next {
+                Some(next) => current = next,
+                // can only happen for rings which were not closed
+                None => break,
+            }
+        }
+        let ring = Polygon::new(LineString::from(ring), vec![]);
+        let area = ring.signed_area();
+        if area > 0.0 {
+            exteriors.push((ring, area));
+        } else if area < 0.0 {
+            holes.push(ring);
+        }
+    }
+
+    let mut interiors = vec![vec![]; exteriors.len()];
+    for hole in holes {
+        let containing = exteriors
+            .iter()
+            .enumerate()
+            .filter(|(_, (exterior, _))| hole.exterior().0.iter().all(|coord| {
+                let point = Point::from(*coord);
+                exterior.contains(&point) || exterior.exterior().contains(&point)
+            }))
+            .min_by(|(_, (_, a)), (_, (_, b))| a.partial_cmp(b).unwrap())
+            .map(|(index, _)| index);
+        if let Some(index) = containing {
+            interiors[index].push(hole.exterior().clone());
+        }
+    }
+    exteriors
+        .into_iter()
+        .zip(interiors)
+        .map(|((exterior, _), interiors)| Polygon::new(exterior.exterior().clone(), interiors))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::dissolve;
+    use crate::AnalysisLocations;
+    use crate::gen::challenger::{Location,Point,Polygon};
+    use crate::tests::square_locations;
+    use geo::{MultiPolygon,polygon,prelude::Area};
+
+    fn square(x: f64, y: f64) -> MultiPolygon<f64> {
+        MultiPolygon(vec![polygon![
+            (x: x, y: y),
+            (x: x + 1.0, y: y),
+            (x: x + 1.0, y: y + 1.0),
+            (x: x, y: y + 1.0),
+            (x: x, y: y),
+        ]])
+    }
+
+    #[test]
+    fn dissolve_merges_adjacent_squares() {
+        let squares = vec![square(0.0, 0.0), square(1.0, 0.0)];
+        let merged = dissolve(squares.iter());
+        assert_eq!(merged.0.len(), 1);
+        assert_eq!(merged.0[0].interiors().len(), 0);
+        assert_eq!(merged.unsigned_area(), 2.0);
+        // the shared edge is removed, only the outer corners and the edge midpoints remain
+        assert_eq!(merged.0[0].exterior().0.len(), 7);
+    }
+
+    #[test]
+    fn dissolve_keeps_holes() {
+        let squares = (0..9)
+            .filter(|i| *i != 4)
+            .map(|i| square(f64::from(i % 3), f64::from(i / 3)))
+            .collect::<Vec<_>>();
+        let merged = dissolve(squares.iter());
+        assert_eq!(merged.0.len(), 1);
+        assert_eq!(merged.0[0].interiors().len(), 1);
+        assert_eq!(merged.unsigned_area(), 8.0);
+        // separate polygons stay separate
+        let separate = vec![square(0.0, 0.0), square(5.0, 5.0)];
+        assert_eq!(dissolve(separate.iter()).0.len(), 2);
+    }
+
+    #[test]
+    fn cities_are_aggregated() {
+        let mut locations = square_locations();
+        let square = |from: f64, to: f64| vec![Polygon {
+            points: vec![
+                Point { longitude: from, latitude: 48.0 },
+                Point { longitude: to, latitude: 48.0 },
+                Point { longitude: to, latitude: 48.01 },
+                Point { longitude: from, latitude: 48.01 },
+            ],
+            interiors: vec![],
+        }];
+        locations.locations.push(Location {
+            zipcode: "12346".to_owned(),
+            city: "Square City".to_owned(),
+            qkm: 1.5,
+            population: 100,
+            polygons: square(8.01, 8.02),
+        });
+        locations.locations.push(Location {
+            zipcode: "54321".to_owned(),
+            city: "Other City".to_owned(),
+            qkm: 2.0,
+            population: 50,
+            polygons: square(9.0, 9.01),
+        });
+        let locations = AnalysisLocations::new(locations);
+        let cities = locations.cities();
+        assert_eq!(cities.len(), 2);
+        assert_eq!(cities[0].name, "Square City");
+        assert_eq!(cities[0].zipcodes, vec!["12345", "12346"]);
+        assert_eq!(cities[0].qkm, 1.5);
+        assert_eq!(cities[0].population, 100);
+        assert_eq!(cities[0].geometry.0.len(), 1);
+        assert_eq!(cities[1].zipcodes, vec!["54321"]);
+        assert_eq!(locations.city_totals(), vec![(100, 1.5), (50, 2.0)]);
+        assert_eq!(locations.city(1), cities.into_iter().nth(1));
+        assert!(locations.city(2).is_none());
+    }
+}
@@ -0,0 +1,241 @@
+use crate::AnalysisLocations;
+use crate::aqi::AQIStandard;
+use crate::city::CityInfo;
+use crate::gen::challenger::{Locations,Polygon,ResultQ1};
+use crate::pipeline::RankedCityDetails;
+
+use geo::MultiPolygon;
+use serde_json::{Value,json};
+
+/// Converts the polygons of a Location (including their holes)
+/// into GeoJSON MultiPolygon coordinates.
+/// Rings are closed (as required by GeoJSON) if their last point
+/// differs from the first one.
+fn polygons_coordinates(polygons: &[Polygon]) -> Value {
+    polygons
+        .iter()
+        .map(|poly| {
+            std::iter::once(&poly.points)
+                .chain(poly.interiors.iter().map(|ring| &ring.points))
+                .map(|points| {
+                    let closing = points.first().filter(|first| points.last() != Some(first));
+                    points
+                        .iter()
+                        .chain(closing)
+                        .map(|point| json!([point.longitude, point.latitude]))
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+/// Converts a geo MultiPolygon (x being the longitude, y the latitude)
+/// into GeoJSON MultiPolygon coordinates.
+fn multipolygon_coordinates(multipoly: &MultiPolygon<f64>) -> Value {
+    multipoly.0
+        .iter()
+        .map(|poly| {
+            std::iter::once(poly.exterior())
+                .chain(poly.interiors())
+                .map(|ring| ring.0
+                    .iter()
+                    .map(|coord| json!([coord.x, coord.y]))
+                    .collect::<Vec<_>>())
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+/// Converts Locations into a GeoJSON FeatureCollection with
+/// one MultiPolygon feature per zipcode.
+/// The zipcode, city, qkm and population are stored as properties.
+pub fn locations_to_geojson(locations: &Locations) -> Value {
+    let features = locations.locations
+        .iter()
+        .map(|location| json!({
+            "type": "Feature",
+            "properties": {
+                "zipcode": location.zipcode,
+                "city": location.city,
+                "qkm": location.qkm,
+                "population": location.population,
+            },
+            "geometry": {
+                "type": "MultiPolygon",
+                "coordinates": polygons_coordinates(&location.polygons),
+            },
+        }))
+        .collect::<Vec<_>>();
+    json!({
+        "type": "FeatureCollection",
+        "features": features,
+    })
+}
+
+/// Converts the result of a window into a GeoJSON FeatureCollection
+/// with one MultiPolygon feature per ranked city,
+/// consisting of the dissolved polygons of all zipcodes of the city.
+/// The position and improvement are stored as properties, along with the current AQIs
+/// (in the same DEBS format as in the result, but null if outside the scale or missing), the number of
+/// sensors and measurements and the extremes of the last day (and when they occurred)
+/// from details (in the same order as the result).
+/// The category (of the index of standard) and dominant pollutant of the current AQI
+/// (see [AQIReport](crate::aqi::AQIReport)) are stored as well, or null if the AQI is outside the scale.
+/// The colour and health message, and whether the air is good as defined by query 2
+/// (see [AQIReport::is_good_air](crate::aqi::AQIReport::is_good_air)), are only available for the EPA categories.
+/// The AQIs of other pollutants from details are stored by the name of the pollutant
+/// (only for pollutants with measurements).
+/// The geometries are taken from cities, which are dissolved only once
+/// (see [AnalysisLocations::cities]) and indexed by the id of the city.
+pub fn result_to_geojson(result: &ResultQ1, details: &[RankedCityDetails], locations: &AnalysisLocations, cities: &[CityInfo<'_>], standard: AQIStandard) -> Value {
+    let empty = MultiPolygon(vec![]);
+    let features = result.topkimproved
+        .iter()
+        .enumerate()
+        .map(|(idx, city)| {
+            let details = details.get(idx).copied().unwrap_or_default();
+            let report = details.report();
+            let epa_category = report
+                .filter(|_| standard.is_epa())
+                .map(|report| report.category());
+            let geometry = locations
+                .inverse_lookup(&city.city)
+                .and_then(|cityid| cities.get(cityid as usize))
+                .map(|info| &info.geometry)
+                .unwrap_or(&empty);
+            let pollutant_aqis = details.pollutants
+                .iter()
+                .map(|(pollutant, aqi)| (pollutant.name().to_owned(), json!(aqi.get_asdebs())))
+                .collect::<serde_json::Map<_, _>>();
+            json!({
+                "type": "Feature",
+                "properties": {
+                    "batch_seq_id": result.batch_seq_id,
+                    "position": city.position,
+                    "city": city.city,
+                    "average_aqi_improvement": city.average_aqi_improvement,
+                    "current_aqip1": details.current_aqip1.asdebs(),
+                    "current_aqip2": details.current_aqip2.asdebs(),
+                    "category": report.map(|report| standard.index().category(report.aqi())),
+                    "color": epa_category.map(|category| category.color()),
+                    "health_message": epa_category.map(|category| category.health_message()),
+                    "good_air": report.filter(|_| standard.is_epa()).map(|report| report.is_good_air()),
+                    "dominant_pollutant": report.map(|report| report.dominant_pollutant().name()),
+                    "sensors": details.sensors,
+                    "measurements": details.measurements,
+                    "peak_aqi": details.peak.map(|peak| peak.aqi.get_asdebs()),
+                    "peak_timestamp": details.peak.map(|peak| peak.timestamp_seconds),
+                    "low_aqi": details.low.map(|low| low.aqi.get_asdebs()),
+                    "low_timestamp": details.low.map(|low| low.timestamp_seconds),
+                    "pollutant_aqis": pollutant_aqis,
+                },
+                "geometry": {
+                    "type": "MultiPolygon",
+                    "coordinates": multipolygon_coordinates(geometry),
+                },
+            })
+        })
+        .collect::<Vec<_>>();
+    json!({
+        "type": "FeatureCollection",
+        "features": features,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{locations_to_geojson,result_to_geojson};
+    use crate::AnalysisLocations;
+    use crate::gen::challenger::{ResultQ1,Ring,TopKCities};
+    use crate::aggregate::AQIPeak;
+    use crate::aqi::{AQIStandard,AQIValue,Pollutant,PollutantAQIs};
+    use crate::pipeline::{CurrentAQI,RankedCityDetails};
+    use crate::import::{PropertyMapping,locations_from_geojson};
+    use crate::tests::square_locations;
+
+    #[test]
+    fn test_locations_geojson_roundtrip() {
+        let geojson = locations_to_geojson(&square_locations());
+        assert_eq!(geojson["features"][0]["geometry"]["coordinates"][0][0][1], serde_json::json!([8.01, 48.0]));
+        let mapping = PropertyMapping {
+            zipcode: "zipcode".to_owned(),
+            city: "city".to_owned(),
+            population: Some("population".to_owned()),
+            ..PropertyMapping::default()
+        };
+        let imported = locations_from_geojson(&geojson.to_string(), &mapping).expect("Export should be importable");
+        assert_eq!(imported, square_locations());
+    }
+
+    #[test]
+    fn test_rings_are_closed() {
+        let mut locations = square_locations();
+        let polygon = &mut locations.locations[0].polygons[0];
+        polygon.points.pop();
+        polygon.interiors.push(Ring { points: polygon.points[..3].to_vec() });
+        let geojson = locations_to_geojson(&locations);
+        let rings = geojson["features"][0]["geometry"]["coordinates"][0].as_array().expect("Polygon should be an array");
+        assert_eq!(rings.len(), 2);
+        assert_eq!(rings[0].as_array().map(Vec::len), Some(5));
+        assert_eq!(rings[1].as_array().map(Vec::len), Some(4));
+        for ring in rings {
+            let ring = ring.as_array().expect("Ring should be an array");
+            assert_eq!(ring.first(), ring.last());
+        }
+    }
+
+    #[test]
+    fn test_result_geojson() {
+        let locations = AnalysisLocations::new(square_locations());
+        let result = ResultQ1 {
+            benchmark_id: 0,
+            batch_seq_id: 17,
+            topkimproved: vec![TopKCities {
+                position: 0,
+                city: "Square City".to_owned(),
+                average_aqi_improvement: 1234,
+                current_aqip1: 20000,
+                current_aqip2: 30000,
+            }],
+        };
+        let peak = AQIPeak { aqi: AQIValue::from_pm25(50.0).unwrap(), timestamp_seconds: 1600000000 };
+        let mut pollutants = PollutantAQIs::default();
+        pollutants.set(Pollutant::O3, AQIValue::from_asdebs(67000));
+        let low = AQIPeak { aqi: AQIValue::from_pm25(5.0).unwrap(), timestamp_seconds: 1599990000 };
+        let details = [RankedCityDetails {
+            current_aqip1: CurrentAQI::Value(20000),
+            current_aqip2: CurrentAQI::Value(30000),
+            sensors: 3,
+            measurements: 42,
+            peak: Some(peak),
+            low: Some(low),
+            pollutants,
+        }];
+        let cities = locations.cities();
+        let geojson = result_to_geojson(&result, &details, &locations, &cities, AQIStandard::UsEpa);
+        let feature = &geojson["features"][0];
+        assert_eq!(feature["properties"]["batch_seq_id"], 17);
+        assert_eq!(feature["properties"]["average_aqi_improvement"], 1234);
+        assert_eq!(feature["properties"]["sensors"], 3);
+        assert_eq!(feature["properties"]["measurements"], 42);
+        assert_eq!(feature["properties"]["peak_aqi"], peak.aqi.get_asdebs());
+        assert_eq!(feature["properties"]["peak_timestamp"], 1600000000);
+        assert_eq!(feature["properties"]["low_aqi"], low.aqi.get_asdebs());
+        assert_eq!(feature["properties"]["low_timestamp"], 1599990000);
+        assert_eq!(feature["properties"]["category"], "Good");
+        assert_eq!(feature["properties"]["color"], "#00E400");
+        assert_eq!(feature["properties"]["good_air"], true);
+        assert_eq!(feature["properties"]["dominant_pollutant"], "PM2.5");
+        assert_eq!(feature["properties"]["pollutant_aqis"], serde_json::json!({"O3": 67000}));
+        assert_eq!(feature["geometry"]["coordinates"][0][0].as_array().map(Vec::len), Some(5));
+        assert_eq!(feature["geometry"]["coordinates"][0][0][0], serde_json::json!([8.0, 48.0]));
+
+        // other indices only provide the name of the category
+        let geojson = result_to_geojson(&result, &details, &locations, &cities, AQIStandard::Caqi);
+        let feature = &geojson["features"][0];
+        assert_eq!(feature["properties"]["category"], "Low");
+        assert_eq!(feature["properties"]["color"], serde_json::Value::Null);
+        assert_eq!(feature["properties"]["good_air"], serde_json::Value::Null);
+    }
+}
@@ -12,6 +12,14 @@ pub struct Measurement {
     ///Particles < 2.5µm (ultrafine particles)
     #[prost(float, tag = "5")]
     pub p2: f32,
+    ///Extended measurement format, not sent by the challenge server
+    ///
+    ///Relative humidity in %
+    #[prost(message, optional, tag = "6")]
+    pub humidity: ::core::option::Option<f32>,
+    ///Temperature in °C
+    #[prost(message, optional, tag = "7")]
+    pub temperature: ::core::option::Option<f32>,
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct Batch {
@@ -114,9 +122,17 @@ pub struct Point {
     pub latitude: f64,
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Ring {
+    #[prost(message, repeated, tag = "1")]
+    pub points: ::prost::alloc::vec::Vec<Point>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
 pub struct Polygon {
     #[prost(message, repeated, tag = "1")]
     pub points: ::prost::alloc::vec::Vec<Point>,
+    ///Holes, not sent by the challenge server
+    #[prost(message, repeated, tag = "2")]
+    pub interiors: ::prost::alloc::vec::Vec<Ring>,
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct Location {
@@ -0,0 +1,70 @@
+/// A circle known to be inside/outside a location
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct CacheItem {
+    ///longitude of the center
+    #[prost(float, tag = "1")]
+    pub x: f32,
+    ///latitude of the center
+    #[prost(float, tag = "2")]
+    pub y: f32,
+    ///squared radius
+    #[prost(float, tag = "3")]
+    pub rsquare: f32,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Cache {
+    #[prost(uint32, tag = "1")]
+    pub capacity: u32,
+    #[prost(message, repeated, tag = "2")]
+    pub items: ::prost::alloc::vec::Vec<CacheItem>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct IndexedRing {
+    ///alternating longitude and latitude of each point
+    #[prost(double, repeated, tag = "1")]
+    pub coordinates: ::prost::alloc::vec::Vec<f64>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct IndexedPolygon {
+    ///alternating longitude and latitude of each point of the exterior ring
+    #[prost(double, repeated, tag = "1")]
+    pub coordinates: ::prost::alloc::vec::Vec<f64>,
+    ///holes
+    #[prost(message, repeated, tag = "2")]
+    pub interiors: ::prost::alloc::vec::Vec<IndexedRing>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct IndexedLocation {
+    #[prost(string, tag = "1")]
+    pub zipcode: ::prost::alloc::string::String,
+    #[prost(uint32, tag = "2")]
+    pub cityid: u32,
+    #[prost(message, repeated, tag = "3")]
+    pub polygons: ::prost::alloc::vec::Vec<IndexedPolygon>,
+    #[prost(message, optional, tag = "4")]
+    pub insidecache: ::core::option::Option<Cache>,
+    #[prost(message, optional, tag = "5")]
+    pub outsidecache: ::core::option::Option<Cache>,
+    #[prost(double, tag = "6")]
+    pub qkm: f64,
+    #[prost(int32, tag = "7")]
+    pub population: i32,
+    ///start and end point (x1, y1, x2, y2) of each line of all polygons (used by both caches)
+    #[prost(float, repeated, tag = "9")]
+    pub lines: ::prost::alloc::vec::Vec<f32>,
+}
+/// A fully built AnalysisLocations
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct LocationsIndex {
+    ///format version, changes whenever the way the index is built changes
+    #[prost(uint32, tag = "1")]
+    pub version: u32,
+    ///hash of the locations dump the index was built from
+    #[prost(uint64, tag = "2")]
+    pub source_hash: u64,
+    ///city names, indexed by cityid
+    #[prost(string, repeated, tag = "3")]
+    pub known_cities: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    #[prost(message, repeated, tag = "4")]
+    pub locations: ::prost::alloc::vec::Vec<IndexedLocation>,
+}
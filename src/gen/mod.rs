@@ -1,2 +1,4 @@
 #[allow(missing_docs)]
 pub mod challenger;
+#[allow(missing_docs)]
+pub mod index;
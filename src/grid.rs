@@ -0,0 +1,271 @@
+use crate::{AnalysisLocation,AnalysisLocations};
+
+use geo::{point,prelude::Contains};
+use rstar::{AABB,Envelope,RTree};
+use rstar::primitives::Line;
+
+/// Describes which locations a single cell of a [GridLocations] overlaps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum GridCell {
+    /// The cell is not part of any location.
+    Outside,
+    /// The cell is fully inside the location with the given
+    /// index into AnalysisLocations::locations.
+    Inside(u32),
+    /// The cell contains the border of at least one location
+    /// (or is overlapped by multiple locations),
+    /// so the exact polygons need to be checked.
+    Boundary,
+}
+
+/// An alternative index to [AnalysisLocations::localize], which precomputes
+/// a grid with a fixed resolution over the bounds of all locations.
+/// Each cell is marked as either fully inside a single zipcode,
+/// fully outside of all zipcodes, or containing a boundary.
+/// Only points in boundary cells need to be localized using the
+/// (slower) exact tests of the wrapped AnalysisLocations.
+/// As sensors are stationary and most of them are far from any border,
+/// most lookups only need to calculate an index into the grid.
+#[derive(Debug)]
+pub struct GridLocations {
+    /// The wrapped locations, used for the exact tests
+    /// and to look up names.
+    locations: AnalysisLocations,
+    /// The cells of the grid, stored row by row
+    /// starting at the lower left corner (min. longitude/latitude).
+    cells: Vec<GridCell>,
+    /// The longitude of the lower left corner of the grid.
+    minx: f64,
+    /// The latitude of the lower left corner of the grid.
+    miny: f64,
+    /// The width and height of a single cell in degrees.
+    cellsize: f64,
+    /// The number of cells per row.
+    width: usize,
+    /// The number of rows.
+    height: usize,
+}
+
+/// Checks if the line segment intersects the rectangle
+/// using the Liang-Barsky line clipping algorithm.
+fn line_intersects(line: &Line<[f64; 2]>, rect: &AABB<[f64; 2]>) -> bool {
+    let [x0, y0] = line.from;
+    let [dx, dy] = [line.to[0] - x0, line.to[1] - y0];
+    let [minx, miny] = rect.lower();
+    let [maxx, maxy] = rect.upper();
+    // the part of the line (0 = from, 1 = to) inside the rectangle
+    let (mut t0, mut t1) = (0.0f64, 1.0f64);
+    for &(p, q) in &[(-dx, x0 - minx), (dx, maxx - x0), (-dy, y0 - miny), (dy, maxy - y0)] {
+        if p == 0.0 {
+            if q < 0.0 {
+                // parallel to and outside of this border
+                return false;
+            }
+        } else {
+            let t = q / p;
+            if p < 0.0 {
+                t0 = t0.max(t);
+            } else {
+                t1 = t1.min(t);
+            }
+        }
+    }
+    t0 <= t1
+}
+
+impl GridLocations {
+    /// Creates a new grid with cells of cellsize by cellsize degrees
+    /// covering all the given locations.
+    /// Smaller cells result in fewer exact tests, but need more memory
+    /// and take longer to build.
+    pub fn new(locations: AnalysisLocations, cellsize: f64) -> Self {
+        use rayon::prelude::*;
+        assert!(cellsize > 0.0, "Cell size needs to be positive");
+        let envelope = locations.bboxtree.root().envelope();
+        let [minx, miny] = envelope.lower();
+        let [maxx, maxy] = envelope.upper();
+        let width = ((maxx - minx) / cellsize).floor() as usize + 1;
+        let height = ((maxy - miny) / cellsize).floor() as usize + 1;
+
+        // All edges of all polygons, including their holes.
+        // Any cell not intersecting one of these does not contain a border.
+        let edges = locations.locations
+            .iter()
+            .flat_map(|(_, multipoly)| multipoly.0.iter())
+            .flat_map(|poly| std::iter::once(poly.exterior()).chain(poly.interiors()))
+            .flat_map(|ring| ring.lines())
+            .map(|line| Line::new([line.start.x, line.start.y], [line.end.x, line.end.y]))
+            .collect::<Vec<_>>();
+        let edges = RTree::bulk_load(edges);
+
+        let cells = (0..width * height)
+            .into_par_iter()
+            .map(|idx| {
+                let (col, row) = (idx % width, idx / width);
+                let cell_minx = minx + col as f64 * cellsize;
+                let cell_miny = miny + row as f64 * cellsize;
+                let cell = AABB::from_corners(
+                    [cell_minx, cell_miny],
+                    [cell_minx + cellsize, cell_miny + cellsize]
+                );
+                if edges.locate_in_envelope_intersecting(&cell).any(|edge| line_intersects(edge, &cell)) {
+                    return GridCell::Boundary;
+                }
+                // No border crosses this cell, so all points inside it are
+                // contained in the same locations as its center.
+                let center = cell.center();
+                let p = point!(x: center[0], y: center[1]);
+                let mut containing = locations.bboxtree
+                    .locate_all_at_point(&center)
+                    .filter(|treeobject| locations.locations[treeobject.idx as usize].1.contains(&p));
+                match (containing.next(), containing.next()) {
+                    (None, _) => GridCell::Outside,
+                    (Some(treeobject), None) => GridCell::Inside(treeobject.idx),
+                    (Some(_), Some(_)) => GridCell::Boundary,
+                }
+            })
+            .collect();
+
+        Self {
+            locations,
+            cells,
+            minx,
+            miny,
+            cellsize,
+            width,
+            height,
+        }
+    }
+
+    /// Returns the cell containing the given coordinates,
+    /// or None if they are outside of the grid.
+    fn cell(&self, latitude: f32, longitude: f32) -> Option<GridCell> {
+        let col = (f64::from(longitude) - self.minx) / self.cellsize;
+        let row = (f64::from(latitude) - self.miny) / self.cellsize;
+        if col < 0.0 || row < 0.0 || col >= self.width as f64 || row >= self.height as f64 {
+            return None;
+        }
+        Some(self.cells[row as usize * self.width + col as usize])
+    }
+
+    /// Tries to find the [AnalysisLocation] which contains the given coordinates.
+    /// Returns the same location as [AnalysisLocations::localize_or_nearest]
+    /// on the wrapped locations.
+    pub fn localize(&self, latitude: f32, longitude: f32) -> Option<&AnalysisLocation> {
+        match self.cell(latitude, longitude) {
+            Some(GridCell::Inside(idx)) => {
+                debug_assert!({
+                    let (_, bounding_poly) = &self.locations.locations[idx as usize];
+                    let p = point!(x: f64::from(longitude), y: f64::from(latitude));
+                    bounding_poly.contains(&p)
+                });
+                Some(&self.locations.locations[idx as usize].0)
+            },
+            Some(GridCell::Outside) if self.locations.fallbackdistance.is_none() => None,
+            _ => self.locations.localize_or_nearest(latitude, longitude),
+        }
+    }
+
+    /// Returns the share of cells which are boundary cells,
+    /// i.e. cells for which an exact test is necessary.
+    pub fn boundary_ratio(&self) -> f64 {
+        let boundary = self.cells
+            .iter()
+            .filter(|cell| **cell == GridCell::Boundary)
+            .count();
+        boundary as f64 / self.cells.len() as f64
+    }
+
+    /// Returns the wrapped [AnalysisLocations],
+    /// e.g. to look up city names.
+    pub fn locations(&self) -> &AnalysisLocations {
+        &self.locations
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::GridLocations;
+    use crate::AnalysisLocations;
+    use crate::gen::challenger::{Location,Locations,Point,Polygon,Ring};
+
+    /// Generates Locations containing two triangles,
+    /// which together form a square of 0.01 by 0.01 degrees.
+    fn triangle_locations() -> Locations {
+        let triangle = |zipcode: &str, city: &str, corners: [(f64, f64); 3]| {
+            let points = corners
+                .iter()
+                .chain(corners.iter().take(1))
+                .map(|&(longitude, latitude)| Point { longitude, latitude })
+                .collect();
+            Location {
+                zipcode: zipcode.to_owned(),
+                city: city.to_owned(),
+                qkm: 0.0,
+                population: 0,
+                polygons: vec![Polygon { points, interiors: vec![] }],
+            }
+        };
+        Locations {
+            locations: vec![
+                triangle("11111", "Lower", [(8.0, 48.0), (8.01, 48.0), (8.01, 48.01)]),
+                triangle("22222", "Upper", [(8.0, 48.0), (8.01, 48.01), (8.0, 48.01)]),
+            ],
+        }
+    }
+
+    /// Generates Locations containing a square of 0.01 by 0.01 degrees with a hole,
+    /// whose borders do not align with the cells of the grid,
+    /// and a smaller square inside the hole.
+    fn hole_locations() -> Locations {
+        let square = |min: f64, max: f64| [(min, min), (max, min), (max, max), (min, max), (min, min)]
+            .iter()
+            .map(|&(x, y)| Point { longitude: 8.0 + x, latitude: 48.0 + y })
+            .collect::<Vec<_>>();
+        let location = |zipcode: &str, city: &str, polygon: Polygon| Location {
+            zipcode: zipcode.to_owned(),
+            city: city.to_owned(),
+            qkm: 0.0,
+            population: 0,
+            polygons: vec![polygon],
+        };
+        Locations {
+            locations: vec![
+                location("11111", "Outer", Polygon { points: square(0.0, 0.01), interiors: vec![Ring { points: square(0.0023, 0.0077) }] }),
+                location("22222", "Island", Polygon { points: square(0.0041, 0.0059), interiors: vec![] }),
+            ],
+        }
+    }
+
+    /// Checks that localizing a lattice of points around the square of 0.01 by 0.01 degrees
+    /// at 8.0/48.0 using the grid yields the same city as the exact localization.
+    fn assert_grid_matches(locations: fn() -> Locations) {
+        let exact = AnalysisLocations::new(locations());
+        let grid = GridLocations::new(AnalysisLocations::new(locations()), 0.001);
+        assert!(grid.boundary_ratio() > 0.0 && grid.boundary_ratio() < 1.0);
+        for i in 0..=60 {
+            for j in 0..=60 {
+                let latitude = 47.999 + i as f32 * 0.0002;
+                let longitude = 7.999 + j as f32 * 0.0002;
+                let expected = exact
+                    .localize(latitude, longitude)
+                    .next()
+                    .map(|location| exact.lookup(location.cityid));
+                let actual = grid
+                    .localize(latitude, longitude)
+                    .map(|location| grid.locations().lookup(location.cityid));
+                assert_eq!(actual, expected, "Mismatch at latitude {}, longitude {}", latitude, longitude);
+            }
+        }
+    }
+
+    #[test]
+    fn grid_matches_exact_localization() {
+        assert_grid_matches(triangle_locations);
+    }
+
+    #[test]
+    fn grid_respects_holes() {
+        assert_grid_matches(hole_locations);
+    }
+}
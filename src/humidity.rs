@@ -0,0 +1,135 @@
+use std::str::FromStr;
+
+/// Corrects the particle concentrations measured by low-cost optical sensors
+/// (like the SDS011 used by most sensors in the dataset) for the hygroscopic growth
+/// of particles at high humidity, which inflates the measured concentrations.
+///
+/// Uses the growth factor of the κ-Köhler theory (Crilley et al., 2018):
+/// the measured concentration is divided by `1 + κ/ρ · aw/(1 - aw)`,
+/// aw being the relative humidity as a fraction and ρ the density of
+/// the dry particles relative to water.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HumidityCorrection {
+    /// The hygroscopicity κ of particles < 10µm
+    pub kappa_p1: f32,
+    /// The hygroscopicity κ of particles < 2.5µm
+    pub kappa_p2: f32,
+    /// The density of the dry particles relative to water
+    pub density: f32,
+    /// The largest relative humidity (in %) used for the correction,
+    /// higher humidities are clamped as the growth factor diverges at 100%
+    pub max_humidity: f32,
+    /// Measurements below this temperature (in °C) are not corrected,
+    /// as the model assumes the water taken up by the particles to be liquid
+    pub min_temperature: f32,
+}
+
+impl Default for HumidityCorrection {
+    /// Uses the hygroscopicity and density fitted by Crilley et al. for
+    /// urban particulate matter, and clamps the humidity to 95%
+    /// (the sensors are unreliable in fog).
+    fn default() -> Self {
+        Self {
+            kappa_p1: 0.4,
+            kappa_p2: 0.4,
+            density: 1.65,
+            max_humidity: 95.0,
+            min_temperature: 0.0,
+        }
+    }
+}
+
+impl HumidityCorrection {
+    /// Uses the same hygroscopicity κ for both particle sizes.
+    pub fn with_kappa(mut self, kappa: f32) -> Self {
+        self.kappa_p1 = kappa;
+        self.kappa_p2 = kappa;
+        self
+    }
+
+    /// Returns the factor by which particles with hygroscopicity kappa
+    /// grow at the given relative humidity (in %).
+    pub fn growth_factor(self, kappa: f32, humidity: f32) -> f32 {
+        let aw = humidity.max(0.0).min(self.max_humidity) / 100.0;
+        1.0 + kappa / self.density * aw / (1.0 - aw)
+    }
+
+    /// Returns the corrected p1 and p2 values, or why the measurement can not be corrected.
+    /// The temperature is optional, as not all sensors provide it.
+    pub fn correct(self, p1: f32, p2: f32, humidity: Option<f32>, temperature: Option<f32>) -> Result<(f32, f32), Uncorrected> {
+        let humidity = humidity
+            .filter(|humidity| humidity.is_finite())
+            .ok_or(Uncorrected::NoHumidity)?;
+        if temperature.is_some_and(|temperature| temperature < self.min_temperature) {
+            return Err(Uncorrected::TooCold);
+        }
+        Ok((p1 / self.growth_factor(self.kappa_p1, humidity), p2 / self.growth_factor(self.kappa_p2, humidity)))
+    }
+}
+
+/// The reason why a measurement was not corrected, see [HumidityCorrection::correct].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Uncorrected {
+    /// The measurement has no (valid) humidity
+    NoHumidity,
+    /// The temperature is below [HumidityCorrection::min_temperature]
+    TooCold,
+}
+
+impl Uncorrected {
+    /// All reasons, in the order of their discriminants.
+    pub const ALL: [Uncorrected; 2] = [Uncorrected::NoHumidity, Uncorrected::TooCold];
+
+    /// Returns a short name of the reason.
+    pub fn name(self) -> &'static str {
+        match self {
+            Uncorrected::NoHumidity => "no humidity",
+            Uncorrected::TooCold => "too cold",
+        }
+    }
+}
+
+impl FromStr for HumidityCorrection {
+    type Err = std::num::ParseFloatError;
+
+    /// Parses the hygroscopicity κ used for both particle sizes,
+    /// an empty string uses the default correction.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "" => Ok(Self::default()),
+            kappa => Ok(Self::default().with_kappa(kappa.parse()?)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{HumidityCorrection,Uncorrected};
+
+    #[test]
+    fn humid_measurements_are_corrected() {
+        let correction = HumidityCorrection::default();
+        // dry air does not change the concentrations
+        assert_eq!(correction.correct(20.0, 10.0, Some(0.0), None), Ok((20.0, 10.0)));
+        // at 50% the particles take up water worth κ/ρ of their volume
+        let factor = 1.0 + 0.4 / 1.65;
+        assert_eq!(correction.growth_factor(0.4, 50.0), factor);
+        assert_eq!(correction.correct(20.0, 10.0, Some(50.0), Some(20.0)), Ok((20.0 / factor, 10.0 / factor)));
+        // the humidity is clamped
+        assert_eq!(correction.growth_factor(0.4, 100.0), correction.growth_factor(0.4, 95.0));
+        assert!(correction.growth_factor(0.4, 100.0).is_finite());
+        // a larger κ corrects more
+        let (p1, p2) = correction.with_kappa(0.6).correct(20.0, 10.0, Some(80.0), None).unwrap();
+        let (default_p1, _) = correction.correct(20.0, 10.0, Some(80.0), None).unwrap();
+        assert!(p1 < default_p1);
+        assert_eq!(p1 / p2, 2.0);
+        // measurements without (valid) humidity or below freezing are not corrected
+        assert_eq!(correction.correct(20.0, 10.0, None, Some(20.0)), Err(Uncorrected::NoHumidity));
+        assert_eq!(correction.correct(20.0, 10.0, Some(f32::NAN), None), Err(Uncorrected::NoHumidity));
+        assert_eq!(correction.correct(20.0, 10.0, Some(90.0), Some(-5.0)), Err(Uncorrected::TooCold));
+
+        assert_eq!("".parse(), Ok(correction));
+        assert_eq!("0.6".parse(), Ok(correction.with_kappa(0.6)));
+        assert!("wet".parse::<HumidityCorrection>().is_err());
+    }
+}
@@ -0,0 +1,729 @@
+use crate::aqi::Pollutant;
+use crate::gen::challenger::{Location,Locations,Point,Polygon,Ring};
+
+use serde_json::Value;
+use thiserror::Error;
+
+/// An error which occurred while importing locations or measurements.
+#[derive(Error,Debug)]
+pub enum ImportError {
+    /// Returned when the GeoJSON data is not valid JSON.
+    #[error("Invalid JSON: {0}")]
+    InvalidJson(#[from] serde_json::Error),
+    /// Returned when the data does not have the expected structure,
+    /// e.g. a GeoJSON document which is not a FeatureCollection.
+    #[error("Invalid structure: {0}")]
+    InvalidStructure(&'static str),
+    /// Returned when a feature/row lacks a mapped property.
+    #[error("Feature {feature} has no usable property {property}")]
+    MissingProperty {
+        /// The index of the feature/row
+        feature: usize,
+        /// The name of the missing property
+        property: String,
+    },
+    /// Returned when the geometry of a feature/row can not be converted.
+    #[error("Feature {feature} has an invalid geometry: {reason}")]
+    InvalidGeometry {
+        /// The index of the feature/row
+        feature: usize,
+        /// Describes why the geometry is invalid
+        reason: String,
+    },
+    /// Returned when a property/field of a feature/row can not be parsed.
+    #[error("Feature {feature} has an invalid {property}: {reason}")]
+    InvalidValue {
+        /// The index of the feature/row
+        feature: usize,
+        /// The name of the invalid property/column
+        property: String,
+        /// Describes why the value is invalid
+        reason: String,
+    },
+}
+
+/// Describes which properties (GeoJSON) or columns (CSV)
+/// contain the fields of a [Location].
+#[derive(Debug, Clone)]
+pub struct PropertyMapping {
+    /// The property containing the zipcode
+    pub zipcode: String,
+    /// The property containing the city name
+    pub city: String,
+    /// The property containing the area in km², if available
+    pub qkm: Option<String>,
+    /// The property containing the population, if available
+    pub population: Option<String>,
+    /// The CSV column containing the WKT geometry (unused for GeoJSON)
+    pub geometry: String,
+    /// The number of digits of zipcodes stored as (GeoJSON) numbers,
+    /// which are padded with leading zeros (e.g. 1067 becomes 01067).
+    /// If None, zipcodes stored as numbers are rejected.
+    pub zipcode_digits: Option<usize>,
+}
+
+impl Default for PropertyMapping {
+    /// Uses the property names of the german postcode boundaries
+    /// published by suche-postleitzahl.org
+    fn default() -> Self {
+        Self {
+            zipcode: "plz".to_owned(),
+            city: "ort".to_owned(),
+            qkm: Some("qkm".to_owned()),
+            population: Some("einwohner".to_owned()),
+            geometry: "WKT".to_owned(),
+            zipcode_digits: Some(5),
+        }
+    }
+}
+
+/// Converts a list of rings (each a list of longitude/latitude pairs)
+/// into a Polygon, the first ring being the exterior and the others holes.
+fn polygon_from_rings(rings: &[Vec<(f64, f64)>]) -> Result<Polygon, String> {
+    let (exterior, interiors) = rings.split_first().ok_or("Polygon without rings")?;
+    let points = |ring: &Vec<(f64, f64)>| {
+        if ring.len() < 3 {
+            return Err(format!("Ring with {} points", ring.len()));
+        }
+        Ok(ring
+            .iter()
+            .map(|&(longitude, latitude)| Point { longitude, latitude })
+            .collect())
+    };
+    Ok(Polygon {
+        points: points(exterior)?,
+        interiors: interiors
+            .iter()
+            .map(|ring| Ok(Ring { points: points(ring)? }))
+            .collect::<Result<_, String>>()?,
+    })
+}
+
+/// Reads a GeoJSON position array ([longitude, latitude, ...]).
+fn geojson_position(value: &Value) -> Option<(f64, f64)> {
+    let position = value.as_array()?;
+    Some((position.first()?.as_f64()?, position.get(1)?.as_f64()?))
+}
+
+/// Reads the rings of a GeoJSON polygon coordinates array.
+fn geojson_rings(value: &Value) -> Option<Vec<Vec<(f64, f64)>>> {
+    value
+        .as_array()?
+        .iter()
+        .map(|ring| ring.as_array()?.iter().map(geojson_position).collect())
+        .collect()
+}
+
+/// Converts a GeoJSON Polygon or MultiPolygon geometry into Polygons.
+fn geojson_polygons(geometry: &Value) -> Result<Vec<Polygon>, String> {
+    let coordinates = geometry.get("coordinates").ok_or("Geometry without coordinates")?;
+    let polygons = match geometry.get("type").and_then(Value::as_str) {
+        Some("Polygon") => vec![geojson_rings(coordinates)],
+        Some("MultiPolygon") => coordinates
+            .as_array()
+            .ok_or("MultiPolygon coordinates are not an array")?
+            .iter()
+            .map(geojson_rings)
+            .collect(),
+        Some(other) => return Err(format!("Unsupported geometry type {}", other)),
+        None => return Err("Geometry without type".to_owned()),
+    };
+    polygons
+        .into_iter()
+        .map(|rings| polygon_from_rings(&rings.ok_or("Malformed coordinates")?))
+        .collect()
+}
+
+/// Reads a property which may be stored as either a string or a number.
+fn property_string(value: Option<&Value>) -> Option<String> {
+    match value? {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+/// Reads a numeric property which may be stored as either a number or a string.
+fn property_f64(value: Option<&Value>) -> Option<f64> {
+    match value? {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => s.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+/// Builds a Location from its already converted polygons and
+/// the properties looked up using lookup.
+fn build_location(feature: usize, polygons: Vec<Polygon>, mapping: &PropertyMapping, lookup: impl Fn(&str) -> Option<Value>) -> Result<Location, ImportError> {
+    let required = |property: &String| {
+        property_string(lookup(property).as_ref())
+            .ok_or_else(|| ImportError::MissingProperty { feature, property: property.clone() })
+    };
+    // numbers lose the leading zeros of zipcodes
+    let zipcode = match (lookup(&mapping.zipcode), mapping.zipcode_digits) {
+        (Some(Value::Number(n)), Some(digits)) => match n.as_u64() {
+            Some(n) => format!("{:0digits$}", n, digits = digits),
+            None => return Err(ImportError::InvalidValue { feature, property: mapping.zipcode.clone(), reason: format!("{} is not a zipcode", n) }),
+        },
+        (Some(Value::Number(_)), None) => return Err(ImportError::InvalidValue {
+            feature,
+            property: mapping.zipcode.clone(),
+            reason: "Zipcodes need to be stored as strings".to_owned(),
+        }),
+        _ => required(&mapping.zipcode)?,
+    };
+    let optional = |property: &Option<String>| {
+        property
+            .as_ref()
+            .and_then(|property| property_f64(lookup(property).as_ref()))
+            .unwrap_or(0.0)
+    };
+    if polygons.is_empty() {
+        return Err(ImportError::InvalidGeometry { feature, reason: "No polygons".to_owned() });
+    }
+    Ok(Location {
+        zipcode,
+        city: required(&mapping.city)?,
+        qkm: optional(&mapping.qkm),
+        population: optional(&mapping.population) as i32,
+        polygons,
+    })
+}
+
+/// Builds Locations from a GeoJSON FeatureCollection,
+/// in which every feature is a zipcode area with a
+/// Polygon or MultiPolygon geometry.
+pub fn locations_from_geojson(geojson: &str, mapping: &PropertyMapping) -> Result<Locations, ImportError> {
+    let collection: Value = serde_json::from_str(geojson)?;
+    if collection.get("type").and_then(Value::as_str) != Some("FeatureCollection") {
+        return Err(ImportError::InvalidStructure("Not a FeatureCollection"));
+    }
+    let features = collection
+        .get("features")
+        .and_then(Value::as_array)
+        .ok_or(ImportError::InvalidStructure("FeatureCollection without features"))?;
+    let locations = features
+        .iter()
+        .enumerate()
+        .map(|(feature, value)| {
+            let geometry = value
+                .get("geometry")
+                .ok_or_else(|| ImportError::InvalidGeometry { feature, reason: "Feature without geometry".to_owned() })?;
+            let polygons = geojson_polygons(geometry)
+                .map_err(|reason| ImportError::InvalidGeometry { feature, reason })?;
+            let properties = value.get("properties");
+            build_location(feature, polygons, mapping, |property| {
+                properties?.get(property).cloned()
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(Locations { locations })
+}
+
+/// A node of a parsed WKT coordinate list,
+/// e.g. `((1 2, 3 4), (5 6, 7 8))`.
+#[derive(Debug)]
+enum WktNode {
+    /// A parenthesized list of nodes
+    List(Vec<WktNode>),
+    /// A single coordinate (additional dimensions are dropped)
+    Coord(f64, f64),
+}
+
+/// Parses a parenthesized WKT coordinate list starting at the beginning of input.
+/// Returns the node and the remaining input.
+fn parse_wkt_node(input: &str) -> Result<(WktNode, &str), String> {
+    let mut rest = input.trim_start().strip_prefix('(').ok_or("Expected (")?;
+    let mut nodes = vec![];
+    loop {
+        rest = rest.trim_start();
+        if rest.starts_with('(') {
+            let (node, remaining) = parse_wkt_node(rest)?;
+            nodes.push(node);
+            rest = remaining;
+        } else {
+            let end = rest.find([',', ')']).ok_or("Unterminated coordinate")?;
+            let mut values = rest[..end].split_whitespace().map(str::parse::<f64>);
+            match (values.next(), values.next()) {
+                (Some(Ok(x)), Some(Ok(y))) => nodes.push(WktNode::Coord(x, y)),
+                _ => return Err(format!("Invalid coordinate {}", &rest[..end])),
+            }
+            rest = &rest[end..];
+        }
+        rest = rest.trim_start();
+        if let Some(remaining) = rest.strip_prefix(',') {
+            rest = remaining;
+        } else if let Some(remaining) = rest.strip_prefix(')') {
+            return Ok((WktNode::List(nodes), remaining));
+        } else {
+            return Err("Expected , or )".to_owned());
+        }
+    }
+}
+
+/// Converts a parsed WKT polygon (a list of rings) into a Polygon.
+fn wkt_polygon(node: &WktNode) -> Result<Polygon, String> {
+    let rings = match node {
+        WktNode::List(rings) => rings,
+        WktNode::Coord(..) => return Err("Expected a list of rings".to_owned()),
+    };
+    let rings = rings
+        .iter()
+        .map(|ring| match ring {
+            WktNode::List(coords) => coords
+                .iter()
+                .map(|coord| match coord {
+                    WktNode::Coord(x, y) => Ok((*x, *y)),
+                    WktNode::List(_) => Err("Expected a coordinate".to_owned()),
+                })
+                .collect(),
+            WktNode::Coord(..) => Err("Expected a ring".to_owned()),
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    polygon_from_rings(&rings)
+}
+
+/// Converts a WKT POLYGON or MULTIPOLYGON (x being the longitude,
+/// y the latitude) into Polygons.
+pub fn polygons_from_wkt(wkt: &str) -> Result<Vec<Polygon>, String> {
+    let wkt = wkt.trim();
+    let split = wkt.find('(').ok_or("Missing coordinates")?;
+    let kind = wkt[..split].trim().to_ascii_uppercase();
+    let (node, rest) = parse_wkt_node(&wkt[split..])?;
+    if !rest.trim().is_empty() {
+        return Err("Trailing characters".to_owned());
+    }
+    match (kind.as_str(), &node) {
+        ("POLYGON", _) => Ok(vec![wkt_polygon(&node)?]),
+        ("MULTIPOLYGON", WktNode::List(polygons)) => polygons.iter().map(wkt_polygon).collect(),
+        _ => Err(format!("Unsupported geometry type {}", kind)),
+    }
+}
+
+/// Splits CSV data line by line into records of fields.
+/// Fields may be quoted using ", in which case they may contain
+/// commas, line breaks and quotes (written as "").
+#[derive(Debug, Default)]
+struct CsvRecords {
+    /// The fields of the current record
+    record: Vec<String>,
+    /// The current field
+    field: String,
+    /// Whether the current field is quoted
+    quoted: bool,
+}
+
+impl CsvRecords {
+    /// Parses the next line (without its line break), returns
+    /// the record if the line break is not part of a quoted field.
+    fn push_line(&mut self, line: &str) -> Option<Vec<String>> {
+        let mut chars = line.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' if self.quoted && chars.peek() == Some(&'"') => {
+                    chars.next();
+                    self.field.push('"');
+                },
+                '"' => self.quoted = !self.quoted,
+                ',' if !self.quoted => self.record.push(std::mem::take(&mut self.field)),
+                '\r' if !self.quoted => {},
+                c => self.field.push(c),
+            }
+        }
+        if self.quoted {
+            self.field.push('\n');
+            return None;
+        }
+        self.record.push(std::mem::take(&mut self.field));
+        Some(std::mem::take(&mut self.record))
+    }
+
+    /// Returns the last record if it is unterminated (i.e. has an unbalanced quote).
+    fn finish(mut self) -> Option<Vec<String>> {
+        if self.field.is_empty() && self.record.is_empty() {
+            return None;
+        }
+        self.record.push(self.field);
+        Some(self.record)
+    }
+}
+
+/// Splits CSV data into records of fields, see [CsvRecords].
+fn parse_csv(csv: &str) -> Vec<Vec<String>> {
+    let mut parser = CsvRecords::default();
+    let mut records = csv
+        .lines()
+        .filter_map(|line| parser.push_line(line))
+        .collect::<Vec<_>>();
+    records.extend(parser.finish());
+    records
+}
+
+/// Builds Locations from CSV data (e.g. exported from a shapefile using QGIS or ogr2ogr),
+/// which has a header row and one row per zipcode area.
+/// The geometry column needs to contain a WKT POLYGON or MULTIPOLYGON.
+pub fn locations_from_wkt_csv(csv: &str, mapping: &PropertyMapping) -> Result<Locations, ImportError> {
+    let mut records = parse_csv(csv).into_iter();
+    let header = records.next().ok_or(ImportError::InvalidStructure("Missing header"))?;
+    let column = |property: &str| header.iter().position(|name| name.trim() == property);
+    let geometry_column = column(&mapping.geometry)
+        .ok_or_else(|| ImportError::MissingProperty { feature: 0, property: mapping.geometry.clone() })?;
+    let locations = records
+        .filter(|record| record.iter().any(|field| !field.trim().is_empty()))
+        .enumerate()
+        .map(|(feature, record)| {
+            let wkt = record
+                .get(geometry_column)
+                .ok_or_else(|| ImportError::MissingProperty { feature, property: mapping.geometry.clone() })?;
+            let polygons = polygons_from_wkt(wkt)
+                .map_err(|reason| ImportError::InvalidGeometry { feature, reason })?;
+            build_location(feature, polygons, mapping, |property| {
+                let value = record.get(column(property)?)?;
+                Some(Value::String(value.clone()))
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(Locations { locations })
+}
+
+/// Reads a table assigning cities to states (or districts) from CSV data
+/// with a header row, using the columns named city_column and state_column.
+/// Returns a (city, state) pair for every row,
+/// see [RegionHierarchy::with_states](crate::region::RegionHierarchy::with_states).
+pub fn state_table_from_csv(csv: &str, city_column: &str, state_column: &str) -> Result<Vec<(String, String)>, ImportError> {
+    let mut records = parse_csv(csv).into_iter();
+    let header = records.next().ok_or(ImportError::InvalidStructure("Missing header"))?;
+    let column = |property: &str| header
+        .iter()
+        .position(|name| name.trim() == property)
+        .ok_or_else(|| ImportError::MissingProperty { feature: 0, property: property.to_owned() });
+    let (city_column, state_column) = (column(city_column)?, column(state_column)?);
+    records
+        .filter(|record| record.iter().any(|field| !field.trim().is_empty()))
+        .enumerate()
+        .map(|(feature, record)| {
+            let field = |column: usize, property: &str| record
+                .get(column)
+                .map(|value| value.trim().to_owned())
+                .ok_or_else(|| ImportError::MissingProperty { feature, property: property.to_owned() });
+            Ok((field(city_column, "city")?, field(state_column, "state")?))
+        })
+        .collect()
+}
+
+/// A measurement of a single pollutant, e.g. from another sensor network than the DEBS data.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PollutantMeasurement {
+    /// The time of the measurement, in seconds since the epoch
+    pub timestamp_seconds: i64,
+    /// The latitude of the sensor
+    pub latitude: f32,
+    /// The longitude of the sensor
+    pub longitude: f32,
+    /// The measured pollutant
+    pub pollutant: Pollutant,
+    /// The concentration, in the unit of the pollutant (see [Pollutant::unit])
+    pub value: f32,
+}
+
+/// Defines how rows with particles (PM10 and PM2.5) are handled by [PollutantCsvReader].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum ParticleRows {
+    /// Reject the rows, as particles are measured by the sensors of the batches
+    #[default]
+    Reject,
+    /// Accept the rows, their AQIs are calculated like those of the other pollutants,
+    /// separately from the particles of the batches
+    Accept,
+}
+
+/// The columns of the CSV data read by [pollutant_measurements_from_csv].
+pub const POLLUTANT_COLUMNS: [&str; 5] = ["timestamp", "latitude", "longitude", "pollutant", "value"];
+
+/// Reads measurements of other pollutants than the particles of the DEBS data
+/// line by line from CSV data with a header row containing the columns of
+/// [POLLUTANT_COLUMNS] (in any order), so only the current row of the CSV data is kept in memory
+/// (the parsed measurements are returned one by one and need to be collected by the caller).
+/// The timestamp is given in seconds since the epoch, the pollutant by its name
+/// (e.g. O3 or CO, see [Pollutant::name]) and the value in the unit of the pollutant.
+/// Negative and non-finite values are rejected, PM10 and PM2.5 are handled as
+/// defined by [ParticleRows] (rejected by default).
+/// The rows of errors are the line numbers (starting at 1 for the header).
+#[derive(Debug, Default)]
+pub struct PollutantCsvReader {
+    /// Splits the lines into records
+    records: CsvRecords,
+    /// The index of each of [POLLUTANT_COLUMNS], None until the header is read
+    columns: Option<[usize; POLLUTANT_COLUMNS.len()]>,
+    /// The number of lines read
+    lines: usize,
+    /// The line number of the first line of the current record
+    record_line: usize,
+    /// How rows with particles are handled
+    particles: ParticleRows,
+}
+
+impl PollutantCsvReader {
+    /// Creates a reader handling rows with particles as defined by particles.
+    pub fn new(particles: ParticleRows) -> Self {
+        Self {
+            particles,
+            ..Self::default()
+        }
+    }
+
+    /// Parses the next line (without its line break),
+    /// returns the measurement if the line completes a non-empty row.
+    pub fn push_line(&mut self, line: &str) -> Result<Option<PollutantMeasurement>, ImportError> {
+        if !self.records.quoted {
+            self.record_line = self.lines + 1;
+        }
+        self.lines += 1;
+        match self.records.push_line(line) {
+            Some(record) => self.parse_record(record),
+            None => Ok(None),
+        }
+    }
+
+    /// Parses an unterminated last row, fails if there is no header.
+    pub fn finish(mut self) -> Result<Option<PollutantMeasurement>, ImportError> {
+        let measurement = match std::mem::take(&mut self.records).finish() {
+            Some(record) => self.parse_record(record)?,
+            None => None,
+        };
+        match self.columns {
+            Some(_) => Ok(measurement),
+            None => Err(ImportError::InvalidStructure("Missing header")),
+        }
+    }
+
+    /// Reads the header (if it has not been read yet) or a measurement from record.
+    fn parse_record(&mut self, record: Vec<String>) -> Result<Option<PollutantMeasurement>, ImportError> {
+        let columns = match self.columns {
+            Some(columns) => columns,
+            None => {
+                let mut columns = [0; POLLUTANT_COLUMNS.len()];
+                for (column, property) in columns.iter_mut().zip(POLLUTANT_COLUMNS.iter()) {
+                    *column = record
+                        .iter()
+                        .position(|name| name.trim() == *property)
+                        .ok_or_else(|| ImportError::MissingProperty { feature: self.record_line, property: (*property).to_owned() })?;
+                }
+                self.columns = Some(columns);
+                return Ok(None);
+            },
+        };
+        if record.iter().all(|field| field.trim().is_empty()) {
+            return Ok(None);
+        }
+        let feature = self.record_line;
+        let field = |index: usize| record
+            .get(columns[index])
+            .map(|value| value.trim())
+            .ok_or_else(|| ImportError::MissingProperty { feature, property: POLLUTANT_COLUMNS[index].to_owned() });
+        let invalid = |index: usize, reason: String| ImportError::InvalidValue { feature, property: POLLUTANT_COLUMNS[index].to_owned(), reason };
+        let parse = |index: usize| -> Result<f32, ImportError> {
+            field(index)?
+                .parse()
+                .map_err(|error: std::num::ParseFloatError| invalid(index, error.to_string()))
+        };
+        let timestamp_seconds = field(0)?
+            .parse()
+            .map_err(|error: std::num::ParseIntError| invalid(0, error.to_string()))?;
+        let pollutant = field(3)?
+            .parse()
+            .map_err(|reason| invalid(3, reason))?;
+        if let (Pollutant::PM10 | Pollutant::PM25, ParticleRows::Reject) = (pollutant, self.particles) {
+            return Err(invalid(3, format!("{} is measured by the particle sensors of the batches", pollutant.name())));
+        }
+        let value = parse(4)?;
+        if !(value.is_finite() && value >= 0.0) {
+            return Err(invalid(4, format!("{} is not a valid concentration", value)));
+        }
+        Ok(Some(PollutantMeasurement { timestamp_seconds, latitude: parse(1)?, longitude: parse(2)?, pollutant, value }))
+    }
+}
+
+/// Reads measurements of other pollutants from CSV data,
+/// handling rows with particles as defined by particles, see [PollutantCsvReader].
+pub fn pollutant_measurements_from_csv(csv: &str, particles: ParticleRows) -> Result<Vec<PollutantMeasurement>, ImportError> {
+    let mut reader = PollutantCsvReader::new(particles);
+    let mut measurements = vec![];
+    for line in csv.lines() {
+        measurements.extend(reader.push_line(line)?);
+    }
+    measurements.extend(reader.finish()?);
+    Ok(measurements)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ImportError,ParticleRows,PollutantMeasurement,PropertyMapping,locations_from_geojson,locations_from_wkt_csv,polygons_from_wkt,pollutant_measurements_from_csv,state_table_from_csv};
+    use crate::AnalysisLocations;
+    use crate::aqi::Pollutant;
+    use crate::gen::challenger::Locations;
+
+    #[test]
+    fn test_import_geojson() {
+        let geojson = r#"{
+            "type": "FeatureCollection",
+            "features": [
+                {
+                    "type": "Feature",
+                    "properties": { "plz": "01067", "ort": "Dresden", "qkm": 6.87, "einwohner": 11957 },
+                    "geometry": { "type": "Polygon", "coordinates": [
+                        [[13.7, 51.0], [13.8, 51.0], [13.8, 51.1], [13.7, 51.0]],
+                        [[13.75, 51.01], [13.76, 51.01], [13.76, 51.02], [13.75, 51.01]]
+                    ] }
+                },
+                {
+                    "type": "Feature",
+                    "properties": { "plz": 79098, "ort": "Freiburg im Breisgau" },
+                    "geometry": { "type": "MultiPolygon", "coordinates": [
+                        [[[7.8, 47.9], [7.9, 47.9], [7.9, 48.0], [7.8, 47.9]]],
+                        [[[7.7, 47.9], [7.75, 47.9], [7.75, 48.0], [7.7, 47.9]]]
+                    ] }
+                }
+            ]
+        }"#;
+        let locations = locations_from_geojson(geojson, &PropertyMapping::default())
+            .expect("Import should work")
+            .locations;
+        assert_eq!(locations.len(), 2);
+        assert_eq!(locations[0].zipcode, "01067");
+        assert_eq!(locations[0].city, "Dresden");
+        assert_eq!(locations[0].qkm, 6.87);
+        assert_eq!(locations[0].population, 11957);
+        // the hole is kept
+        assert_eq!(locations[0].polygons.len(), 1);
+        assert_eq!(locations[0].polygons[0].points.len(), 4);
+        assert_eq!(locations[0].polygons[0].interiors.len(), 1);
+        assert_eq!(locations[0].polygons[0].interiors[0].points[1].longitude, 13.76);
+        // points inside the hole are not part of the location
+        let analysis = AnalysisLocations::new(Locations { locations: locations.clone() });
+        assert!(analysis.localize(51.012, 13.758).next().is_none());
+        assert!(analysis.localize(51.005, 13.79).next().is_some());
+        assert_eq!(locations[0].polygons[0].points[1].longitude, 13.8);
+        assert_eq!(locations[0].polygons[0].points[1].latitude, 51.0);
+        assert_eq!(locations[1].zipcode, "79098");
+        assert_eq!(locations[1].population, 0);
+        assert_eq!(locations[1].polygons.len(), 2);
+
+        let mapping = PropertyMapping {
+            city: "name".to_owned(),
+            ..PropertyMapping::default()
+        };
+        match locations_from_geojson(geojson, &mapping) {
+            Err(ImportError::MissingProperty { feature: 0, property }) => assert_eq!(property, "name"),
+            other => panic!("Unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_numeric_zipcodes() {
+        let geojson = |zipcode: &str| format!(r#"{{
+            "type": "FeatureCollection",
+            "features": [{{
+                "type": "Feature",
+                "properties": {{ "plz": {}, "ort": "Dresden" }},
+                "geometry": {{ "type": "Polygon", "coordinates": [[[13.7, 51.0], [13.8, 51.0], [13.8, 51.1], [13.7, 51.0]]] }}
+            }}]
+        }}"#, zipcode);
+        let import = |zipcode: &str, mapping: &PropertyMapping| locations_from_geojson(&geojson(zipcode), mapping)
+            .map(|locations| locations.locations[0].zipcode.clone());
+        let mapping = PropertyMapping::default();
+        assert_eq!(import("1067", &mapping).ok(), Some("01067".to_owned()));
+        assert_eq!(import("79098", &mapping).ok(), Some("79098".to_owned()));
+        assert_eq!(import("\"1067\"", &mapping).ok(), Some("1067".to_owned()));
+        assert!(matches!(import("10.67", &mapping), Err(ImportError::InvalidValue { feature: 0, .. })));
+        let strict = PropertyMapping { zipcode_digits: None, ..PropertyMapping::default() };
+        assert!(matches!(import("1067", &strict), Err(ImportError::InvalidValue { feature: 0, .. })));
+        assert_eq!(import("\"01067\"", &strict).ok(), Some("01067".to_owned()));
+    }
+
+    #[test]
+    fn test_import_wkt_csv() {
+        let csv = "zip,name,area,WKT\n\
+            01067,Dresden,6.87,\"POLYGON ((13.7 51.0, 13.8 51.0, 13.8 51.1, 13.7 51.0))\"\r\n\
+            79098,\"Freiburg, Breisgau\",,\"MULTIPOLYGON (((7.8 47.9, 7.9 47.9, 7.9 48.0, 7.8 47.9)), ((7.7 47.9,7.75 47.9,7.75 48.0,7.7 47.9)))\"\n";
+        let mapping = PropertyMapping {
+            zipcode: "zip".to_owned(),
+            city: "name".to_owned(),
+            qkm: Some("area".to_owned()),
+            population: None,
+            geometry: "WKT".to_owned(),
+            zipcode_digits: None,
+        };
+        let locations = locations_from_wkt_csv(csv, &mapping)
+            .expect("Import should work")
+            .locations;
+        assert_eq!(locations.len(), 2);
+        assert_eq!(locations[0].zipcode, "01067");
+        assert_eq!(locations[0].qkm, 6.87);
+        assert_eq!(locations[0].polygons[0].points.len(), 4);
+        assert_eq!(locations[1].city, "Freiburg, Breisgau");
+        assert_eq!(locations[1].qkm, 0.0);
+        assert_eq!(locations[1].polygons.len(), 2);
+        assert_eq!(locations[1].polygons[1].points[1].longitude, 7.75);
+    }
+
+    #[test]
+    fn test_invalid_wkt() {
+        assert!(polygons_from_wkt("POINT (1 2)").is_err());
+        assert!(polygons_from_wkt("POLYGON ((1 2, 3 4, 5 6, 1 2)").is_err());
+        assert!(polygons_from_wkt("POLYGON ((1 2, 3 x, 5 6, 1 2))").is_err());
+        assert!(polygons_from_wkt("POLYGON ((1 2, 3 4))").is_err());
+        assert_eq!(polygons_from_wkt("polygon((1 2 0, 3 4 0, 5 6 0, 1 2 0))").map(|polys| polys.len()), Ok(1));
+    }
+
+    #[test]
+    fn test_import_state_table() {
+        let csv = "ort,bundesland\nDresden,Sachsen\n\n\"Freiburg, Breisgau\",Baden-Württemberg\n";
+        let table = state_table_from_csv(csv, "ort", "bundesland").expect("Import should work");
+        assert_eq!(table, vec![
+            ("Dresden".to_owned(), "Sachsen".to_owned()),
+            ("Freiburg, Breisgau".to_owned(), "Baden-Württemberg".to_owned()),
+        ]);
+        assert!(matches!(
+            state_table_from_csv(csv, "ort", "land"),
+            Err(ImportError::MissingProperty { feature: 0, .. })
+        ));
+    }
+
+    #[test]
+    fn test_import_pollutants() {
+        let csv = "value,timestamp,pollutant,latitude,longitude\n\
+            0.054,1583020800,O3,48.0,8.0\n\
+            \n\
+            3.5,1583021100,co,48.1,8.1";
+        let measurements = pollutant_measurements_from_csv(csv, ParticleRows::Reject).expect("Import should work");
+        assert_eq!(measurements, vec![
+            PollutantMeasurement { timestamp_seconds: 1583020800, latitude: 48.0, longitude: 8.0, pollutant: Pollutant::O3, value: 0.054 },
+            PollutantMeasurement { timestamp_seconds: 1583021100, latitude: 48.1, longitude: 8.1, pollutant: Pollutant::CO, value: 3.5 },
+        ]);
+        assert!(matches!(pollutant_measurements_from_csv("", ParticleRows::Reject), Err(ImportError::InvalidStructure(_))));
+        assert!(matches!(
+            pollutant_measurements_from_csv("timestamp,latitude,longitude,pollutant\n", ParticleRows::Reject),
+            Err(ImportError::MissingProperty { feature: 1, .. })
+        ));
+        // errors contain the line number, counting the header and blank lines
+        assert!(matches!(
+            pollutant_measurements_from_csv("timestamp,latitude,longitude,pollutant,value\n1,48,8,CO2,1\n", ParticleRows::Reject),
+            Err(ImportError::InvalidValue { feature: 2, .. })
+        ));
+        assert!(matches!(
+            pollutant_measurements_from_csv("timestamp,latitude,longitude,pollutant,value\n1,48,8,CO,1\n\n\"2\n\",48,8,CO,1\n3,48,8,CO,-1\n", ParticleRows::Reject),
+            Err(ImportError::InvalidValue { feature: 6, .. })
+        ));
+        // particles are measured by the sensors of the batches
+        match pollutant_measurements_from_csv("timestamp,latitude,longitude,pollutant,value\n1,48,8,PM2.5,12.5\n", ParticleRows::Reject) {
+            Err(ImportError::InvalidValue { feature: 2, property, .. }) => assert_eq!(property, "pollutant"),
+            other => panic!("Unexpected result {:?}", other),
+        }
+        // unless they are accepted explicitly
+        assert_eq!(
+            pollutant_measurements_from_csv("timestamp,latitude,longitude,pollutant,value\n1,48,8,PM2.5,12.5\n", ParticleRows::Accept).expect("Import should work"),
+            vec![PollutantMeasurement { timestamp_seconds: 1, latitude: 48.0, longitude: 8.0, pollutant: Pollutant::PM25, value: 12.5 }]
+        );
+    }
+}
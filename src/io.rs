@@ -1,8 +1,12 @@
 use crate::gen::challenger::{Locations,Batch};
+use crate::gen::index::LocationsIndex;
+use crate::AnalysisLocations;
+use crate::persist::source_hash;
+use crate::import::{ImportError,ParticleRows,PollutantCsvReader,PollutantMeasurement,PropertyMapping,locations_from_geojson,locations_from_wkt_csv,state_table_from_csv};
 
 use bytes::Bytes;
 use tokio::fs::File;
-use tokio::io::AsyncReadExt;
+use tokio::io::{AsyncBufReadExt,AsyncReadExt,AsyncWriteExt,BufReader};
 
 use prost::Message;
 
@@ -42,22 +46,173 @@ pub enum LoadError {
         /// The error which occurred during decoding.
         #[source]
         source: prost::DecodeError,
+    },
+    /// Returned when the file is not valid UTF-8.
+    #[error("Failed to read {filename} as UTF-8: {source}")]
+    FileUtf8Error {
+        /// The filename of the file which is not valid UTF-8.
+        filename: String,
+        /// The error which occurred during conversion.
+        #[source]
+        source: std::str::Utf8Error,
+    },
+    /// Returned when locations, a state table or measurements could not be imported
+    /// from the (GeoJSON or CSV) data contained in the file.
+    #[error("Failed to import {filename}: {source}")]
+    FileImportError {
+        /// The filename of the file which could not be imported.
+        filename: String,
+        /// The error which occurred during import.
+        #[source]
+        source: ImportError,
     }
 }
 
+/// A Storing Error, caused by an error when
+/// - creating the file
+/// - writing to the file
+#[derive(Error,Debug)]
+pub enum StoreError {
+    /// Returned when tokio::File::create() fails.
+    #[error("Failed to create {filename}: {source}")]
+    FileCreateError {
+        /// The filename of the file which could not be created.
+        filename: String,
+        /// The error returned by tokio::File::create().
+        #[source]
+        source: tokio::io::Error,
+    },
+    /// Returned when writing to or flushing the file fails.
+    #[error("Failed to write {filename}: {source}")]
+    FileWriteError {
+        /// The filename of the file which could not be written.
+        filename: String,
+        /// The error returned when writing.
+        #[source]
+        source: tokio::io::Error,
+    },
+}
+
+/// Reads the full contents of the file at filename.
+async fn read_file(filename: &str) -> Result<Bytes, LoadError> {
+    let mut f = File::open(filename)
+        .await
+        .map_err(|source| LoadError::FileOpenError { filename: filename.to_owned(), source })?;
+    let mut data = vec![];
+    f.read_to_end(&mut data)
+        .await
+        .map_err(|source| LoadError::FileReadError { filename: filename.to_owned(), source })?;
+    Ok(Bytes::from(data))
+}
+
 /// Loads the protobuf locations data from a file
 /// named locations_dump.bin in the directory `root`.
 pub async fn load_locations(root: &str) -> Result<Locations, LoadError> {
     let filename = format!("{}/locations_dump.bin", &root);
-    let mut f = File::open(&filename)
+    let b = read_file(&filename).await?;
+    Message::decode(b).map_err(|source| LoadError::FileDecodeError { filename: filename, source })
+}
+
+/// Reads the file at filename and imports Locations using the import function.
+async fn import_locations(
+    filename: &str,
+    mapping: &PropertyMapping,
+    import: fn(&str, &PropertyMapping) -> Result<Locations, ImportError>
+) -> Result<Locations, LoadError> {
+    let b = read_file(filename).await?;
+    let data = std::str::from_utf8(&b)
+        .map_err(|source| LoadError::FileUtf8Error { filename: filename.to_owned(), source })?;
+    import(data, mapping).map_err(|source| LoadError::FileImportError { filename: filename.to_owned(), source })
+}
+
+/// Loads locations from a GeoJSON FeatureCollection stored at filename.
+/// See [locations_from_geojson].
+pub async fn load_locations_geojson(filename: &str, mapping: &PropertyMapping) -> Result<Locations, LoadError> {
+    import_locations(filename, mapping, locations_from_geojson).await
+}
+
+/// Loads locations from a CSV file with WKT geometries stored at filename.
+/// See [locations_from_wkt_csv].
+pub async fn load_locations_wkt_csv(filename: &str, mapping: &PropertyMapping) -> Result<Locations, LoadError> {
+    import_locations(filename, mapping, locations_from_wkt_csv).await
+}
+
+/// Loads a table assigning cities to states from a CSV file stored at filename.
+/// See [state_table_from_csv].
+pub async fn load_state_table(filename: &str, city_column: &str, state_column: &str) -> Result<Vec<(String, String)>, LoadError> {
+    let b = read_file(filename).await?;
+    let data = std::str::from_utf8(&b)
+        .map_err(|source| LoadError::FileUtf8Error { filename: filename.to_owned(), source })?;
+    state_table_from_csv(data, city_column, state_column)
+        .map_err(|source| LoadError::FileImportError { filename: filename.to_owned(), source })
+}
+
+/// Loads measurements of other pollutants from a CSV file stored at filename,
+/// reading it line by line and handling rows with particles as defined by particles.
+/// Only the parsed measurements are kept in memory. See [PollutantCsvReader].
+pub async fn load_pollutant_measurements(filename: &str, particles: ParticleRows) -> Result<Vec<PollutantMeasurement>, LoadError> {
+    let f = File::open(filename)
         .await
-        .map_err(|source| LoadError::FileOpenError { filename: filename.clone(), source })?;
-    let mut data = vec![];
-    f.read_to_end(&mut data)
+        .map_err(|source| LoadError::FileOpenError { filename: filename.to_owned(), source })?;
+    let import_error = |source| LoadError::FileImportError { filename: filename.to_owned(), source };
+    let mut lines = BufReader::new(f).lines();
+    let mut reader = PollutantCsvReader::new(particles);
+    let mut measurements = vec![];
+    while let Some(line) = lines
+        .next_line()
         .await
-        .map_err(|source| LoadError::FileReadError { filename: filename.clone(), source })?;
-    let b = Bytes::from(data);
-    Message::decode(b).map_err(|source| LoadError::FileDecodeError { filename: filename, source })
+        .map_err(|source| LoadError::FileReadError { filename: filename.to_owned(), source })? {
+        measurements.extend(reader.push_line(&line).map_err(import_error)?);
+    }
+    measurements.extend(reader.finish().map_err(import_error)?);
+    Ok(measurements)
+}
+
+/// Loads a prebuilt index of AnalysisLocations from a file
+/// named locations_index.bin in the directory `root`.
+pub async fn load_locations_index(root: &str) -> Result<LocationsIndex, LoadError> {
+    let filename = format!("{}/locations_index.bin", &root);
+    let b = read_file(&filename).await?;
+    LocationsIndex::decode(b).map_err(|source| LoadError::FileDecodeError { filename, source })
+}
+
+/// Stores a prebuilt index of AnalysisLocations (see [AnalysisLocations::to_index])
+/// into a file named locations_index.bin in the directory `root`.
+pub async fn store_locations_index(root: &str, index: &LocationsIndex) -> Result<(), StoreError> {
+    use bytes::BytesMut;
+    let filename = format!("{}/locations_index.bin", &root);
+    let mut buf = BytesMut::with_capacity(index.encoded_len());
+    index.encode(&mut buf).expect("Buffer should have sufficient capacity");
+    let mut f = File::create(&filename)
+        .await
+        .map_err(|source| StoreError::FileCreateError { filename: filename.clone(), source })?;
+    f.write_all(&buf)
+        .await
+        .map_err(|source| StoreError::FileWriteError { filename: filename.clone(), source })?;
+    f.flush()
+        .await
+        .map_err(|source| StoreError::FileWriteError { filename, source })
+}
+
+/// Loads AnalysisLocations for the locations dump in the directory `root`.
+/// If a matching index (built from the same dump, see [load_locations_index])
+/// exists, it is used instead of building the AnalysisLocations from scratch.
+/// Returns the AnalysisLocations, the hash of the locations dump
+/// (which is needed to create an index using [AnalysisLocations::to_index])
+/// and whether a matching index was used (if not, the index is missing or outdated).
+pub async fn load_analysis_locations(root: &str) -> Result<(AnalysisLocations, u64, bool), LoadError> {
+    let filename = format!("{}/locations_dump.bin", &root);
+    let b = read_file(&filename).await?;
+    let hash = source_hash(&b);
+    let indexed = load_locations_index(root)
+        .await
+        .ok()
+        .and_then(|index| AnalysisLocations::from_index(index, hash).ok());
+    if let Some(locations) = indexed {
+        return Ok((locations, hash, true));
+    }
+    let locations = Locations::decode(b).map_err(|source| LoadError::FileDecodeError { filename, source })?;
+    Ok((AnalysisLocations::new(locations), hash, false))
 }
 
 /// Loads the batch of measurements num relative to the directory `root`.
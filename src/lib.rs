@@ -20,33 +20,94 @@ pub mod aggregate;
 pub mod spliter;
 /// Joins all components to solve query 1 of the DEBS Grand Challenge.
 pub mod pipeline;
+/// Provides a precomputed grid index as an alternative
+/// to localizing using AnalysisLocations directly.
+pub mod grid;
+/// Provides a memo of sensor locations, which avoids
+/// localizing the same sensor in every batch.
+pub mod memo;
+/// Contains functions to convert AnalysisLocations
+/// from/to an index which can be stored on disk.
+pub mod persist;
+/// Contains functions to build Locations from
+/// GeoJSON and WKT/CSV data.
+pub mod import;
+/// Contains functions to export Locations and results as GeoJSON.
+pub mod export;
+/// Provides information about cities,
+/// which consist of one or more zipcodes.
+pub mod city;
+/// Contains a hierarchy of regions (zipcode, city and state),
+/// which allows aggregating measurements on several levels.
+pub mod region;
+/// Contains robust aggregates (median, trimmed mean and outlier rejection),
+/// which can be used instead of the mean.
+pub mod robust;
+/// Contains the plausibility checks rejecting implausible measurements
+/// (e.g. of broken or stuck sensors) before they are aggregated.
+pub mod validate;
+/// Contains the correction of particle concentrations measured
+/// by low-cost sensors at high humidity.
+pub mod humidity;
 
 use crate::gen::challenger::Locations;
-use geo::{MultiPolygon,point,prelude::{Contains,BoundingRect}};
+use geo::{Closest,MultiPolygon,point,prelude::{Contains,BoundingRect,ClosestPoint,GeodesicDistance}};
 
 /// A newtype uniquely identifying a zipcode in AnalysisLocations.
 type CityId = u32;
 
+/// A newtype uniquely identifying a location (zipcode) in AnalysisLocations.
+type LocationId = u32;
+
 /// A tuple denoting metadata about a Location
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq)]
 pub struct AnalysisLocation {
     /// The locations zipcode
     zipcode: String,
+    /// The internal id of the location, which can be used
+    /// with AnalysisLocations::zipcode to obtain the zipcode
+    locationid: LocationId,
     /// The internal id of the location,
     /// which can be used with AnalysisLocations::lookup
     /// to obtain the location's name
     cityid: CityId,
+    /// The area of the location in km²
+    qkm: f64,
+    /// The number of inhabitants of the location
+    population: i32,
 }
 
 
 use std::sync::atomic::AtomicUsize;
 
+/// Describes how useful the caches of a single zipcode have been.
+/// See [AnalysisLocations::cache_stats].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LocationCacheStats<'a> {
+    /// The zipcode the stats belong to
+    pub zipcode: &'a str,
+    /// Count of how often the zipcode's insidecache was useful
+    pub insidehits: usize,
+    /// Count of how often the zipcode's outsidecache was useful
+    pub outsidehits: usize,
+    /// Count of how often neither cache was useful
+    pub misses: usize,
+    /// The number of items in the insidecache
+    pub insideitems: usize,
+    /// The number of items in the outsidecache
+    pub outsideitems: usize,
+}
+
 /// A collection of Locations which can be queried using latitude and longitude.
 #[derive(Debug)]
 pub struct AnalysisLocations {
     /// A RTree of the bounding boxes of each zipcode
     /// Used to quickly find zipcodes which could contain a city.
     bboxtree: RTree<RTreeLocation>,
+    /// The envelope of all bounding boxes in bboxtree,
+    /// extended by fallbackdistance if set.
+    /// Used to quickly discard points which can not belong to any zipcode.
+    bounds: AABB<[f64; 2]>,
     /// Stores the polygon(s) for each zipcode.
     /// As bounds testing is slow, insidecache and outsidecache
     /// are checked first.
@@ -66,9 +127,18 @@ pub struct AnalysisLocations {
     pub cachemisses: AtomicUsize,
     /// Count of how often outsidecache was useful
     pub outsidecachehits: AtomicUsize,
+    /// Count of how often neither cache was useful, for every zipcode
+    locationmisses: Vec<AtomicUsize>,
+    /// If set, points which are not contained in any zipcode are
+    /// assigned to the nearest zipcode at most this many meters away.
+    /// See [AnalysisLocations::with_nearest_fallback].
+    fallbackdistance: Option<f64>,
+    /// Count of how often a point was assigned to the nearest zipcode
+    /// because no zipcode contained it
+    pub fallbackhits: AtomicUsize,
 }
 
-use rstar::{AABB,RTree,RTreeObject,Point,PointDistance};
+use rstar::{AABB,Envelope,RTree,RTreeObject,Point,PointDistance};
 
 #[derive(Debug)]
 struct RTreeLocation {
@@ -81,7 +151,7 @@ impl RTreeObject for RTreeLocation {
     type Envelope = AABB<[f64; 2]>;
 
     fn envelope(&self) -> Self::Envelope {
-        self.bbox.clone()
+        self.bbox
     }
 }
 
@@ -116,12 +186,17 @@ struct LocationCacheItem {
 
 use std::sync::RwLock;
 
-/// Amount of items in a LocationCache.
+/// The length of one degree of latitude at the equator in meters.
+/// This is the shortest length of a degree of latitude, which makes it
+/// usable to calculate an upper bound of degrees for a distance in meters.
+const METERS_PER_DEGREE: f64 = 110_574.0;
+
+/// Default amount of items in a LocationCache.
 /// Raising this number increases the hit rate of the cache,
 /// but also increases the nubmer of items which need to be checked.
-const CACHE_SIZE: usize = 32;
+/// See [AnalysisLocations::with_cache_size].
+pub const CACHE_SIZE: usize = 32;
 
-use arrayvec::ArrayVec;
 use rstar::primitives::Line;
 
 /// LocationCache is used to quickly determine if a point is
@@ -136,19 +211,33 @@ struct LocationCache {
     /// to the point which is being added.
     lines: RTree<Line<[f32; 2]>>,
     // items: RwLock<RTree<LocationCacheItem, LocationRTreeParams>>,
-    /// A list of CACHE_SIZE LocationCacheItem,
+    /// A list of up to capacity LocationCacheItem, sorted by descending radius
+    /// (so the largest circles are checked first),
     /// protected from concurrent access using RwLock
-    items: RwLock<ArrayVec<LocationCacheItem, CACHE_SIZE>>,
+    items: RwLock<Vec<LocationCacheItem>>,
+    /// The maximum number of items.
+    /// When full, the item with the smallest radius is replaced
+    /// by new items with a larger radius.
+    capacity: usize,
+    /// Count of how often this cache contained a point
+    hits: AtomicUsize,
 }
 
 impl LocationCache {
-    /// Creates a new LocationCache for the given location
+    /// Creates a new LocationCache for the given location,
+    /// using the points of the exterior and interior rings of all polygons.
     fn new(locations: &crate::gen::challenger::Location) -> Self {
         let points = locations.polygons
             .iter()
-            .flat_map(|poly| poly.points.iter())
+            .flat_map(|poly| poly.points.iter().chain(poly.interiors.iter().flat_map(|ring| ring.points.iter())))
             .map(|point| [point.longitude as f32, point.latitude as f32])
             .collect::<Vec<_>>();
+        Self::from_points(points)
+    }
+
+    /// Creates a new LocationCache using the points of all polygons
+    /// of a location, in the same order as in the protobuf Location.
+    fn from_points(points: Vec<[f32; 2]>) -> Self {
         let points_len = points.len();
         assert!(points_len > 0);
         let lines = (0..points_len)
@@ -162,12 +251,19 @@ impl LocationCache {
                 rstar::primitives::Line::new(first_point, second_point)
             })
             .collect::<Vec<_>>();
-        let lines = RTree::bulk_load(lines);
+        Self::from_lines(RTree::bulk_load(lines))
+    }
+
+    /// Creates a new LocationCache using an already built R-tree
+    /// of the lines of all polygons of a location.
+    fn from_lines(lines: RTree<Line<[f32; 2]>>) -> Self {
         LocationCache {
             lines,
             // items: RwLock::new(RTree::new_with_params()),
             // items: RwLock::new(Vec::with_capacity(<LocationRTreeParams as rstar::RTreeParams>::MAX_SIZE)),
-            items: RwLock::new(ArrayVec::new()),
+            items: RwLock::new(Vec::with_capacity(CACHE_SIZE)),
+            capacity: CACHE_SIZE,
+            hits: AtomicUsize::from(0),
         }
     }
 
@@ -180,17 +276,34 @@ impl LocationCache {
     /// This will occur quite often due to cache misses.
     /// In such cases a check with the actual polygon is necessary.
     fn contains(&self, latitude: f32, longitude: f32) -> bool {
-        self.items
+        let contains = self.items
             .read()
             .unwrap()
             .iter()
             .map(|item| (item, [item.x, item.y].distance_2(&[longitude, latitude])))
-            .any(|(item, distance)| distance < item.rsquare)
+            .any(|(item, distance)| distance < item.rsquare);
+        if contains {
+            self.hits.fetch_add(1, std::sync::atomic::Ordering::AcqRel);
+        }
+        contains
+    }
+
+    /// Returns the smallest squared radius of all items,
+    /// or None if there is still room for more items.
+    fn smallest_rsquare_if_full(&self) -> Option<f32> {
+        let items = self.items.read().unwrap();
+        if items.len() >= self.capacity {
+            Some(items.last().map(|item| item.rsquare).unwrap_or(f32::INFINITY))
+        } else {
+            None
+        }
     }
 
     /// Adds a point at the given coordinates to this LocationCache.
+    /// If the cache is full, the point replaces the item with the
+    /// smallest radius, if its own radius is larger.
     fn add(&self, latitude: f32, longitude: f32) {
-        if self.items.read().unwrap().len() >= CACHE_SIZE {
+        if self.capacity == 0 {
             return;
         }
 
@@ -203,18 +316,41 @@ impl LocationCache {
             return;
         }
         
+        if let Some(smallest) = self.smallest_rsquare_if_full() {
+            if rsquare <= smallest {
+                // would cover less than all current items
+                return;
+            }
+        }
+
         let mut items = self.items.write().unwrap();
-        if items.len() >= CACHE_SIZE {
-            // can happen since read lock was dropped previously
-            // just abort
-            return;
+        if items.len() >= self.capacity {
+            // re-check, since the read lock was dropped previously
+            match items.last() {
+                Some(smallest) if smallest.rsquare < rsquare => {
+                    items.pop();
+                },
+                _ => return,
+            }
         }
-        items.push(LocationCacheItem {
+        let position = items
+            .iter()
+            .position(|item| item.rsquare < rsquare)
+            .unwrap_or_else(|| items.len());
+        items.insert(position, LocationCacheItem {
             x: longitude,
             y: latitude,
             rsquare,
         });
     }
+
+    /// Changes the maximum number of items,
+    /// dropping the items with the smallest radius if necessary.
+    fn set_capacity(&mut self, capacity: usize) {
+        let items = self.items.get_mut().unwrap();
+        items.truncate(capacity);
+        self.capacity = capacity;
+    }
 }
 
 impl AnalysisLocations {
@@ -227,13 +363,14 @@ impl AnalysisLocations {
         let mut known_cities = vec![];
         let mut next_id = 0u32;
         let cache : Vec<LocationCache> = locations.locations.iter()
-            .map(|location| LocationCache::new(location))
+            .map(LocationCache::new)
             .collect();
         let outsidecache : Vec<LocationCache> = locations.locations.iter()
-            .map(|location| LocationCache::new(location))
+            .map(LocationCache::new)
             .collect();
         let locations : Vec<(AnalysisLocation, MultiPolygon<f64>)> = locations.locations.into_iter()
-            .map(|location| {
+            .enumerate()
+            .map(|(locationid, location)| {
                 let multipoly = geo::MultiPolygon::from_iter(
                     location
                         .polygons
@@ -244,8 +381,14 @@ impl AnalysisLocations {
                                 poly.points
                                 .iter()
                                 .map(|point| (point.longitude, point.latitude))),
-                            // interior ring (unused)
-                            vec![]
+                            // interior rings (holes)
+                            poly.interiors
+                                .iter()
+                                .map(|ring| geo::LineString::from_iter(
+                                    ring.points
+                                    .iter()
+                                    .map(|point| (point.longitude, point.latitude))))
+                                .collect()
                         ))
                 );
                 let cityid = match known_cities_map.get(&location.city) {
@@ -259,11 +402,27 @@ impl AnalysisLocations {
                 };
                 let location = AnalysisLocation {
                     zipcode: location.zipcode,
+                    locationid: locationid as LocationId,
                     cityid,
+                    qkm: location.qkm,
+                    population: location.population,
                 };
                 (location, multipoly)
             })
             .collect();
+        Self::from_parts(locations, known_cities, cache, outsidecache)
+    }
+
+    /// Creates a new AnalysisLocations struct from already converted locations
+    /// and caches, where the caches have the same order as the locations.
+    fn from_parts(
+        locations: Vec<(AnalysisLocation, MultiPolygon<f64>)>,
+        known_cities: Vec<String>,
+        insidecache: Vec<LocationCache>,
+        outsidecache: Vec<LocationCache>,
+    ) -> Self {
+        assert_eq!(locations.len(), insidecache.len());
+        assert_eq!(locations.len(), outsidecache.len());
         let bboxitems = locations
             .iter()
             .enumerate()
@@ -279,16 +438,91 @@ impl AnalysisLocations {
             })
             .collect::<Vec<_>>();
         let bboxtree = RTree::bulk_load(bboxitems);
+        let bounds = bboxtree.root().envelope();
+        let locationmisses = locations.iter().map(|_| AtomicUsize::from(0)).collect();
         Self {
             bboxtree,
+            bounds,
             locations,
             known_cities,
-            insidecache: cache,
-            outsidecache: outsidecache,
+            insidecache,
+            outsidecache,
             cachehits: AtomicUsize::from(0),
             cachemisses: AtomicUsize::from(0),
             outsidecachehits: AtomicUsize::from(0),
+            locationmisses,
+            fallbackdistance: None,
+            fallbackhits: AtomicUsize::from(0),
+        }
+    }
+
+    /// Sets the number of items of each inside/outside cache
+    /// (default: [CACHE_SIZE]).
+    pub fn with_cache_size(mut self, cache_size: usize) -> Self {
+        for cache in self.insidecache.iter_mut().chain(self.outsidecache.iter_mut()) {
+            cache.set_capacity(cache_size);
         }
+        self
+    }
+
+    /// Returns how useful the caches have been for every zipcode,
+    /// in the same order as the zipcodes were loaded.
+    /// Zipcodes with many misses are poorly covered by their caches.
+    pub fn cache_stats(&self) -> Vec<LocationCacheStats<'_>> {
+        use std::sync::atomic::Ordering;
+        self.locations
+            .iter()
+            .zip(self.insidecache.iter())
+            .zip(self.outsidecache.iter())
+            .zip(self.locationmisses.iter())
+            .map(|((((location, _), insidecache), outsidecache), misses)| LocationCacheStats {
+                zipcode: &location.zipcode,
+                insidehits: insidecache.hits.load(Ordering::Acquire),
+                outsidehits: outsidecache.hits.load(Ordering::Acquire),
+                misses: misses.load(Ordering::Acquire),
+                insideitems: insidecache.items.read().unwrap().len(),
+                outsideitems: outsidecache.items.read().unwrap().len(),
+            })
+            .collect()
+    }
+
+    /// Enables assigning points which are not contained in any zipcode
+    /// to the nearest zipcode, as long as it is at most max_distance meters away.
+    /// This makes it possible to keep measurements of sensors placed
+    /// slightly outside of the (simplified) zipcode boundaries.
+    /// Used by [AnalysisLocations::localize_or_nearest].
+    pub fn with_nearest_fallback(mut self, max_distance: f64) -> Self {
+        // extend the bounds, so points near the outer border are not discarded
+        let envelope = self.bboxtree.root().envelope();
+        let [minx, miny] = envelope.lower();
+        let [maxx, maxy] = envelope.upper();
+        let max_latitude = miny.abs().max(maxy.abs());
+        let max_degrees = max_distance / (METERS_PER_DEGREE * max_latitude.to_radians().cos());
+        self.bounds = AABB::from_corners(
+            [minx - max_degrees, miny - max_degrees],
+            [maxx + max_degrees, maxy + max_degrees]
+        );
+        self.fallbackdistance = Some(max_distance);
+        self
+    }
+
+    /// Returns the rectangle enclosing all locations
+    /// (x being the longitude, y the latitude).
+    /// If the nearest fallback is enabled, the rectangle is extended
+    /// by the fallback distance.
+    pub fn bounds(&self) -> geo::Rect<f64> {
+        let [minx, miny] = self.bounds.lower();
+        let [maxx, maxy] = self.bounds.upper();
+        geo::Rect::new((minx, miny), (maxx, maxy))
+    }
+
+    /// Quickly checks if the given coordinates are inside [AnalysisLocations::bounds].
+    /// If this returns false, neither [AnalysisLocations::localize] nor
+    /// [AnalysisLocations::localize_or_nearest] will find a location, so
+    /// this can be used to skip searching for points outside the covered area
+    /// (e.g. outside germany).
+    pub fn in_bounds(&self, latitude: f32, longitude: f32) -> bool {
+        self.bounds.contains_point(&[f64::from(longitude), f64::from(latitude)])
     }
 
     /// Tries to find the [AnalysisLocation] which contains the given coordinates.
@@ -327,6 +561,7 @@ impl AnalysisLocations {
                     let (location, bounding_poly) = &self.locations[treeobject.idx as usize];
                     let p = point!(x: f64::from(longitude), y: f64::from(latitude));
                     self.cachemisses.fetch_add(1, std::sync::atomic::Ordering::AcqRel);
+                    self.locationmisses[treeobject.idx as usize].fetch_add(1, std::sync::atomic::Ordering::AcqRel);
                     if bounding_poly.contains(&p) {
                         self.insidecache[treeobject.idx as usize].add(latitude, longitude);
                         Some(location)
@@ -338,11 +573,61 @@ impl AnalysisLocations {
             })
     }
 
+    /// Tries to find the [AnalysisLocation] which contains the given coordinates.
+    /// If none contains them and the nearest fallback is enabled
+    /// (see [AnalysisLocations::with_nearest_fallback]), the nearest
+    /// location within the configured distance is returned instead.
+    pub fn localize_or_nearest(&self, latitude: f32, longitude: f32) -> Option<&AnalysisLocation> {
+        if let Some(location) = self.localize(latitude, longitude).next() {
+            return Some(location);
+        }
+        let max_distance = self.fallbackdistance?;
+        let location = self.nearest(latitude, longitude, max_distance)?;
+        self.fallbackhits.fetch_add(1, std::sync::atomic::Ordering::AcqRel);
+        Some(location)
+    }
+
+    /// Finds the [AnalysisLocation] nearest to the given coordinates,
+    /// if it is at most max_distance meters away.
+    /// Candidates are found using the bounding boxes, then the geodesic distance
+    /// to the closest point of each candidate's (multi)polygon is compared.
+    /// Points contained in a location have a distance of 0 to it.
+    pub fn nearest(&self, latitude: f32, longitude: f32, max_distance: f64) -> Option<&AnalysisLocation> {
+        let p = point!(x: f64::from(longitude), y: f64::from(latitude));
+        // Degrees of longitude get shorter towards the poles,
+        // so scale the upper bound of degrees accordingly
+        let max_degrees = max_distance / (METERS_PER_DEGREE * p.y().to_radians().cos());
+        self.bboxtree
+            .nearest_neighbor_iter_with_distance_2(&[p.x(), p.y()])
+            .take_while(|(_, distance_2)| *distance_2 <= max_degrees * max_degrees)
+            .filter_map(|(treeobject, _)| {
+                let (location, bounding_poly) = &self.locations[treeobject.idx as usize];
+                let closest = match bounding_poly.closest_point(&p) {
+                    Closest::Intersection(closest) | Closest::SinglePoint(closest) => closest,
+                    Closest::Indeterminate => return None,
+                };
+                Some((location, p.geodesic_distance(&closest)))
+            })
+            .filter(|(_, distance)| *distance <= max_distance)
+            .min_by(|(_, a), (_, b)| a.partial_cmp(b).expect("Distance should not be NaN"))
+            .map(|(location, _)| location)
+    }
+
     /// Looks up the name of the city using the provided CityId.
     pub fn lookup(&self, cityid: CityId) -> &str {
         &self.known_cities[cityid as usize]
     }
 
+    /// Looks up the id of the city the location belongs to.
+    pub fn cityid(&self, locationid: LocationId) -> CityId {
+        self.locations[locationid as usize].0.cityid
+    }
+
+    /// Looks up the zipcode of the location using the provided LocationId.
+    pub fn zipcode(&self, locationid: LocationId) -> &str {
+        &self.locations[locationid as usize].0.zipcode
+    }
+
     /// Looks up the city id using the name of a city.
     pub fn inverse_lookup(&self, cityname: &str) -> Option<CityId> {
         use std::convert::TryInto;
@@ -384,7 +669,7 @@ mod tests {
             .await
             .expect("Failed to load batch");
         for measurement in batch.lastyear.iter().chain(batch.current.iter()) {
-            if measurement.latitude < 47.40724 || measurement.latitude > 54.9079 || measurement.longitude < 5.98815 || measurement.longitude > 14.98853 {
+            if !locations.in_bounds(measurement.latitude, measurement.longitude) {
                 // skip measurements outside germany
                 continue;
             }
@@ -394,4 +679,93 @@ mod tests {
             assert_eq!(loc.next(), None);
         }
     }
+
+    /// Generates Locations containing a single zipcode,
+    /// which is a square of 0.01 by 0.01 degrees.
+    pub(crate) fn square_locations() -> super::Locations {
+        square_locations_sized(0.01)
+    }
+
+    /// Generates Locations containing a single zipcode,
+    /// which is a square of size by size degrees.
+    fn square_locations_sized(size: f64) -> super::Locations {
+        use super::gen::challenger::{Location,Locations,Point,Polygon};
+        let points = [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0), (0.0, 0.0)]
+            .iter()
+            .map(|&(x, y)| Point { longitude: 8.0 + x * size, latitude: 48.0 + y * size })
+            .collect();
+        Locations {
+            locations: vec![Location {
+                zipcode: "12345".to_owned(),
+                city: "Square City".to_owned(),
+                qkm: 0.0,
+                population: 0,
+                polygons: vec![Polygon { points, interiors: vec![] }],
+            }],
+        }
+    }
+
+    #[test]
+    fn check_nearest_fallback_works() {
+        use super::AnalysisLocations;
+        use std::sync::atomic::Ordering;
+        // about 55m south of the square
+        let (latitude, longitude) = (47.9995, 8.005);
+
+        let locations = AnalysisLocations::new(square_locations());
+        assert_eq!(locations.localize_or_nearest(latitude, longitude), None);
+        assert!(locations.localize_or_nearest(48.005, 8.005).is_some());
+
+        let locations = AnalysisLocations::new(square_locations())
+            .with_nearest_fallback(20.0);
+        assert_eq!(locations.localize_or_nearest(latitude, longitude), None);
+        assert_eq!(locations.fallbackhits.load(Ordering::SeqCst), 0);
+
+        let locations = AnalysisLocations::new(square_locations())
+            .with_nearest_fallback(100.0);
+        let square = locations.localize_or_nearest(latitude, longitude);
+        assert_eq!(square.map(|location| locations.lookup(location.cityid)), Some("Square City"));
+        assert_eq!(locations.fallbackhits.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn check_cache_replacement_works() {
+        use super::AnalysisLocations;
+        let locations = AnalysisLocations::new(square_locations_sized(0.1))
+            .with_cache_size(2);
+        // two small circles near the corners, then two larger circles
+        // near the other corners replacing them
+        for (latitude, longitude) in &[(48.01, 8.01), (48.09, 8.09), (48.02, 8.08), (48.08, 8.02)] {
+            assert!(locations.localize(*latitude, *longitude).next().is_some());
+        }
+        let stats = locations.cache_stats();
+        assert_eq!(stats[0].zipcode, "12345");
+        assert_eq!(stats[0].misses, 4);
+        assert_eq!(stats[0].insideitems, 2);
+        assert_eq!(stats[0].outsideitems, 0);
+        // the small circle was replaced, so this is a miss again
+        assert!(locations.localize(48.01, 8.01).next().is_some());
+        // covered by one of the larger circles
+        assert!(locations.localize(48.025, 8.075).next().is_some());
+        let stats = locations.cache_stats();
+        assert_eq!(stats[0].misses, 5);
+        assert_eq!(stats[0].insidehits, 1);
+        assert_eq!(stats[0].outsidehits, 0);
+    }
+
+    #[test]
+    fn check_bounds_works() {
+        use super::AnalysisLocations;
+        let locations = AnalysisLocations::new(square_locations());
+        let bounds = locations.bounds();
+        assert_eq!(bounds.min().x_y(), (8.0, 48.0));
+        assert_eq!(bounds.max().x_y(), (8.01, 48.01));
+        assert!(locations.in_bounds(48.005, 8.005));
+        assert!(!locations.in_bounds(47.9995, 8.005));
+
+        // the bounds need to include points rescued by the fallback
+        let locations = locations.with_nearest_fallback(100.0);
+        assert!(locations.in_bounds(47.9995, 8.005));
+        assert!(!locations.in_bounds(47.99, 8.005));
+    }
 }
@@ -0,0 +1,154 @@
+use crate::LocationId;
+
+use std::collections::HashMap;
+use std::sync::RwLock;
+use std::sync::atomic::{AtomicU64,AtomicUsize,Ordering};
+
+/// A single memoized localization result.
+#[derive(Debug)]
+struct MemoEntry {
+    /// The result of the localization,
+    /// None if the sensor is not inside any location.
+    locationid: Option<LocationId>,
+    /// The time (see [SensorMemo::clock]) at which the entry was inserted or last used.
+    /// Entries which have not been used since the last eviction
+    /// are removed first.
+    last_used: AtomicU64,
+}
+
+/// Memoizes the LocationId of sensors, identified by their exact coordinates.
+/// As the same (stationary) sensors report every few minutes, most
+/// measurements can be assigned to a city without localizing them again.
+/// The memo holds at most capacity sensors. When full, sensors not
+/// used since the last eviction are removed (approximating LRU without
+/// needing a write lock on every hit). Newly inserted sensors count as used,
+/// so they are not removed by the next eviction.
+#[derive(Debug)]
+pub struct SensorMemo {
+    /// The maximum number of memoized sensors
+    capacity: usize,
+    /// Maps the bits of latitude and longitude to the memoized entry
+    entries: RwLock<HashMap<(u32, u32), MemoEntry>>,
+    /// Incremented whenever an entry is inserted or used
+    clock: AtomicU64,
+    /// The clock at the last eviction
+    last_eviction: AtomicU64,
+    /// Count of how often a sensor was found in the memo
+    pub hits: AtomicUsize,
+    /// Count of how often a sensor had to be localized
+    pub misses: AtomicUsize,
+    /// Count of how many sensors have been removed from the memo
+    pub evictions: AtomicUsize,
+}
+
+impl SensorMemo {
+    /// Creates a new, empty SensorMemo holding up to capacity sensors.
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "Capacity needs to be positive");
+        Self {
+            capacity,
+            entries: RwLock::new(HashMap::with_capacity(capacity)),
+            clock: AtomicU64::from(0),
+            last_eviction: AtomicU64::from(0),
+            hits: AtomicUsize::from(0),
+            misses: AtomicUsize::from(0),
+            evictions: AtomicUsize::from(0),
+        }
+    }
+
+    /// Returns the memoized LocationId of the sensor at the given coordinates.
+    /// If the sensor is unknown, localize is called and its result is memoized.
+    pub fn get_or_localize(&self, latitude: f32, longitude: f32, localize: impl FnOnce() -> Option<LocationId>) -> Option<LocationId> {
+        let key = (latitude.to_bits(), longitude.to_bits());
+        if let Some(entry) = self.entries.read().unwrap().get(&key) {
+            entry.last_used.store(self.clock.fetch_add(1, Ordering::Relaxed), Ordering::Relaxed);
+            self.hits.fetch_add(1, Ordering::AcqRel);
+            return entry.locationid;
+        }
+        self.misses.fetch_add(1, Ordering::AcqRel);
+        let locationid = localize();
+
+        // evict before inserting, so the new entry is never evicted right away
+        let mut entries = self.entries.write().unwrap();
+        if entries.len() >= self.capacity && !entries.contains_key(&key) {
+            self.evict(&mut entries);
+        }
+        entries.insert(key, MemoEntry {
+            locationid,
+            last_used: AtomicU64::new(self.clock.fetch_add(1, Ordering::Relaxed)),
+        });
+        locationid
+    }
+
+    /// Removes all entries which have not been used since the last eviction.
+    /// If all entries have been used, the least recently used entries are removed
+    /// to make room for one new entry.
+    fn evict(&self, entries: &mut HashMap<(u32, u32), MemoEntry>) {
+        let before = entries.len();
+        let last_eviction = self.last_eviction.swap(self.clock.load(Ordering::Relaxed), Ordering::Relaxed);
+        entries.retain(|_, entry| entry.last_used.load(Ordering::Relaxed) >= last_eviction);
+        if entries.len() >= self.capacity {
+            let mut last_used = entries
+                .values()
+                .map(|entry| entry.last_used.load(Ordering::Relaxed))
+                .collect::<Vec<_>>();
+            let excess = entries.len() + 1 - self.capacity;
+            let (_, &mut oldest_kept, _) = last_used.select_nth_unstable(excess);
+            entries.retain(|_, entry| entry.last_used.load(Ordering::Relaxed) >= oldest_kept);
+        }
+        self.evictions.fetch_add(before - entries.len(), Ordering::AcqRel);
+    }
+
+    /// Returns the share of lookups which were answered by the memo.
+    pub fn hit_rate(&self) -> f64 {
+        let hits = self.hits.load(Ordering::Acquire);
+        let misses = self.misses.load(Ordering::Acquire);
+        hits as f64 / (hits + misses).max(1) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SensorMemo;
+    use std::sync::atomic::Ordering;
+
+    #[test]
+    fn memo_localizes_once() {
+        let memo = SensorMemo::new(10);
+        assert_eq!(memo.get_or_localize(48.0, 8.0, || Some(1)), Some(1));
+        assert_eq!(memo.get_or_localize(48.0, 8.0, || panic!("Should be memoized")), Some(1));
+        assert_eq!(memo.get_or_localize(46.0, 20.0, || None), None);
+        assert_eq!(memo.get_or_localize(46.0, 20.0, || panic!("Should be memoized")), None);
+        assert_eq!(memo.hits.load(Ordering::SeqCst), 2);
+        assert_eq!(memo.misses.load(Ordering::SeqCst), 2);
+        assert_eq!(memo.hit_rate(), 0.5);
+    }
+
+    #[test]
+    fn memo_evicts_unused() {
+        let memo = SensorMemo::new(2);
+        memo.get_or_localize(48.0, 8.0, || Some(1));
+        memo.get_or_localize(49.0, 8.0, || Some(2));
+        // use the first sensor, so the second one is evicted
+        memo.get_or_localize(48.0, 8.0, || panic!("Should be memoized"));
+        memo.get_or_localize(50.0, 8.0, || Some(3));
+        assert_eq!(memo.evictions.load(Ordering::SeqCst), 1);
+        assert_eq!(memo.get_or_localize(48.0, 8.0, || panic!("Should be memoized")), Some(1));
+        assert_eq!(memo.get_or_localize(49.0, 8.0, || Some(4)), Some(4));
+        assert_eq!(memo.entries.read().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn memo_keeps_new_entries() {
+        let memo = SensorMemo::new(2);
+        memo.get_or_localize(48.0, 8.0, || Some(1));
+        memo.get_or_localize(49.0, 8.0, || Some(2));
+        memo.get_or_localize(49.0, 8.0, || panic!("Should be memoized"));
+        memo.get_or_localize(50.0, 8.0, || Some(3));
+        // the sensor inserted by the last eviction was not used since, but is kept
+        memo.get_or_localize(51.0, 8.0, || Some(4));
+        assert_eq!(memo.get_or_localize(50.0, 8.0, || panic!("Should be memoized")), Some(3));
+        assert_eq!(memo.get_or_localize(51.0, 8.0, || panic!("Should be memoized")), Some(4));
+        assert_eq!(memo.evictions.load(Ordering::SeqCst), 2);
+    }
+}
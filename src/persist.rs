@@ -0,0 +1,236 @@
+use crate::{AnalysisLocation,AnalysisLocations,LocationCache,LocationCacheItem};
+use crate::gen::index::{Cache,CacheItem,IndexedLocation,IndexedPolygon,IndexedRing,LocationsIndex};
+
+use geo::MultiPolygon;
+use rstar::RTree;
+use rstar::primitives::Line;
+use thiserror::Error;
+
+/// The version of the index format.
+/// Needs to be increased whenever the way [AnalysisLocations]
+/// are built changes, so outdated indexes are not used.
+pub const INDEX_VERSION: u32 = 5;
+
+/// Calculates a 64 bit FNV-1a hash of the raw locations dump.
+/// Used to detect if an index was built from a different dump.
+/// Contrary to std's DefaultHasher, the result is guaranteed to
+/// be the same for every build.
+pub fn source_hash(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// An error returned when an index can not be used
+/// to restore [AnalysisLocations].
+#[derive(Error,Debug,PartialEq)]
+pub enum IndexError {
+    /// Returned when the index was created using a different INDEX_VERSION.
+    #[error("Index has version {found}, expected version {expected}")]
+    VersionMismatch {
+        /// The version of the index
+        found: u32,
+        /// The version which is supported
+        expected: u32,
+    },
+    /// Returned when the index was built from a different locations dump.
+    #[error("Index was built from a different locations dump")]
+    SourceMismatch,
+    /// Returned when the index contains inconsistent data.
+    #[error("Index is malformed: {0}")]
+    Malformed(&'static str),
+}
+
+impl LocationCache {
+    /// Converts the items and capacity of the cache into its protobuf representation.
+    fn to_index(&self) -> Cache {
+        let items = self.items
+            .read()
+            .unwrap()
+            .iter()
+            .map(|item| CacheItem {
+                x: item.x,
+                y: item.y,
+                rsquare: item.rsquare,
+            })
+            .collect();
+        Cache {
+            capacity: self.capacity as u32,
+            items,
+        }
+    }
+
+    /// Restores the items and capacity of a cache created using [LocationCache::to_index].
+    fn restore(&mut self, cache: Cache) {
+        self.capacity = cache.capacity as usize;
+        let items = self.items.get_mut().unwrap();
+        items.clear();
+        items.extend(cache.items
+            .into_iter()
+            .take(self.capacity)
+            .map(|item| LocationCacheItem {
+                x: item.x,
+                y: item.y,
+                rsquare: item.rsquare,
+            }));
+    }
+}
+
+impl AnalysisLocations {
+    /// Converts the fully built AnalysisLocations (including the current
+    /// contents of the caches and the built R-trees) into an index, which can be stored using
+    /// [store_locations_index](crate::io::store_locations_index).
+    /// source_hash is the [source_hash] of the locations dump
+    /// these AnalysisLocations were built from.
+    pub fn to_index(&self, source_hash: u64) -> LocationsIndex {
+        let locations = self.locations
+            .iter()
+            .zip(self.insidecache.iter())
+            .zip(self.outsidecache.iter())
+            .map(|(((location, multipoly), insidecache), outsidecache)| {
+                let coordinates = |ring: &geo::LineString<f64>| ring.0
+                    .iter()
+                    .flat_map(|coord| std::iter::once(coord.x).chain(std::iter::once(coord.y)))
+                    .collect();
+                let polygons = multipoly.0
+                    .iter()
+                    .map(|poly| IndexedPolygon {
+                        coordinates: coordinates(poly.exterior()),
+                        interiors: poly.interiors()
+                            .iter()
+                            .map(|ring| IndexedRing { coordinates: coordinates(ring) })
+                            .collect(),
+                    })
+                    .collect();
+                // sorted, as the iteration order of the R-tree depends on how it was loaded
+                let mut lines = insidecache.lines
+                    .iter()
+                    .map(|line| [line.from[0], line.from[1], line.to[0], line.to[1]])
+                    .collect::<Vec<_>>();
+                lines.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+                IndexedLocation {
+                    zipcode: location.zipcode.clone(),
+                    cityid: location.cityid,
+                    qkm: location.qkm,
+                    population: location.population,
+                    polygons,
+                    insidecache: Some(insidecache.to_index()),
+                    outsidecache: Some(outsidecache.to_index()),
+                    lines: lines.into_iter().flatten().collect(),
+                }
+            })
+            .collect();
+        LocationsIndex {
+            version: INDEX_VERSION,
+            source_hash,
+            known_cities: self.known_cities.clone(),
+            locations,
+        }
+    }
+
+    /// Restores AnalysisLocations from an index created using [AnalysisLocations::to_index].
+    /// The R-trees are bulk loaded again from the stored lines and
+    /// the bounding boxes of the polygons, which is much faster than
+    /// converting the polygons of the locations dump into lines.
+    /// Fails if the index has a different version or was built from
+    /// a locations dump with a hash different from source_hash.
+    pub fn from_index(index: LocationsIndex, source_hash: u64) -> Result<Self, IndexError> {
+        use std::iter::FromIterator;
+        if index.version != INDEX_VERSION {
+            return Err(IndexError::VersionMismatch { found: index.version, expected: INDEX_VERSION });
+        }
+        if index.source_hash != source_hash {
+            return Err(IndexError::SourceMismatch);
+        }
+        let mut locations = Vec::with_capacity(index.locations.len());
+        let mut insidecaches = Vec::with_capacity(index.locations.len());
+        let mut outsidecaches = Vec::with_capacity(index.locations.len());
+        for location in index.locations {
+            if location.cityid as usize >= index.known_cities.len() {
+                return Err(IndexError::Malformed("Unknown cityid"));
+            }
+            if location.polygons.is_empty() {
+                return Err(IndexError::Malformed("Location without polygons"));
+            }
+            let invalid = |coordinates: &[f64]| coordinates.is_empty() || !coordinates.chunks_exact(2).remainder().is_empty();
+            if location.polygons.iter().any(|poly| invalid(&poly.coordinates) || poly.interiors.iter().any(|ring| invalid(&ring.coordinates))) {
+                return Err(IndexError::Malformed("Polygon with invalid coordinates"));
+            }
+            if location.lines.is_empty() || !location.lines.chunks_exact(4).remainder().is_empty() {
+                return Err(IndexError::Malformed("Location with invalid lines"));
+            }
+            let lines = RTree::bulk_load(location.lines
+                .chunks_exact(4)
+                .map(|line| Line::new([line[0], line[1]], [line[2], line[3]]))
+                .collect());
+            let mut insidecache = LocationCache::from_lines(lines.clone());
+            insidecache.restore(location.insidecache.unwrap_or_default());
+            let mut outsidecache = LocationCache::from_lines(lines);
+            outsidecache.restore(location.outsidecache.unwrap_or_default());
+            let ring = |coordinates: &[f64]| geo::LineString::from_iter(
+                coordinates
+                .chunks(2)
+                .map(|coord| (coord[0], coord[1])));
+            let multipoly = MultiPolygon::from_iter(
+                location
+                    .polygons
+                    .iter()
+                    .map(|poly| geo::Polygon::new(
+                        ring(&poly.coordinates),
+                        poly.interiors.iter().map(|interior| ring(&interior.coordinates)).collect()
+                    ))
+            );
+            locations.push((AnalysisLocation {
+                zipcode: location.zipcode,
+                locationid: locations.len() as u32,
+                cityid: location.cityid,
+                qkm: location.qkm,
+                population: location.population,
+            }, multipoly));
+            insidecaches.push(insidecache);
+            outsidecaches.push(outsidecache);
+        }
+        Ok(Self::from_parts(locations, index.known_cities, insidecaches, outsidecaches))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{IndexError,INDEX_VERSION};
+    use crate::AnalysisLocations;
+    use crate::tests::square_locations;
+
+    #[test]
+    fn index_roundtrip_keeps_caches() {
+        let locations = AnalysisLocations::new(square_locations())
+            .with_cache_size(4);
+        assert!(locations.localize(48.005, 8.005).next().is_some());
+        let index = locations.to_index(42);
+        assert_eq!(index.version, INDEX_VERSION);
+
+        let restored = AnalysisLocations::from_index(index.clone(), 42).expect("Index should be valid");
+        assert_eq!(restored.to_index(42), index);
+        assert_eq!(restored.bounds(), locations.bounds());
+        // the warm cache should be used right away
+        let square = restored.localize(48.005, 8.005).next();
+        assert_eq!(square.map(|location| restored.lookup(location.cityid)), Some("Square City"));
+        assert_eq!(restored.cache_stats()[0].insidehits, 1);
+        assert_eq!(restored.cache_stats()[0].misses, 0);
+        assert!(restored.localize(47.0, 8.005).next().is_none());
+    }
+
+    #[test]
+    fn index_mismatch_is_rejected() {
+        let index = AnalysisLocations::new(square_locations()).to_index(42);
+        assert_eq!(AnalysisLocations::from_index(index.clone(), 43).err(), Some(IndexError::SourceMismatch));
+        let mut outdated = index;
+        outdated.version = INDEX_VERSION + 1;
+        assert_eq!(
+            AnalysisLocations::from_index(outdated, 42).err(),
+            Some(IndexError::VersionMismatch { found: INDEX_VERSION + 1, expected: INDEX_VERSION })
+        );
+        let mut malformed = AnalysisLocations::new(square_locations()).to_index(42);
+        malformed.locations[0].lines.pop();
+        assert!(matches!(AnalysisLocations::from_index(malformed, 42), Err(IndexError::Malformed(_))));
+    }
+}
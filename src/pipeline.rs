@@ -1,144 +1,660 @@
 use crate::AnalysisLocations;
 use crate::aggregate::*;
-use crate::aqi::AQIValue;
+use crate::aqi::{AQIReport,AQIStandard,AQITableVersion,AQIValue,NOWCAST_HOURS,OUT_OF_SCALE_ASDEBS,OutOfScale,OutOfScalePolicy,Pollutant,PollutantAQIs,nowcast};
 use crate::spliter::*;
 use crate::CityId;
+use crate::memo::SensorMemo;
+use crate::region::{RegionHierarchy,RegionId,RegionLevel,worst_regions};
+use crate::robust::{Median,QuantileAggregate,TrimmedMean,TukeyMean};
+use crate::validate::{RejectReason,RejectedMeasurement,ValidationOptions,Validator};
 use crate::gen::challenger::{Batch,Measurement};
+use crate::humidity::{HumidityCorrection,Uncorrected};
+use crate::import::PollutantMeasurement;
 
-/// Aggregates multiple preaggregated values for every city
+use std::borrow::Cow;
+use std::collections::{BTreeMap,HashMap};
+
+/// Aggregates multiple preaggregated values for every city (or other region/sensor)
 // FIXME: This could probably simplified/reduced to preaggregated.fold(map_add())
-fn get_final_aggregate<'a>(preaggregated: impl Iterator<Item = &'a PreAggregateData>) -> CityParticleMap {
+fn get_final_aggregate<'a, K: std::hash::Hash + Eq + Copy + 'a, A: Aggregate + 'a>(preaggregated: impl Iterator<Item = &'a HashMap<K, A>>) -> HashMap<K, A> {
     preaggregated
-        .map(|x| &x.values)
         .flatten()
         .fold(
-            CityParticleMap::default(),
+            HashMap::default(),
             |mut result, (cityid, preaggregate)| {
             match result.get_mut(cityid) {
-                Some(x) => *x += *preaggregate,
-                None => assert!(result.insert(*cityid, *preaggregate).is_none()),
+                Some(x) => *x += preaggregate.clone(),
+                None => assert!(result.insert(*cityid, preaggregate.clone()).is_none()),
             };
             result
         })
 }
 
+/// Determines how cities are ranked by [run_pipeline].
+/// Regardless of the mode, the unweighted improvement is reported.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum RankingMode {
+    /// Ranks cities by the improvement of their AQI, as required by the DEBS challenge.
+    #[default]
+    Debs,
+    /// Ranks cities by the improvement multiplied by their population,
+    /// i.e. by how many people are exposed to the improvement.
+    Population,
+    /// Ranks cities by the improvement multiplied by the number of sensors
+    /// with measurements per km² during the last 5 days. This prefers improvements
+    /// measured by many sensors over those measured by few sensors.
+    SensorDensity,
+}
+
+impl std::str::FromStr for RankingMode {
+    type Err = String;
+
+    /// Parses the name of a ranking mode (debs, population or density).
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "debs" => Ok(RankingMode::Debs),
+            "population" => Ok(RankingMode::Population),
+            "density" => Ok(RankingMode::SensorDensity),
+            _ => Err(format!("Unknown ranking mode {}", name)),
+        }
+    }
+}
+
+impl RankingMode {
+    /// Calculates the score used to rank a city (lower is better, as for the improvement).
+    /// city_totals contains the population and area of every city (see
+    /// [AnalysisLocations::city_totals]), sensors the number of sensors of the city
+    /// with measurements during the last 5 days.
+    /// Cities with an unknown population or area have a score of 0 if weighted.
+    fn score(self, improvement: i32, cityid: CityId, sensors: usize, city_totals: &[(i64, f64)]) -> f64 {
+        let (population, qkm) = city_totals[cityid as usize];
+        let weight = match self {
+            RankingMode::Debs => 1.0,
+            RankingMode::Population => population as f64,
+            RankingMode::SensorDensity if qkm > 0.0 => sensors as f64 / qkm,
+            RankingMode::SensorDensity => 0.0,
+        };
+        f64::from(improvement) * weight
+    }
+}
+
+/// Determines which statistic of the measurements is used
+/// to calculate the AQI of a city (see [crate::robust]).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Statistic {
+    /// The mean of all measurements ([ParticleAggregate]), as required by the DEBS challenge.
+    #[default]
+    Mean,
+    /// The (approximate) median of the measurements ([Median])
+    Median,
+    /// The (approximate) mean of the measurements without the smallest
+    /// and largest measurements ([TrimmedMean])
+    TrimmedMean,
+    /// The (approximate) mean of the measurements without outliers ([TukeyMean])
+    TukeyMean,
+}
+
+impl std::str::FromStr for Statistic {
+    type Err = String;
+
+    /// Parses the name of a statistic (mean, median, trimmed or tukey).
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "mean" => Ok(Statistic::Mean),
+            "median" => Ok(Statistic::Median),
+            "trimmed" => Ok(Statistic::TrimmedMean),
+            "tukey" => Ok(Statistic::TukeyMean),
+            _ => Err(format!("Unknown statistic {}", name)),
+        }
+    }
+}
+
+/// Options changing how [run_pipeline] aggregates and ranks cities.
+/// The default options produce the results required by the DEBS challenge.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct PipelineOptions {
+    /// How cities are ranked
+    pub ranking: RankingMode,
+    /// The statistic used to aggregate measurements
+    pub statistic: Statistic,
+    /// If set, the measurements of each sensor are averaged first and the
+    /// average of a city is the mean of its sensor averages, so a single
+    /// sensor sending many measurements can not dominate its city.
+    pub sensor_means: bool,
+    /// The minimum number of sensors with measurements during the last 5 days
+    /// a city needs to be ranked (0 ranks all cities)
+    pub min_sensors: usize,
+    /// The plausibility checks applied to all measurements,
+    /// if None only negative values are rejected
+    pub validation: Option<ValidationOptions>,
+    /// If positive, the incrementally updated aggregates are compared
+    /// to aggregates recomputed from scratch every verify_interval windows,
+    /// and any divergence is reported (a debugging aid, as recomputing is slow)
+    pub verify_interval: usize,
+    /// How concentrations above the AQI scale are handled
+    pub out_of_scale: OutOfScalePolicy,
+    /// The air quality index used to calculate AQIs. The current AQI of ranked cities
+    /// uses the averaging period of the index, the improvement always compares the 5 day averages.
+    pub standard: AQIStandard,
+    /// If set, the current AQI of ranked cities is calculated from the NowCast
+    /// of the hourly averages of the last 12 hours (as used by the EPA for real-time reporting)
+    /// instead of the average of the averaging period
+    pub nowcast: bool,
+    /// If set, the particle concentrations of measurements providing the humidity
+    /// are corrected before they are aggregated. The raw values are retained along with
+    /// the humidity and temperature in [LocalizedMeasurement], plausibility checks
+    /// (and the rejected measurements) use the raw values
+    pub humidity_correction: Option<HumidityCorrection>,
+    /// If set, the zipcodes with the highest AQI are printed along with the results,
+    /// which requires aggregating every measurement per zipcode as well
+    pub zipcode_hotspots: bool,
+}
+
+impl PipelineOptions {
+    /// Returns whether the values of each sensor need to be aggregated.
+    fn track_sensors(&self) -> bool {
+        self.sensor_means || self.min_sensors > 0 || self.ranking == RankingMode::SensorDensity
+    }
+}
+
+/// The aggregates of all measurements during a time window (e.g. the last 5 days).
+#[derive(Debug, Default, Clone)]
+struct WindowAggregates<A = ParticleAggregate> {
+    /// The values of each city
+    cities: CityParticleMap<A>,
+    /// The values of each sensor, empty if sensors are not tracked
+    sensors: SensorParticleMap<A>,
+}
+
+impl<A: Aggregate> WindowAggregates<A> {
+    /// Aggregates multiple preaggregated values.
+    fn from_preaggregates<'a>(preaggregated: impl Iterator<Item = &'a PreAggregateData<A>> + Clone) -> Self where A: 'a {
+        Self {
+            cities: get_final_aggregate(preaggregated.clone().map(|x| &x.values)),
+            sensors: get_final_aggregate(preaggregated.map(|x| &x.sensors)),
+        }
+    }
+
+    /// Returns the largest difference between the p1/p2 values of the cities and sensors
+    /// of both windows, or infinity if their counts differ.
+    /// Empty aggregates (left after removing all values) are ignored.
+    fn divergence(&self, other: &Self) -> f32 {
+        divergence(&self.cities, &other.cities).max(divergence(&self.sensors, &other.sensors))
+    }
+
+    /// Returns the values of each city used to calculate its AQI,
+    /// which are the means of its sensors if enabled in options.
+    fn city_values(&self, options: &PipelineOptions) -> Cow<'_, CityParticleMap<A>> {
+        if options.sensor_means {
+            Cow::Owned(sensor_means(&self.sensors))
+        } else {
+            Cow::Borrowed(&self.cities)
+        }
+    }
+}
+
+impl<A: Aggregate> Monoid for WindowAggregates<A> {
+    fn identity() -> Self {
+        Self::default()
+    }
+
+    fn combine(&mut self, other: &Self) {
+        map_add(&mut self.cities, &other.cities);
+        map_add(&mut self.sensors, &other.sensors);
+    }
+}
+
+impl<A: Aggregate> Group for WindowAggregates<A> {
+    fn uncombine(&mut self, other: &Self) {
+        map_sub(&mut self.cities, &other.cities);
+        map_sub(&mut self.sensors, &other.sensors);
+    }
+}
+
+/// Combines preaggregated 5 minute windows with [WindowAggregates] directly,
+/// so the sliding windows only borrow the preaggregates of the [AnalysisWindow]s.
+#[derive(Debug, Default, Clone, Copy)]
+struct PreAggregateCombiner;
+
+impl<A: Aggregate> Combiner<PreAggregateData<A>, WindowAggregates<A>> for PreAggregateCombiner {
+    fn combine(&self, aggregate: &mut WindowAggregates<A>, value: &PreAggregateData<A>) {
+        map_add(&mut aggregate.cities, &value.values);
+        map_add(&mut aggregate.sensors, &value.sensors);
+    }
+}
+
+impl<A: Aggregate> Uncombiner<PreAggregateData<A>, WindowAggregates<A>> for PreAggregateCombiner {
+    fn uncombine(&self, aggregate: &mut WindowAggregates<A>, value: &PreAggregateData<A>) {
+        map_sub(&mut aggregate.cities, &value.values);
+        map_sub(&mut aggregate.sensors, &value.sensors);
+    }
+}
+
+/// Returns the values of the given cities (e.g. the ranked cities) during each of the last
+/// [NOWCAST_HOURS] hours (newest first), calculated from the nested windows of aggregator
+/// covering the last hour, the last two hours etc., starting at the window with the index first_window.
+/// Only the aggregates of these cities (or their sensors) are read from the windows.
+fn hourly_city_values<A: Aggregate, V, C: Combiner<V, WindowAggregates<A>>>(aggregator: &SlidingAggregator<WindowAggregates<A>, V, C>, first_window: usize, cities: &[CityId], options: &PipelineOptions) -> Vec<CityParticleMap<A>> {
+    let city_set = cities.iter().copied().collect::<std::collections::HashSet<_>>();
+    (0..NOWCAST_HOURS)
+        .map(|hour| {
+            let aggregates = aggregator.aggregate(first_window + hour);
+            let previous = if hour > 0 { Some(aggregator.aggregate(first_window + hour - 1)) } else { None };
+            // the values of the hour, i.e. without the values of the previous (shorter) window
+            let hourly = |aggregate: &A, previous: Option<&A>| {
+                let mut aggregate = aggregate.clone();
+                if let Some(previous) = previous {
+                    aggregate -= previous.clone();
+                }
+                aggregate
+            };
+            if options.sensor_means {
+                let sensors = aggregates.sensors
+                    .iter()
+                    .filter(|((cityid, _), _)| city_set.contains(cityid))
+                    .map(|(key, aggregate)| (*key, hourly(aggregate, previous.as_ref().and_then(|previous| previous.sensors.get(key)))))
+                    .collect::<SensorParticleMap<A>>();
+                sensor_means(&sensors)
+            } else {
+                cities
+                    .iter()
+                    .filter_map(|cityid| {
+                        let aggregate = aggregates.cities.get(cityid)?;
+                        Some((*cityid, hourly(aggregate, previous.as_ref().and_then(|previous| previous.cities.get(cityid)))))
+                    })
+                    .collect()
+            }
+        })
+        .collect()
+}
+
+/// The values the current AQI of the ranked cities is calculated from.
+enum CurrentValues<'a, A> {
+    /// The averages of the averaging period
+    Averages(&'a CityParticleMap<A>),
+    /// The hourly averages used by the NowCast, see [hourly_city_values]
+    NowCast(&'a [CityParticleMap<A>]),
+}
+
+impl<A: Aggregate> CurrentValues<'_, A> {
+    /// Returns the current p1 and p2 concentration of a city,
+    /// None if there is no NowCast (too few recent measurements).
+    fn concentrations(&self, cityid: CityId) -> (Option<f32>, Option<f32>) {
+        match self {
+            CurrentValues::Averages(values) => {
+                let aggregate = values
+                    .get(&cityid)
+                    .expect("Know city is contained in last 10 min");
+                (Some(aggregate.p1()), Some(aggregate.p2()))
+            },
+            CurrentValues::NowCast(hourly_values) => {
+                let hourly = |value: fn(&A) -> f32| hourly_values
+                    .iter()
+                    .map(|values| values.get(&cityid).filter(|aggregate| aggregate.count() > 0).map(value))
+                    .collect::<Vec<_>>();
+                (nowcast(&hourly(A::p1)), nowcast(&hourly(A::p2)))
+            },
+        }
+    }
+}
+
+/// Returns the largest difference between the p1/p2 values of the aggregates in a and b,
+/// see [WindowAggregates::divergence].
+fn divergence<K: std::hash::Hash + Eq, A: Aggregate>(a: &HashMap<K, A>, b: &HashMap<K, A>) -> f32 {
+    let nonempty = |map: &HashMap<K, A>| map.values().filter(|aggregate| aggregate.count() > 0).count();
+    if nonempty(a) != nonempty(b) {
+        return f32::INFINITY;
+    }
+    a.iter()
+        .filter(|(_, aggregate)| aggregate.count() > 0)
+        .map(|(key, aggregate)| match b.get(key) {
+            Some(other) if other.count() == aggregate.count() =>
+                (aggregate.p1() - other.p1()).abs().max((aggregate.p2() - other.p2()).abs()),
+            _ => f32::INFINITY,
+        })
+        .fold(0.0, f32::max)
+}
+
+/// The current AQI of a single pollutant of a ranked city.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum CurrentAQI {
+    /// The AQI in DEBS format (above 500000 if extrapolated, see [OutOfScalePolicy]),
+    /// calculated exactly for the EPA indices (see [AQIStandard::pm25_asdebs])
+    Value(i32),
+    /// The concentration is outside of the AQI scale
+    OutOfScale(OutOfScale),
+    /// There is no NowCast (too few recent measurements)
+    #[default]
+    Missing,
+}
+
+impl CurrentAQI {
+    /// Creates the current AQI from the AQI (in DEBS format) of a concentration, None if there is no concentration.
+    pub fn new(aqi: Option<Result<i32, OutOfScale>>) -> Self {
+        match aqi {
+            Some(Ok(aqi)) => CurrentAQI::Value(aqi),
+            Some(Err(out_of_scale)) => CurrentAQI::OutOfScale(out_of_scale),
+            None => CurrentAQI::Missing,
+        }
+    }
+
+    /// Returns the AQI, if there is one.
+    pub fn value(self) -> Option<AQIValue> {
+        match self {
+            CurrentAQI::Value(aqi) => AQIValue::from_asdebs(aqi),
+            _ => None,
+        }
+    }
+
+    /// Returns the AQI in DEBS format, if there is one.
+    pub fn asdebs(self) -> Option<i32> {
+        match self {
+            CurrentAQI::Value(aqi) => Some(aqi),
+            _ => None,
+        }
+    }
+
+    /// Returns the AQI in DEBS format, [OUT_OF_SCALE_ASDEBS] if there is none.
+    pub fn get_asdebs(self) -> i32 {
+        self.asdebs().unwrap_or(OUT_OF_SCALE_ASDEBS)
+    }
+}
+
+/// Additional information about a ranked city, which is not part of the DEBS result.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct RankedCityDetails {
+    /// The current p1 AQI (as reported in the DEBS result, where
+    /// missing AQIs are replaced by [OUT_OF_SCALE_ASDEBS])
+    pub current_aqip1: CurrentAQI,
+    /// The current p2 AQI, see current_aqip1
+    pub current_aqip2: CurrentAQI,
+    /// The number of sensors with measurements during the last 5 days
+    /// (0 if sensors are not tracked, see [PipelineOptions])
+    pub sensors: usize,
+    /// The number of measurements during the last 5 days
+    pub measurements: usize,
+    /// The 5 minute window with the highest AQI during the last 24 hours,
+    /// None if all windows were outside of the AQI scale
+    pub peak: Option<AQIPeak>,
+    /// The 5 minute window with the lowest AQI during the last 24 hours
+    pub low: Option<AQIPeak>,
+    /// The AQIs of the measurements of other pollutants (see [PollutantMeasurement])
+    /// during their averaging periods, only calculated using an EPA index
+    pub pollutants: PollutantAQIs,
+}
+
+impl RankedCityDetails {
+    /// Returns the report of the current AQIs, None if either is missing.
+    pub fn report(&self) -> Option<AQIReport> {
+        Some(AQIReport::new(self.current_aqip1.value()?, self.current_aqip2.value()?))
+    }
+}
+
 /// Describes the AQI improvement of a city
 struct TopKCity {
     /// Improvement (diffference between current/lastyear AQI in debs format)
     improvement: i32,
+    /// The score used for ranking the city, see [RankingMode]
+    score: f64,
     /// The city's id
     cityid: CityId,
+    /// The sensors and measurements of the city
+    details: RankedCityDetails,
 }
 
-/// Calculates the improvements for each active city and
-/// generates a Vec<TopKCity> sorted by improvement.
-fn calc_improvements(active_cities: &ActiveCities, current_aggregates: CityParticleMap, lastyear_aggregates: CityParticleMap) -> Vec<TopKCity> {
+/// Calculates the improvements for each active city (with at least
+/// options.min_sensors sensors) and generates a Vec<TopKCity>
+/// sorted by the score of the ranking mode.
+fn calc_improvements<A: Aggregate>(active_cities: &ActiveCities, current: &WindowAggregates<A>, lastyear: &WindowAggregates<A>, options: &PipelineOptions, city_totals: &[(i64, f64)]) -> Vec<TopKCity> {
     use rayon::prelude::*;
+    let current_aggregates = current.city_values(options);
+    let lastyear_aggregates = lastyear.city_values(options);
+    let sensors = sensor_counts(&current.sensors);
     let mut improvements = current_aggregates
-        .into_par_iter()
+        .par_iter()
+        .map(|(cityid, aggregate)| (*cityid, aggregate))
         .filter(|(cityid, _)| active_cities.is_active(*cityid))
+        .filter(|(cityid, _)| sensors.get(cityid).copied().unwrap_or(0) >= options.min_sensors)
         .filter_map(|(cityid, aggregate)| {
             // Get this year's 5 day AQI
-            let current_aqip1 = AQIValue::from_pm10(aggregate.p1())?;//.expect("Average current p1 invalid");
-            let current_aqip2 = AQIValue::from_pm25(aggregate.p2())?;//.expect("Average current p2 invalid");
-            let current_aqi = current_aqip1.get_asdebs().max(current_aqip2.get_asdebs());
+            let standard = options.standard;
+            let current_aqip1 = standard.pm10_asdebs(aggregate.p1(), options.out_of_scale).ok()?;
+            let current_aqip2 = standard.pm25_asdebs(aggregate.p2(), options.out_of_scale).ok()?;
+            let current_aqi = current_aqip1.max(current_aqip2);
 
             // Get last year's 5 day AQI
             // If no sensor data was available in given city for lastyear window period,
             // calculation of improvement is impossible. Skip those cases.
             let lastyear_aggregate = lastyear_aggregates.get(&cityid)?;
-            let lastyear_aqip1 = AQIValue::from_pm10(lastyear_aggregate.p1())?;//.expect("Average lastyear p1 invalid");
-            let lastyear_aqip2 = AQIValue::from_pm25(lastyear_aggregate.p2())?;//.expect("Average lastyear p2 invalid");
-            let lastyear_aqi = lastyear_aqip1.get_asdebs().max(lastyear_aqip2.get_asdebs());
+            let lastyear_aqip1 = standard.pm10_asdebs(lastyear_aggregate.p1(), options.out_of_scale).ok()?;
+            let lastyear_aqip2 = standard.pm25_asdebs(lastyear_aggregate.p2(), options.out_of_scale).ok()?;
+            let lastyear_aqi = lastyear_aqip1.max(lastyear_aqip2);
 
             let improvement = current_aqi - lastyear_aqi;
+            let city_sensors = sensors.get(&cityid).copied().unwrap_or(0);
 
             Some(TopKCity {
                 improvement,
-                cityid
+                score: options.ranking.score(improvement, cityid, city_sensors, city_totals),
+                cityid,
+                details: RankedCityDetails {
+                    sensors: city_sensors,
+                    measurements: current.cities.get(&cityid).map(A::count).unwrap_or(0),
+                    ..RankedCityDetails::default()
+                },
             })
         })
         .collect::<Vec<TopKCity>>();
-    improvements.sort_by_key(|x| x.improvement);
+    improvements.sort_by(|a, b| a.score.partial_cmp(&b.score).expect("Score should not be NaN"));
     improvements
 }
 
 /// Uses the list of improvements to generate the final result containing
-/// position, city name, the improvement and current air quality.
-fn get_top_cities(improvements: Vec<TopKCity>, active_cities: &ActiveCities, last_day_aqi: &CityParticleMap, locations: &AnalysisLocations, maxbatch: i64) -> crate::gen::challenger::ResultQ1 {
-    let result = improvements.into_iter()
+/// position, city name, the improvement and current air quality,
+/// along with the details of each ranked city (including its extremes of the last day).
+/// The current air quality is calculated from the current values
+/// (the averages of the averaging period of options.standard or the NowCast).
+fn get_top_cities<A: Aggregate>(improvements: Vec<TopKCity>, active_cities: &ActiveCities, current_values: CurrentValues<'_, A>, last_day_extremes: &CityExtremes, locations: &AnalysisLocations, maxbatch: i64, options: &PipelineOptions) -> (crate::gen::challenger::ResultQ1, Vec<RankedCityDetails>) {
+    let (result, details) = improvements.into_iter()
         .take(50) // top 50
         .enumerate()
         .map(|(position, city)| {
 
             // Calculate the aggregate for the lasy day (only for the top 50)
             assert!(active_cities.is_active(city.cityid));
-            let lastday_aggregate = last_day_aqi
-                .get(&city.cityid)
-                .expect("Know city is contained in last 10 min");
-            let current_aqip1 = AQIValue::from_pm10(lastday_aggregate.p1());
-            /* #[cfg(debug_assertions)]
-               if current_aqip1.is_none() {
-               println!(
-               "24h p1 for city {} (cityid {}) outside scale",
-               locations.lookup(city.cityid).to_owned(),
-               city.cityid
-               );
-               let last24h_iter = window
-               .current
-               .clone()
-               .rev()
-               .take(24*(60/5) - 1)
-               .filter_map(|city_aggregate_map| city_aggregate_map.get(&city.cityid));
-               let last24h_values = last24h_iter
-               .clone()
-               .copied()
-               .collect::<Vec<ParticleAggregate>>();
-               let last24h_actual = last24h_iter.copied().collect::<ParticleAggregate>();
-               println!(
-               " Cached 24h p1: {:?}, actual: {:?}, values:\n{:?}",
-               cache.current_1d_aggregates.get(&city.cityid),
-               last24h_actual,
-               last24h_values);
-               } */
-            let current_aqip1 = current_aqip1
-                .map(|v| v.get_asdebs())
-                .unwrap_or(i32::MAX); // FIXME: Handle Average last 24h p1 outside scale
-            let current_aqip2 = AQIValue::from_pm25(lastday_aggregate.p2())
-                .map(|v| v.get_asdebs())
-                .unwrap_or(i32::MAX); // FIXME: Handle Average last 24h p2 outside scale
-
-            crate::gen::challenger::TopKCities {
+            let (p1, p2) = current_values.concentrations(city.cityid);
+            // AQIs outside the scale (depending on the policy) or without a NowCast
+            // (too few recent measurements) are reported as OUT_OF_SCALE_ASDEBS,
+            // the details keep why they are missing
+            let standard = options.standard;
+            let current_aqip1 = CurrentAQI::new(p1.map(|p1| standard.pm10_asdebs(p1, options.out_of_scale)));
+            let current_aqip2 = CurrentAQI::new(p2.map(|p2| standard.pm25_asdebs(p2, options.out_of_scale)));
+
+            (crate::gen::challenger::TopKCities {
                 position: position as i32,
                 city: locations.lookup(city.cityid).to_owned(),
-                current_aqip1,
-                current_aqip2,
+                current_aqip1: current_aqip1.get_asdebs(),
+                current_aqip2: current_aqip2.get_asdebs(),
                 average_aqi_improvement: -city.improvement,
-               }
+               }, RankedCityDetails {
+                   current_aqip1,
+                   current_aqip2,
+                   peak: last_day_extremes.max.get(&city.cityid).copied(),
+                   low: last_day_extremes.min.get(&city.cityid).copied(),
+                   ..city.details
+               })
         })
-        .collect::<Vec<_>>();
-    crate::gen::challenger::ResultQ1 {
+        .unzip();
+    (crate::gen::challenger::ResultQ1 {
         benchmark_id: 0, 
         batch_seq_id: maxbatch,
         topkimproved: result,
+    }, details)
+}
+
+/// The maximum number of sensors memoized by [run_pipeline].
+const SENSOR_MEMO_CAPACITY: usize = 1 << 16;
+
+/// The measurements of other pollutants (see [PollutantMeasurement]) of each city,
+/// aggregated in 5 minute buckets.
+#[derive(Debug, Default)]
+struct PollutantBuckets {
+    /// The aggregates of each city, by the start of the bucket (in seconds)
+    buckets: BTreeMap<i64, HashMap<CityId, PollutantAggregate>>,
+}
+
+impl PollutantBuckets {
+    /// The length of a bucket in seconds
+    const BUCKET_SECONDS: i64 = 5 * 60;
+
+    /// Localizes the measurements and aggregates them per city and bucket,
+    /// measurements outside of all locations are dropped.
+    fn new(measurements: &[PollutantMeasurement], locations: &AnalysisLocations) -> Self {
+        let mut buckets = BTreeMap::<i64, HashMap<CityId, PollutantAggregate>>::new();
+        for measurement in measurements {
+            let location = match locations.localize_or_nearest(measurement.latitude, measurement.longitude) {
+                Some(location) => location,
+                None => continue,
+            };
+            let start = measurement.timestamp_seconds - measurement.timestamp_seconds.rem_euclid(Self::BUCKET_SECONDS);
+            buckets
+                .entry(start)
+                .or_default()
+                .entry(location.cityid)
+                .or_default()
+                .add(measurement.pollutant, measurement.value);
+        }
+        Self { buckets }
+    }
+
+    /// Returns whether there are no measurements.
+    fn is_empty(&self) -> bool {
+        self.buckets.is_empty()
+    }
+
+    /// Returns the AQIs of a city, calculated from the measurements of each pollutant
+    /// during its averaging period (see [Pollutant::averaging_hours]) up to (and including)
+    /// the bucket containing timestamp, using the given version of the EPA tables.
+    fn city_aqis(&self, cityid: CityId, timestamp: i64, version: AQITableVersion, policy: OutOfScalePolicy) -> PollutantAQIs {
+        let newest = timestamp - timestamp.rem_euclid(Self::BUCKET_SECONDS);
+        let mut aqis = PollutantAQIs::default();
+        for pollutant in Pollutant::ALL.iter().copied() {
+            let oldest = newest - i64::from(pollutant.averaging_hours()) * 60 * 60 + Self::BUCKET_SECONDS;
+            let aggregate = self.buckets
+                .range(oldest..=newest)
+                .filter_map(|(_, cities)| cities.get(&cityid))
+                .fold(PollutantAggregate::identity(), |mut aggregate, bucket| {
+                    aggregate.combine(bucket);
+                    aggregate
+                });
+            aqis.set(pollutant, aggregate.aqis(version, policy).get(pollutant));
+        }
+        aqis
+    }
+}
+
+/// The number of regions printed per level in a [RegionalSummary].
+const SUMMARY_REGIONS: usize = 10;
+
+/// Returns whether the result for batch_seq_id is printed.
+fn is_reported(batch_seq_id: i64) -> bool {
+    (batch_seq_id + 10) % 10000 <= 20
+}
+
+/// The regions with the worst air quality during the last 24 hours,
+/// calculated from the same windows as the ranking of the cities.
+struct RegionalSummary {
+    /// The zipcodes with the highest AQI
+    hotspots: Vec<(RegionId, i32)>,
+    /// The states with the highest AQI
+    states: Vec<(RegionId, i32)>,
+}
+
+impl RegionalSummary {
+    /// Prints the summary, using the names of the regions.
+    fn print(&self, regions: &RegionHierarchy, locations: &AnalysisLocations) {
+        for (title, level, values) in [
+            ("Zipcodes", RegionLevel::Zipcode, &self.hotspots),
+            ("States", RegionLevel::State, &self.states),
+        ].iter() {
+            if values.is_empty() {
+                continue;
+            }
+            println!("{} with the highest AQI in the last 24h:", title);
+            for (regionid, aqi) in values.iter() {
+                println!("  {}: {}", regions.name(locations, *level, *regionid), aqi);
+            }
+        }
     }
 }
 
 /// Runs the full query 1 pipeline using the input iterator batches_iter and locations to localize
-/// the measurements
-pub fn run_pipeline(locations: AnalysisLocations, batches_iter: impl Iterator<Item=Batch> + Send) {
+/// the measurements.
+/// The result of every window is passed to on_result (e.g. to export it)
+/// along with the details of each ranked city, some of the results are also printed.
+/// Along with the printed results, the zipcodes and states
+/// (as defined by regions) with the worst air quality are printed.
+/// Cities are aggregated and ranked as defined by options.
+/// Measurements rejected by the plausibility checks of options are passed to on_reject
+/// (e.g. to store them in a quarantine file).
+/// The AQIs of measurements of other pollutants (e.g. from other sensor networks)
+/// are calculated for the ranked cities, see [RankedCityDetails::pollutants].
+pub fn run_pipeline(locations: &AnalysisLocations, regions: &RegionHierarchy, options: PipelineOptions, batches_iter: impl Iterator<Item=Batch> + Send, pollutants: &[PollutantMeasurement], on_result: impl FnMut(&crate::gen::challenger::ResultQ1, &[RankedCityDetails]), on_reject: impl FnMut(&RejectedMeasurement)) {
+    let pollutants = PollutantBuckets::new(pollutants, locations);
+    match options.statistic {
+        Statistic::Mean => run_pipeline_with::<ParticleAggregate>(locations, regions, options, batches_iter, &pollutants, on_result, on_reject),
+        Statistic::Median => run_pipeline_with::<QuantileAggregate<Median>>(locations, regions, options, batches_iter, &pollutants, on_result, on_reject),
+        Statistic::TrimmedMean => run_pipeline_with::<QuantileAggregate<TrimmedMean>>(locations, regions, options, batches_iter, &pollutants, on_result, on_reject),
+        Statistic::TukeyMean => run_pipeline_with::<QuantileAggregate<TukeyMean>>(locations, regions, options, batches_iter, &pollutants, on_result, on_reject),
+    }
+}
+
+/// The aggregators cached between two windows, see [run_pipeline_with]:
+/// the current values of the last day and the last 5 days, the values of last year
+/// and the extremes of the last day, all of which aggregate the preaggregates of the [AnalysisWindow]s.
+type WindowAggregators<A> = (
+    SlidingAggregator<WindowAggregates<A>, PreAggregateData<A>, PreAggregateCombiner>,
+    SlidingAggregator<WindowAggregates<A>, PreAggregateData<A>, PreAggregateCombiner>,
+    SlidingAggregator<CityExtremes, PreAggregateData<A>, ExtremesCombiner>,
+);
+
+/// Runs the pipeline as described in [run_pipeline], aggregating the measurements using A.
+fn run_pipeline_with<A: Aggregate + 'static>(locations: &AnalysisLocations, regions: &RegionHierarchy, options: PipelineOptions, batches_iter: impl Iterator<Item=Batch> + Send, pollutants: &PollutantBuckets, mut on_result: impl FnMut(&crate::gen::challenger::ResultQ1, &[RankedCityDetails]), mut on_reject: impl FnMut(&RejectedMeasurement)) {
+    let memo = SensorMemo::new(SENSOR_MEMO_CAPACITY);
+    let mut validator = Validator::new(options.validation);
+    let city_totals = locations.city_totals();
+    // the number of measurements which were not corrected for humidity, by reason
+    let uncorrected = [std::sync::atomic::AtomicUsize::new(0), std::sync::atomic::AtomicUsize::new(0)];
     let localize = |meas : Measurement, batch_seq_id: i64| {
-        if meas.latitude < 47.40724 || meas.latitude > 54.9079 || meas.longitude < 5.98815 || meas.longitude > 14.98853 {
-            // outside germany, don't bother searching
+        if !locations.in_bounds(meas.latitude, meas.longitude) {
+            // outside of all locations, don't bother searching
             return None;
         }
-        let location = locations.localize(meas.latitude, meas.longitude).next()?;
+        let locationid = memo.get_or_localize(meas.latitude, meas.longitude, || {
+            locations
+                .localize_or_nearest(meas.latitude, meas.longitude)
+                .map(|location| location.locationid)
+        })?;
+        let (p1, p2) = match options.humidity_correction.map(|correction| correction.correct(meas.p1, meas.p2, meas.humidity, meas.temperature)) {
+            Some(Ok(corrected)) => corrected,
+            Some(Err(reason)) => {
+                uncorrected[reason as usize].fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                (meas.p1, meas.p2)
+            },
+            None => (meas.p1, meas.p2),
+        };
         Some(LocalizedMeasurement {
             batch_seq_id,
-            cityid: location.cityid,
+            cityid: locations.cityid(locationid),
+            locationid,
+            sensorid: sensor_id(meas.latitude, meas.longitude),
             timestamp_seconds: meas.timestamp.unwrap().seconds,
-            p1: meas.p1,
-            p2: meas.p2,
+            p1,
+            p2,
+            raw_p1: meas.p1,
+            raw_p2: meas.p2,
+            humidity: meas.humidity,
+            temperature: meas.temperature,
         })
     };
     use rayon::prelude::*;
@@ -146,15 +662,16 @@ pub fn run_pipeline(locations: AnalysisLocations, batches_iter: impl Iterator<It
         .map(|batch| {
         let batch_seq_id = batch.seq_id;
 
-        let current_iter = batch.current
+        // full validation is sequential, as stuck sensors depend on the previous measurements
+        let current = validator.validate(batch.current, batch_seq_id, false, |rejected| on_reject(&rejected));
+        let lastyear = validator.validate(batch.lastyear, batch_seq_id, true, |rejected| on_reject(&rejected));
+        let current_iter = current
             .into_par_iter()
-            .filter(|m| m.p1 >= 0.0 && m.p2 >= 0.0)
             .filter_map(move |m| localize(m, batch_seq_id))
             .collect::<Vec<_>>()
             .into_iter();
-        let lastyear_iter = batch.lastyear
+        let lastyear_iter = lastyear
             .into_par_iter()
-            .filter(|m| m.p1 >= 0.0 && m.p2 >= 0.0)
             .filter_map(move |m| localize(m, batch_seq_id))
             .collect::<Vec<_>>()
             .into_iter();
@@ -169,19 +686,28 @@ pub fn run_pipeline(locations: AnalysisLocations, batches_iter: impl Iterator<It
                 x.first().map(|first| first.timestamp_seconds).unwrap_or(0),
                 x.last().map(|first| first.timestamp_seconds).unwrap_or(0));
         })
-        .preaggregate();
+        .preaggregate_sensors::<A>(options.track_sensors())
+        .with_zipcodes(options.zipcode_hotspots);
     let lastyear_iter = batch_lastyear_iter
         .flatten()
         .partition_5min()
-        .preaggregate();
-
-    struct SlidingWindowContents {
-        current_1d_aggregates: CityParticleMap,
-        current_5d_aggregates: CityParticleMap,
-        lastyear_5d_aggregates: CityParticleMap,
-    }
+        .preaggregate_sensors::<A>(options.track_sensors());
+    // the number of windows calculated since the last verification
+    // and the largest divergence found verifying them
+    let unverified_windows = std::cell::Cell::new(0usize);
+    let max_divergence = std::cell::Cell::new(0.0f32);
+    // the number of 5 minute windows the current AQI is averaged over
+    let averaging_windows = options.standard.index().averaging_hours() as usize * (60/5);
+    // the current windows of the averaging period (0) and the last 5 days (1),
+    // followed by the windows of the last hour, two hours etc. used by the NowCast (if enabled)
+    let nowcast_hours = if options.nowcast { NOWCAST_HOURS } else { 0 };
+    let current_windows = [averaging_windows, 5*24*(60/5)]
+        .iter()
+        .copied()
+        .chain((1..=nowcast_hours).map(|hour| hour * (60/5)))
+        .collect::<Vec<_>>();
     let mut resiter = IterPair(current_iter, lastyear_iter)
-        .with_analysis_windows(5*24*(60/5), 5*24*(60/5), |window, cache: Option<SlidingWindowContents>| {
+        .with_analysis_windows(5*24*(60/5), 5*24*(60/5), |window, cache: Option<WindowAggregators<A>>| {
             debug_assert_eq!(window.current.len(), 5*24*(60/5));
             debug_assert_eq!(window.lastyear.len(), 5*24*(60/5));
             let maxbatch_current = window.current.clone().last().map(|x| x.maxbatch).unwrap();
@@ -194,7 +720,7 @@ pub fn run_pipeline(locations: AnalysisLocations, batches_iter: impl Iterator<It
                 .clone() // only clones the iterator, not the underlying values
                 .rev()
                 .take(2) // get last 2 batches of 5 minutes = 10 minute window
-                .flat_map(|city_aggregate_map : &PreAggregateData| city_aggregate_map.values.iter().map(|(k,_v)| k))
+                .flat_map(|city_aggregate_map : &PreAggregateData<A>| city_aggregate_map.values.iter().map(|(k,_v)| k))
                 .copied()
                 .collect::<ActiveCities>();
             /* {
@@ -203,73 +729,340 @@ pub fn run_pipeline(locations: AnalysisLocations, batches_iter: impl Iterator<It
                 println!("window has {} current/{} lastyear samples ({} total)", current_samples, lastyear_samples, current_samples + lastyear_samples);
             } */
             
-            let (last_day_aqi, current_aggregates, lastyear_aggregates, cache) = {
-                let (current_1d_aggregates, current_5d_aggregates, lastyear_5d_aggregates) = if let Some(SlidingWindowContents { current_1d_aggregates, current_5d_aggregates, lastyear_5d_aggregates }) = cache {
-                    // cache exists add values from cache to newest value
-                    // to obtain the value for this window
-                    let newest : &CityParticleMap = &window.current.clone().last().unwrap().values;
-                    let newest_lastyear : &CityParticleMap = &window.lastyear.clone().last().unwrap().values;
-                    let mut new_current_1d_aggregates = newest.clone();
-                    let mut new_current_5d_aggregates = newest.clone();
-                    let mut new_lastyear_5d_aggregates = newest_lastyear.clone();
-                    map_add(&mut new_current_1d_aggregates, &current_1d_aggregates);
-                    map_add(&mut new_current_5d_aggregates, &current_5d_aggregates);
-                    map_add(&mut new_lastyear_5d_aggregates, &lastyear_5d_aggregates);
-                    (new_current_1d_aggregates, new_current_5d_aggregates, new_lastyear_5d_aggregates) 
-                } else {
-                    // generate the aggreagate from scrath using the 5 day window
-                    let last_day_windows = window.current
+            // the current windows (see current_windows), the window of last year and the extremes of the last day
+            let (mut current, mut lastyear, mut extremes) = match cache {
+                // cache exists, add newest value to obtain the values for this window
+                Some(cache) => {
+                    let (mut current, mut lastyear, mut extremes) = cache;
+                    let newest = window.current.clone().last().unwrap();
+                    current.push(newest);
+                    lastyear.push(window.lastyear.clone().last().unwrap());
+                    extremes.push(newest);
+                    (current, lastyear, extremes)
+                },
+                // generate the aggregates from scratch using the 5 day windows
+                None => {
+                    let mut current = SlidingAggregator::invertible(&current_windows, PreAggregateCombiner);
+                    let mut lastyear = SlidingAggregator::invertible(&[5*24*(60/5)], PreAggregateCombiner);
+                    // maxima can not be removed, so the two-stacks algorithm is used
+                    let mut extremes = SlidingAggregator::new(&[24*(60/5)], ExtremesCombiner { standard: options.standard, policy: options.out_of_scale });
+                    for (i, preaggregate) in window.current.clone().enumerate() {
+                        current.evict(window.current.clone().take(i));
+                        current.push(preaggregate);
+                        extremes.evict(window.current.clone().take(i));
+                        extremes.push(preaggregate);
+                    }
+                    for (i, preaggregate) in window.lastyear.clone().enumerate() {
+                        lastyear.evict(window.lastyear.clone().take(i));
+                        lastyear.push(preaggregate);
+                    }
+                    (current, lastyear, extremes)
+                },
+            };
+            // the aggregates are only borrowed, so the results are calculated before the windows advance
+            let current_aqi = current.aggregate(0);
+            let current_aggregates = current.aggregate(1);
+            let lastyear_aggregates = lastyear.aggregate(0);
+            let last_day_extremes = extremes.aggregate(0);
+            unverified_windows.set(unverified_windows.get() + 1);
+            if options.verify_interval > 0 && unverified_windows.get() >= options.verify_interval {
+                unverified_windows.set(0);
+                // recompute all aggregates from scratch to detect drift or bugs in the aggregators
+                let averaging_period = window.current.clone().rev().take(averaging_windows);
+                let divergence = current_aqi.divergence(&WindowAggregates::from_preaggregates(averaging_period))
+                    .max(current_aggregates.divergence(&WindowAggregates::from_preaggregates(window.current.clone())))
+                    .max(lastyear_aggregates.divergence(&WindowAggregates::from_preaggregates(window.lastyear.clone())));
+                println!("Incremental aggregates diverge by {} from recomputed aggregates at batch {}", divergence, maxbatch);
+                max_divergence.set(max_divergence.get().max(divergence));
+            }
+
+            // the summary of other levels is only needed for reported results,
+            // so the zipcodes of the last day (if enabled) are not cached but aggregated on demand
+            let timestamp = window.current.clone().rev().find_map(|x| x.timestamp);
+            let summary = if is_reported(maxbatch) {
+                let hotspots = if options.zipcode_hotspots {
+                    let current_zipcodes = get_final_aggregate(window.current
                         .clone()
                         .rev()
-                        .take(24*(60/5)); // take values from last day
-                    let current_1d_aggregates = get_final_aggregate(last_day_windows);
-                    let current_aggregates = get_final_aggregate(window.current.clone());
-                    let lastyear_aggregates = get_final_aggregate(window.lastyear.clone());
-                    (current_1d_aggregates, current_aggregates, lastyear_aggregates)
+                        .take(averaging_windows)
+                        .map(|x| &x.zipcodes));
+                    worst_regions(&current_zipcodes, SUMMARY_REGIONS, options.standard, options.out_of_scale)
+                } else {
+                    vec![]
                 };
+                Some(RegionalSummary {
+                    hotspots,
+                    states: worst_regions(&regions.rollup_cities(&current_aqi.cities), SUMMARY_REGIONS, options.standard, options.out_of_scale),
+                })
+            } else {
+                None
+            };
 
-                // create the cache by removing dropping the now obsolete last element from each window
-                // to be examined
-                // remove the first CityParticleMap from one day ago
-                let oldest_current_1d = window.current.clone().rev().nth(24*(60/5)-1).unwrap();
-                let mut current_1d_aggregates_tocache = current_1d_aggregates.clone();
-                map_sub(&mut current_1d_aggregates_tocache, &oldest_current_1d.values);
-
-
-                // remove the first CityParticleMap from 5 days ago
-                // since the window is exactly 5 days big, this should be the first item
-                let oldest_current_5d = window.current.clone().next().unwrap();
-                let mut current_5d_aggregates_tocache = current_5d_aggregates.clone();
-                map_sub(&mut current_5d_aggregates_tocache, &oldest_current_5d.values);
-
-                // remove the first CityParticleMap from 1 year 5 days ago
-                // since the window is exactly 5 days big, this should be the first item
-                let oldest_lastyear_5d = window.lastyear.clone().next().unwrap();
-                let mut lastyear_5d_aggregates_tocache = lastyear_5d_aggregates.clone();
-                map_sub(&mut lastyear_5d_aggregates_tocache, &oldest_lastyear_5d.values);
-
-                let newcache = SlidingWindowContents {
-                    current_1d_aggregates: current_1d_aggregates_tocache,
-                    current_5d_aggregates: current_5d_aggregates_tocache,
-                    lastyear_5d_aggregates: lastyear_5d_aggregates_tocache,
-                };
-                (current_1d_aggregates, current_5d_aggregates, lastyear_5d_aggregates, newcache)
+            let mut improvements = calc_improvements(&active_cities, &current_aggregates, &lastyear_aggregates, &options, &city_totals);
+            // the AQIs of other pollutants are only calculated for the ranked cities (see get_top_cities)
+            if let (Some(version), Some(timestamp)) = (options.standard.table_version(), timestamp) {
+                if !pollutants.is_empty() {
+                    for city in improvements.iter_mut().take(50) {
+                        city.details.pollutants = pollutants.city_aqis(city.cityid, timestamp, version, options.out_of_scale);
+                    }
+                }
+            }
+            // the NowCast is only calculated for the ranked cities
+            let ranked = improvements.iter().take(50).map(|city| city.cityid).collect::<Vec<_>>();
+            let hourly_values = options.nowcast.then(|| hourly_city_values(&current, 2, &ranked, &options));
+            let current_values = current_aqi.city_values(&options);
+            let current_values = match &hourly_values {
+                Some(hourly_values) => CurrentValues::NowCast(hourly_values),
+                None => CurrentValues::Averages(&current_values),
             };
-            ((active_cities, last_day_aqi, current_aggregates, lastyear_aggregates, maxbatch), cache)
-        })
-        .map(|(active_cities, last_day_aqi, current_aggregates, lastyear_aggregates, maxbatch)| {
-            let improvements = calc_improvements(&active_cities, current_aggregates, lastyear_aggregates);
-            (improvements, active_cities, last_day_aqi, maxbatch)
-        })
-        .map(|(improvements, active_cities, last_day_aqi, maxbatch)| {
-            get_top_cities(improvements, &active_cities, &last_day_aqi, &locations, maxbatch)
+            let (res, details) = get_top_cities(improvements, &active_cities, current_values, &last_day_extremes, locations, maxbatch, &options);
+
+            // make room for the next window, while the oldest preaggregates are still in the queues
+            current.evict(window.current.clone());
+            lastyear.evict(window.lastyear.clone());
+            extremes.evict(window.current.clone());
+            ((res, details, summary), (current, lastyear, extremes))
         });
 
-    for res in resiter {
-        if (res.batch_seq_id + 10) % 10000 <= 20 {
+    for (res, details, summary) in resiter {
+        if is_reported(res.batch_seq_id) {
             println!("{:#?}", res);
+            println!("Sensors/measurements of the ranked cities: {:?}", details
+                .iter()
+                .map(|city| (city.sensors, city.measurements))
+                .collect::<Vec<_>>());
+            println!("AQI categories (dominant pollutant) of the ranked cities: {:?}", details
+                .iter()
+                .map(|city| city.report().map(|report| (options.standard.index().category(report.aqi()), report.dominant_pollutant().name())))
+                .collect::<Vec<_>>());
+            println!("Peak AQI (timestamp) of the ranked cities during the last day: {:?}", details
+                .iter()
+                .map(|city| city.peak.map(|peak| (peak.aqi.get(), peak.timestamp_seconds)))
+                .collect::<Vec<_>>());
+            println!("Lowest AQI (timestamp) of the ranked cities during the last day: {:?}", details
+                .iter()
+                .map(|city| city.low.map(|low| (low.aqi.get(), low.timestamp_seconds)))
+                .collect::<Vec<_>>());
+            if !pollutants.is_empty() {
+                println!("AQIs of other pollutants of the ranked cities: {:?}", details
+                    .iter()
+                    .map(|city| city.pollutants
+                        .iter()
+                        .map(|(pollutant, aqi)| (pollutant.name(), aqi.get()))
+                        .collect::<Vec<_>>())
+                    .collect::<Vec<_>>());
+            }
+            if let Some(summary) = summary {
+                summary.print(regions, locations);
+            }
         }
+        on_result(&res, &details);
+    }
+    if options.verify_interval > 0 {
+        println!("Maximum divergence of incremental aggregates: {}", max_divergence.get());
     }
+    println!("Rejected measurements: {} ({})", validator.total_rejected(), RejectReason::ALL
+        .iter()
+        .map(|reason| format!("{}: {}", reason.name(), validator.rejected(*reason)))
+        .collect::<Vec<_>>()
+        .join(", "));
     use std::sync::atomic::Ordering;
+    if options.humidity_correction.is_some() {
+        println!("Measurements not corrected for humidity: {}", Uncorrected::ALL
+            .iter()
+            .map(|reason| format!("{}: {}", reason.name(), uncorrected[*reason as usize].load(Ordering::SeqCst)))
+            .collect::<Vec<_>>()
+            .join(", "));
+    }
     println!("Cache hits/misses/outside: {}/{}/{}", locations.cachehits.load(Ordering::SeqCst), locations.cachemisses.load(Ordering::SeqCst), locations.outsidecachehits.load(Ordering::SeqCst));
+    println!("Measurements assigned to nearest location: {}", locations.fallbackhits.load(Ordering::SeqCst));
+    let mut cache_stats = locations.cache_stats();
+    cache_stats.sort_by_key(|stats| std::cmp::Reverse(stats.misses));
+    println!("Zipcodes with the most cache misses: {:#?}", &cache_stats[..cache_stats.len().min(10)]);
+    println!("Sensor memo hits/misses/evictions: {}/{}/{} ({:.03}% hit rate)",
+        memo.hits.load(Ordering::SeqCst),
+        memo.misses.load(Ordering::SeqCst),
+        memo.evictions.load(Ordering::SeqCst),
+        memo.hit_rate() * 100.0);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CurrentAQI,CurrentValues,PipelineOptions,RankedCityDetails,RankingMode,TopKCity,WindowAggregates,calc_improvements,get_top_cities,hourly_city_values};
+    use crate::AnalysisLocations;
+    use crate::aggregate::{ActiveCities,CityExtremes,MonoidCombiner,ParticleAggregate,SlidingAggregator};
+    use crate::aqi::{NOWCAST_HOURS,OUT_OF_SCALE_ASDEBS,OutOfScale,OutOfScalePolicy};
+    use crate::tests::square_locations;
+
+    /// Generates WindowAggregates with the given (p1, p2, number of measurements) per city,
+    /// where each measurement is sent by a different sensor.
+    fn aggregates(values: &[(f32, f32, usize)]) -> WindowAggregates {
+        let mut aggregates = WindowAggregates::default();
+        for (cityid, &(p1, p2, count)) in values.iter().enumerate() {
+            let cityid = cityid as u32;
+            aggregates.cities.insert(cityid, std::iter::repeat((p1, p2)).take(count).collect());
+            for sensorid in 0..count {
+                aggregates.sensors.insert((cityid, sensorid as u64), ParticleAggregate::new((p1, p2)));
+            }
+        }
+        aggregates
+    }
+
+    #[test]
+    fn ranking_modes_weight_improvements() {
+        let active_cities = (0..3).collect::<ActiveCities>();
+        // city 0 improves the most, city 2 the least
+        let current = aggregates(&[(10.0, 5.0, 1), (10.0, 5.0, 10), (10.0, 5.0, 100)]);
+        let lastyear = aggregates(&[(100.0, 50.0, 1), (60.0, 30.0, 1), (30.0, 15.0, 1)]);
+        // (population, qkm) of each city
+        let city_totals = vec![(10, 10.0), (1000, 10.0), (100_000, 0.0)];
+        let ranked = |ranking| {
+            let options = PipelineOptions { ranking, ..PipelineOptions::default() };
+            calc_improvements(&active_cities, &current, &lastyear, &options, &city_totals)
+                .iter()
+                .map(|city| city.cityid)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(ranked(RankingMode::Debs), vec![0, 1, 2]);
+        assert_eq!(ranked(RankingMode::Population), vec![2, 1, 0]);
+        // city 2 has an unknown area, so its score is 0
+        assert_eq!(ranked(RankingMode::SensorDensity), vec![1, 0, 2]);
+        // the density counts sensors, not measurements
+        let mut current = current.clone();
+        current.cities.insert(0, std::iter::repeat((10.0, 5.0)).take(1000).collect());
+        current.sensors.insert((0, 0), std::iter::repeat((10.0, 5.0)).take(1000).collect());
+        let options = PipelineOptions { ranking: RankingMode::SensorDensity, ..PipelineOptions::default() };
+        let improvements = calc_improvements(&active_cities, &current, &lastyear, &options, &city_totals);
+        assert_eq!(improvements.iter().map(|city| city.cityid).collect::<Vec<_>>(), vec![1, 0, 2]);
+        // averaging the sensors first (which changes the counts of the aggregates) keeps the density
+        let options = PipelineOptions { sensor_means: true, ..options };
+        let means = calc_improvements(&active_cities, &current, &lastyear, &options, &city_totals);
+        assert_eq!(means.iter().map(|city| (city.cityid, city.score)).collect::<Vec<_>>(),
+            improvements.iter().map(|city| (city.cityid, city.score)).collect::<Vec<_>>());
+        assert_eq!("density".parse(), Ok(RankingMode::SensorDensity));
+        assert!("unknown".parse::<RankingMode>().is_err());
+    }
+
+    #[test]
+    fn sensor_means_avoid_single_sensor_dominance() {
+        let active_cities = (0..2).collect::<ActiveCities>();
+        // city 0 has a chatty sensor measuring good air and a quiet one measuring bad air
+        let mut current = WindowAggregates::default();
+        current.cities.insert(0, (0..99).map(|_| (5.0, 2.0)).chain(std::iter::once((100.0, 50.0))).collect());
+        current.sensors.insert((0, 1), (0..99).map(|_| (5.0, 2.0)).collect());
+        current.sensors.insert((0, 2), ParticleAggregate::new((100.0, 50.0)));
+        current.cities.insert(1, ParticleAggregate::new((20.0, 10.0)));
+        current.sensors.insert((1, 1), ParticleAggregate::new((20.0, 10.0)));
+        let lastyear = aggregates(&[(20.0, 10.0, 1), (20.0, 10.0, 1)]);
+        let city_totals = vec![(0, 0.0), (0, 0.0)];
+
+        // using the raw mean, city 0 improved the most
+        let raw = calc_improvements(&active_cities, &current, &lastyear, &PipelineOptions::default(), &city_totals);
+        assert_eq!(raw.iter().map(|city| city.cityid).collect::<Vec<_>>(), vec![0, 1]);
+        assert_eq!(raw[0].details.measurements, 100);
+        assert_eq!(raw[0].details.sensors, 2);
+
+        // using the mean of the sensors, the air in city 0 got worse
+        let options = PipelineOptions { sensor_means: true, ..PipelineOptions::default() };
+        let means = calc_improvements(&active_cities, &current, &lastyear, &options, &city_totals);
+        assert_eq!(means.iter().map(|city| city.cityid).collect::<Vec<_>>(), vec![1, 0]);
+        assert!(means[1].improvement > 0);
+
+        // city 1 only has a single sensor
+        let options = PipelineOptions { min_sensors: 2, ..PipelineOptions::default() };
+        let filtered = calc_improvements(&active_cities, &current, &lastyear, &options, &city_totals);
+        assert_eq!(filtered.iter().map(|city| city.cityid).collect::<Vec<_>>(), vec![0]);
+    }
+
+    #[test]
+    fn out_of_scale_aqis_are_kept_in_details() {
+        let locations = AnalysisLocations::new(square_locations());
+        let active_cities = (0..1).collect::<ActiveCities>();
+        let improvements = || vec![TopKCity { improvement: -1, score: -1.0, cityid: 0, details: RankedCityDetails::default() }];
+        let current = aggregates(&[(1000.0, 5.0, 1)]);
+        let top_cities = |current_values, out_of_scale| {
+            let options = PipelineOptions { out_of_scale, ..PipelineOptions::default() };
+            get_top_cities(improvements(), &active_cities, current_values, &CityExtremes::default(), &locations, 7, &options)
+        };
+
+        // the result contains OUT_OF_SCALE_ASDEBS, the details why the AQI is missing
+        let (result, details) = top_cities(CurrentValues::Averages(&current.cities), OutOfScalePolicy::Reject);
+        assert_eq!(result.topkimproved[0].current_aqip1, OUT_OF_SCALE_ASDEBS);
+        assert_eq!(details[0].current_aqip1, CurrentAQI::OutOfScale(OutOfScale { concentration: 1000.0 }));
+        assert_eq!(details[0].current_aqip2.get_asdebs(), result.topkimproved[0].current_aqip2);
+        assert!(details[0].current_aqip2.value().is_some());
+        assert_eq!(details[0].report(), None);
+
+        // extrapolated AQIs are never reported as OUT_OF_SCALE_ASDEBS
+        let huge = aggregates(&[(1e10, 5.0, 1)]);
+        let (result, details) = top_cities(CurrentValues::Averages(&huge.cities), OutOfScalePolicy::Extrapolate);
+        assert_eq!(result.topkimproved[0].current_aqip1, OUT_OF_SCALE_ASDEBS - 1);
+        assert!(details[0].report().is_some());
+
+        // without a NowCast, the AQIs are missing
+        let (result, details) = top_cities(CurrentValues::NowCast(&[]), OutOfScalePolicy::Reject);
+        assert_eq!(result.topkimproved[0].current_aqip2, OUT_OF_SCALE_ASDEBS);
+        assert_eq!(details[0].current_aqip2, CurrentAQI::Missing);
+    }
+
+    #[test]
+    fn divergence_detects_differences() {
+        let window = aggregates(&[(10.0, 5.0, 2), (20.0, 10.0, 1)]);
+        assert_eq!(window.divergence(&window.clone()), 0.0);
+
+        // an emptied aggregate is ignored
+        let mut emptied = window.clone();
+        emptied.cities.insert(2, ParticleAggregate::default());
+        assert_eq!(emptied.divergence(&window), 0.0);
+
+        let mut changed = window.clone();
+        changed.cities.insert(1, ParticleAggregate::new((20.5, 10.0)));
+        assert_eq!(changed.divergence(&window), 0.5);
+
+        let mut missing = window.clone();
+        missing.sensors.remove(&(0, 1));
+        assert_eq!(missing.divergence(&window), f32::INFINITY);
+        assert_eq!(window.divergence(&missing), f32::INFINITY);
+    }
+
+    #[test]
+    fn hourly_values_match_recomputation() {
+        // 14 hours of 5 minute windows, the value of city 0 increasing every window
+        let windows = (0..14 * 12)
+            .map(|window| aggregates(&[(window as f32, 1.0, 1), (1.0, 1.0, 1)]))
+            .collect::<Vec<_>>();
+        let lengths = (1..=NOWCAST_HOURS).map(|hour| hour * 12).collect::<Vec<_>>();
+        let mut aggregator = SlidingAggregator::invertible(&[&[5][..], &lengths].concat(), MonoidCombiner);
+        for (i, window) in windows.iter().enumerate() {
+            aggregator.evict(windows[..i].iter());
+            aggregator.push(window);
+        }
+        // only the values of city 0 are calculated
+        let hourly = hourly_city_values(&aggregator, 1, &[0], &PipelineOptions::default());
+        let sensor_means = hourly_city_values(&aggregator, 1, &[0], &PipelineOptions { sensor_means: true, ..PipelineOptions::default() });
+        assert_eq!(hourly.len(), NOWCAST_HOURS);
+        for (hour, values) in hourly.iter().enumerate() {
+            let newest = windows.len() - hour * 12;
+            let recomputed = windows[newest - 12..newest]
+                .iter()
+                .map(|window| window.cities[&0].clone())
+                .collect::<ParticleAggregate>();
+            assert_eq!(values[&0], recomputed);
+            assert!(!values.contains_key(&1));
+            // the single sensor of city 0 sent all measurements
+            assert_eq!(sensor_means[hour][&0].p1(), recomputed.p1());
+            assert_eq!(sensor_means[hour][&0].count(), 1);
+            assert!(!sensor_means[hour].contains_key(&1));
+        }
+
+        // hours without measurements are empty
+        let windows = windows.into_iter().chain(std::iter::repeat(WindowAggregates::default()).take(12)).collect::<Vec<_>>();
+        for i in windows.len() - 12..windows.len() {
+            aggregator.evict(windows[..i].iter());
+            aggregator.push(&windows[i]);
+        }
+        let hourly = hourly_city_values(&aggregator, 1, &[0], &PipelineOptions::default());
+        assert_eq!(hourly[0].get(&0).map(|aggregate| aggregate.count()).unwrap_or(0), 0);
+        assert_eq!(hourly[1][&0].count(), 12);
+    }
 }
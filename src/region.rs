@@ -0,0 +1,216 @@
+use crate::{AnalysisLocations,CityId,LocationId};
+use crate::aggregate::{Aggregate,CityParticleMap,RegionParticleMap};
+use crate::aqi::{AQIStandard,AQIValue,OUT_OF_SCALE_ASDEBS,OutOfScalePolicy};
+
+use std::collections::HashMap;
+
+/// A newtype identifying a region of a single [RegionLevel],
+/// i.e. a LocationId, CityId or the id of a state.
+pub type RegionId = u32;
+
+/// The levels of the region hierarchy, from the finest to the coarsest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegionLevel {
+    /// A single location, identified by its LocationId
+    Zipcode,
+    /// A city, identified by its CityId
+    City,
+    /// A federal state or district,
+    /// only available for cities contained in the state table
+    State,
+}
+
+/// Maps every zipcode to its city and optionally every city to its state.
+/// As [Aggregate]s can simply be added,
+/// it is sufficient to aggregate measurements per zipcode, the values of
+/// all other levels can be calculated from these using [RegionHierarchy::rollup].
+#[derive(Debug, Clone, PartialEq)]
+pub struct RegionHierarchy {
+    /// The CityId of each LocationId
+    cities: Vec<CityId>,
+    /// The state of each CityId, None if unknown
+    states: Vec<Option<RegionId>>,
+    /// The names of all states, where the RegionId is simply an index
+    state_names: Vec<String>,
+}
+
+impl RegionHierarchy {
+    /// Creates a new RegionHierarchy consisting of the zipcodes and cities
+    /// of locations, without any states.
+    pub fn new(locations: &AnalysisLocations) -> Self {
+        Self {
+            cities: locations.locations
+                .iter()
+                .map(|(location, _)| location.cityid)
+                .collect(),
+            states: vec![None; locations.known_cities.len()],
+            state_names: vec![],
+        }
+    }
+
+    /// Assigns cities to states using a table of (city name, state name) rows,
+    /// e.g. loaded using [load_state_table](crate::io::load_state_table).
+    /// Rows with unknown cities are ignored, cities without a row do not belong to any state.
+    pub fn with_states<S: AsRef<str>>(mut self, locations: &AnalysisLocations, table: impl IntoIterator<Item = (S, S)>) -> Self {
+        let cityids = locations.known_cities
+            .iter()
+            .enumerate()
+            .map(|(cityid, name)| (name.as_str(), cityid))
+            .collect::<HashMap<_, _>>();
+        let mut stateids = HashMap::new();
+        for (city, state) in table {
+            let cityid = match cityids.get(city.as_ref()) {
+                Some(cityid) => *cityid,
+                None => continue,
+            };
+            let state_names = &mut self.state_names;
+            let stateid = *stateids
+                .entry(state.as_ref().to_owned())
+                .or_insert_with(|| {
+                    state_names.push(state.as_ref().to_owned());
+                    (state_names.len() - 1) as RegionId
+                });
+            self.states[cityid] = Some(stateid);
+        }
+        self
+    }
+
+    /// Returns the region of the given level the location belongs to.
+    /// Returns None if the level is [RegionLevel::State] and the city has no state.
+    pub fn region(&self, locationid: LocationId, level: RegionLevel) -> Option<RegionId> {
+        let cityid = self.cities[locationid as usize];
+        match level {
+            RegionLevel::Zipcode => Some(locationid),
+            RegionLevel::City => Some(cityid),
+            RegionLevel::State => self.states[cityid as usize],
+        }
+    }
+
+    /// Returns the name of a region, i.e. the zipcode, city or state name.
+    pub fn name<'a>(&'a self, locations: &'a AnalysisLocations, level: RegionLevel, regionid: RegionId) -> &'a str {
+        match level {
+            RegionLevel::Zipcode => locations.zipcode(regionid),
+            RegionLevel::City => locations.lookup(regionid),
+            RegionLevel::State => &self.state_names[regionid as usize],
+        }
+    }
+
+    /// Returns the number of known states.
+    pub fn state_count(&self) -> usize {
+        self.state_names.len()
+    }
+
+    /// Aggregates the values of zipcodes (as in [PreAggregateData::zipcodes](crate::aggregate::PreAggregateData::zipcodes))
+    /// on the given level. Zipcodes without a region on this level are skipped.
+    pub fn rollup<A: Aggregate>(&self, zipcodes: &RegionParticleMap<A>, level: RegionLevel) -> RegionParticleMap<A> {
+        let mut result = RegionParticleMap::default();
+        for (locationid, aggregate) in zipcodes {
+            if let Some(regionid) = self.region(*locationid, level) {
+                *result.entry(regionid).or_default() += aggregate.clone();
+            }
+        }
+        result
+    }
+
+    /// Aggregates the values of cities (as in [PreAggregateData::values](crate::aggregate::PreAggregateData::values))
+    /// on the state level, so states do not require the values of zipcodes.
+    /// Cities without a state are skipped.
+    pub fn rollup_cities<A: Aggregate>(&self, cities: &CityParticleMap<A>) -> RegionParticleMap<A> {
+        let mut result = RegionParticleMap::default();
+        for (cityid, aggregate) in cities {
+            if let Some(stateid) = self.states[*cityid as usize] {
+                *result.entry(stateid).or_default() += aggregate.clone();
+            }
+        }
+        result
+    }
+}
+
+/// Returns the (at most) k regions with the highest AQI (of the index of standard) in DEBS format
+/// (the maximum of the p1 and p2 AQI), sorted by their AQI.
+/// Averages above the AQI scale are handled as defined by policy,
+/// regions with averages outside the AQI scale are ranked first.
+pub fn worst_regions<A: Aggregate>(values: &RegionParticleMap<A>, k: usize, standard: AQIStandard, policy: OutOfScalePolicy) -> Vec<(RegionId, i32)> {
+    let mut regions = values
+        .iter()
+        .map(|(regionid, aggregate)| {
+            let aqip1 = standard.index().pm10(aggregate.p1(), policy).map(AQIValue::get_asdebs).unwrap_or(OUT_OF_SCALE_ASDEBS);
+            let aqip2 = standard.index().pm25(aggregate.p2(), policy).map(AQIValue::get_asdebs).unwrap_or(OUT_OF_SCALE_ASDEBS);
+            (*regionid, aqip1.max(aqip2))
+        })
+        .collect::<Vec<_>>();
+    regions.sort_by_key(|(regionid, aqi)| (std::cmp::Reverse(*aqi), *regionid));
+    regions.truncate(k);
+    regions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RegionHierarchy,RegionLevel,worst_regions};
+    use crate::aqi::{AQIStandard,OutOfScalePolicy};
+    use crate::AnalysisLocations;
+    use crate::aggregate::{ParticleAggregate,RegionParticleMap};
+    use crate::gen::challenger::{Location,Locations};
+    use crate::tests::square_locations;
+
+    /// Generates AnalysisLocations with the zipcodes 0 and 1 in city A,
+    /// 2 in city B and 3 in city C, all using the square test polygon.
+    fn test_locations() -> AnalysisLocations {
+        let polygons = square_locations().locations.remove(0).polygons;
+        let locations = ["A", "A", "B", "C"]
+            .iter()
+            .enumerate()
+            .map(|(zipcode, city)| Location {
+                zipcode: zipcode.to_string(),
+                city: city.to_string(),
+                qkm: 1.0,
+                population: 100,
+                polygons: polygons.clone(),
+            })
+            .collect();
+        AnalysisLocations::new(Locations { locations })
+    }
+
+    #[test]
+    fn rollup_aggregates_levels() {
+        let locations = test_locations();
+        let regions = RegionHierarchy::new(&locations)
+            .with_states(&locations, vec![("A", "North"), ("B", "North"), ("Unknown", "South")]);
+        // the unknown city is ignored, so there is no state South
+        assert_eq!(regions.state_count(), 1);
+        assert_eq!(regions.region(1, RegionLevel::City), Some(0));
+        assert_eq!(regions.region(2, RegionLevel::State), Some(0));
+        assert_eq!(regions.region(3, RegionLevel::State), None);
+        assert_eq!(regions.name(&locations, RegionLevel::Zipcode, 3), "3");
+        assert_eq!(regions.name(&locations, RegionLevel::City, 1), "B");
+        assert_eq!(regions.name(&locations, RegionLevel::State, 0), "North");
+
+        let zipcodes = vec![(10.0, 1.0), (20.0, 2.0), (30.0, 3.0), (40.0, 4.0)]
+            .into_iter()
+            .enumerate()
+            .map(|(locationid, value)| (locationid as u32, ParticleAggregate::new(value)))
+            .collect::<RegionParticleMap>();
+        let cities = regions.rollup(&zipcodes, RegionLevel::City);
+        assert_eq!(cities.len(), 3);
+        assert_eq!(cities[&0].p1(), 15.0);
+        assert_eq!(cities[&1].p1(), 30.0);
+        // zipcode 3 does not belong to a state
+        let states = regions.rollup(&zipcodes, RegionLevel::State);
+        assert_eq!(states.len(), 1);
+        assert_eq!(states[&0].p1(), 20.0);
+        assert_eq!(states[&0].p2(), 2.0);
+        // states can be rolled up from cities as well
+        assert_eq!(regions.rollup_cities(&cities), states);
+    }
+
+    #[test]
+    fn worst_regions_are_sorted() {
+        let values = vec![(0, (10.0, 1.0)), (1, (80.0, 5.0)), (2, (20.0, 40.0))]
+            .into_iter()
+            .map(|(regionid, value)| (regionid, ParticleAggregate::new(value)))
+            .collect::<RegionParticleMap>();
+        let worst = worst_regions(&values, 2, AQIStandard::UsEpa, OutOfScalePolicy::Reject);
+        assert_eq!(worst.iter().map(|(regionid, _)| *regionid).collect::<Vec<_>>(), vec![2, 1]);
+        assert!(worst[0].1 > worst[1].1);
+    }
+}
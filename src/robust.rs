@@ -0,0 +1,323 @@
+use crate::aggregate::Aggregate;
+
+use std::marker::PhantomData;
+
+/// Values at or below this value (in µg/m³) are counted as 0.
+const MIN_VALUE: f64 = 0.01;
+
+/// The ratio between the upper and lower bound of a bin.
+/// Every value is approximated with a relative error of at most
+/// (GAMMA - 1) / (GAMMA + 1), i.e. 1%.
+const GAMMA: f64 = 1.02;
+
+/// The share of the smallest and largest values ignored by [TrimmedMean].
+pub const TRIM_FRACTION: f64 = 0.1;
+
+/// A logarithmic histogram (similar to a DDSketch), which approximates
+/// the distribution of values with a bounded relative error.
+/// Contrary to most quantile sketches, values can be removed again
+/// by subtracting the histogram they were added with,
+/// which makes it usable in sliding windows.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Histogram {
+    /// The count of each non-empty bin, sorted by the bin index
+    bins: Vec<(u16, u32)>,
+    /// The number of values in all bins
+    count: usize,
+}
+
+impl Histogram {
+    /// Returns the index of the bin containing value.
+    /// Bin 0 contains values <= MIN_VALUE, bin i > 0 contains
+    /// values in (MIN_VALUE * GAMMA^(i - 1), MIN_VALUE * GAMMA^i].
+    fn bin(value: f32) -> u16 {
+        let value = f64::from(value);
+        if value <= MIN_VALUE {
+            0
+        } else {
+            ((value / MIN_VALUE).ln() / GAMMA.ln()).ceil().min(f64::from(u16::MAX)) as u16
+        }
+    }
+
+    /// Returns the value representing all values in the bin.
+    fn value(bin: u16) -> f64 {
+        if bin == 0 {
+            0.0
+        } else {
+            MIN_VALUE * GAMMA.powi(i32::from(bin)) * 2.0 / (1.0 + GAMMA)
+        }
+    }
+
+    /// Adds a single value to the histogram.
+    pub fn insert(&mut self, value: f32) {
+        debug_assert!(value >= 0.0);
+        let bin = Self::bin(value);
+        match self.bins.binary_search_by_key(&bin, |(bin, _)| *bin) {
+            Ok(idx) => self.bins[idx].1 += 1,
+            Err(idx) => self.bins.insert(idx, (bin, 1)),
+        }
+        self.count += 1;
+    }
+
+    /// Adds all values of other to the histogram.
+    pub fn merge(&mut self, other: &Histogram) {
+        for &(bin, count) in &other.bins {
+            match self.bins.binary_search_by_key(&bin, |(bin, _)| *bin) {
+                Ok(idx) => self.bins[idx].1 += count,
+                Err(idx) => self.bins.insert(idx, (bin, count)),
+            }
+        }
+        self.count += other.count;
+    }
+
+    /// Removes all values of other, which must have been added before.
+    pub fn remove(&mut self, other: &Histogram) {
+        debug_assert!(self.count >= other.count, "Removing {} values from histogram with only {} added", other.count, self.count);
+        for &(bin, count) in &other.bins {
+            let idx = self.bins
+                .binary_search_by_key(&bin, |(bin, _)| *bin)
+                .expect("Removing bin which has not been added previously");
+            debug_assert!(self.bins[idx].1 >= count);
+            self.bins[idx].1 -= count;
+        }
+        self.bins.retain(|(_, count)| *count > 0);
+        self.count -= other.count;
+    }
+
+    /// Returns the number of values in the histogram.
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Returns the (approximate) q-quantile of the values, 0 if empty.
+    pub fn quantile(&self, q: f64) -> f64 {
+        if self.count == 0 {
+            return 0.0;
+        }
+        let rank = (q.clamp(0.0, 1.0) * (self.count - 1) as f64).round() as usize;
+        let mut seen = 0;
+        for &(bin, count) in &self.bins {
+            seen += count as usize;
+            if seen > rank {
+                return Self::value(bin);
+            }
+        }
+        unreachable!("Rank {} exceeds count {}", rank, self.count)
+    }
+
+    /// Returns the (approximate) mean of the values with a rank
+    /// in skip..(count - skip), 0 if there are no such values.
+    fn mean_between_ranks(&self, skip: usize) -> f64 {
+        let end = self.count.saturating_sub(skip);
+        if skip >= end {
+            return 0.0;
+        }
+        let mut sum = 0.0;
+        let mut seen = 0;
+        for &(bin, count) in &self.bins {
+            let first = seen.max(skip);
+            seen += count as usize;
+            let last = seen.min(end);
+            if last > first {
+                sum += Self::value(bin) * (last - first) as f64;
+            }
+        }
+        sum / (end - skip) as f64
+    }
+
+    /// Returns the (approximate) mean of the values within [min, max],
+    /// 0 if there are no such values.
+    fn mean_between_values(&self, min: f64, max: f64) -> f64 {
+        let (sum, count) = self.bins
+            .iter()
+            .map(|&(bin, count)| (Self::value(bin), count))
+            .filter(|(value, _)| *value >= min && *value <= max)
+            .fold((0.0, 0), |(sum, total), (value, count)| (sum + value * f64::from(count), total + count));
+        if count == 0 {
+            0.0
+        } else {
+            sum / f64::from(count)
+        }
+    }
+}
+
+/// A statistic calculated from a [Histogram] by a [QuantileAggregate].
+pub trait RobustStatistic: std::fmt::Debug + Default + Clone + Send + Sync {
+    /// Calculates the statistic of the values in the histogram.
+    fn evaluate(histogram: &Histogram) -> f64;
+}
+
+/// The median of the values.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Median;
+
+impl RobustStatistic for Median {
+    fn evaluate(histogram: &Histogram) -> f64 {
+        histogram.quantile(0.5)
+    }
+}
+
+/// The mean of the values, ignoring the smallest and largest
+/// [TRIM_FRACTION] of the values.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct TrimmedMean;
+
+impl RobustStatistic for TrimmedMean {
+    fn evaluate(histogram: &Histogram) -> f64 {
+        let skip = (histogram.count() as f64 * TRIM_FRACTION).floor() as usize;
+        histogram.mean_between_ranks(skip)
+    }
+}
+
+/// The mean of the values, ignoring outliers outside of Tukey's fences,
+/// i.e. values more than 1.5 interquartile ranges below
+/// the first or above the third quartile.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct TukeyMean;
+
+impl RobustStatistic for TukeyMean {
+    fn evaluate(histogram: &Histogram) -> f64 {
+        let q1 = histogram.quantile(0.25);
+        let q3 = histogram.quantile(0.75);
+        let iqr = q3 - q1;
+        histogram.mean_between_values(q1 - 1.5 * iqr, q3 + 1.5 * iqr)
+    }
+}
+
+/// An [Aggregate] which stores the distributions of p1 and p2
+/// in [Histogram]s, and calculates the robust statistic S
+/// (e.g. the [Median]) instead of the mean. Contrary to the mean, a single
+/// broken sensor reporting extreme values barely changes the result.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct QuantileAggregate<S: RobustStatistic> {
+    /// The distribution of p1 values
+    p1: Histogram,
+    /// The distribution of p2 values
+    p2: Histogram,
+    /// The statistic calculated
+    statistic: PhantomData<S>,
+}
+
+impl<S: RobustStatistic> Aggregate for QuantileAggregate<S> {
+    fn from_measurement(init: (f32, f32)) -> Self {
+        let mut aggregate = Self::default();
+        aggregate.add_measurement(init);
+        aggregate
+    }
+
+    fn add_measurement(&mut self, val: (f32, f32)) {
+        self.p1.insert(val.0);
+        self.p2.insert(val.1);
+    }
+
+    fn p1(&self) -> f32 {
+        S::evaluate(&self.p1) as f32
+    }
+
+    fn p2(&self) -> f32 {
+        S::evaluate(&self.p2) as f32
+    }
+
+    fn count(&self) -> usize {
+        self.p1.count()
+    }
+}
+
+impl<S: RobustStatistic> std::ops::AddAssign for QuantileAggregate<S> {
+    fn add_assign(&mut self, rhs: Self) {
+        self.p1.merge(&rhs.p1);
+        self.p2.merge(&rhs.p2);
+    }
+}
+
+impl<S: RobustStatistic> std::ops::SubAssign for QuantileAggregate<S> {
+    fn sub_assign(&mut self, rhs: Self) {
+        self.p1.remove(&rhs.p1);
+        self.p2.remove(&rhs.p2);
+    }
+}
+
+impl<S: RobustStatistic> std::ops::Add for QuantileAggregate<S> {
+    type Output = Self;
+    fn add(mut self, rhs: Self) -> Self {
+        self += rhs;
+        self
+    }
+}
+
+impl<S: RobustStatistic> std::ops::Sub for QuantileAggregate<S> {
+    type Output = Self;
+    fn sub(mut self, rhs: Self) -> Self {
+        self -= rhs;
+        self
+    }
+}
+
+impl<S: RobustStatistic> std::iter::FromIterator<QuantileAggregate<S>> for QuantileAggregate<S> {
+    fn from_iter<T: IntoIterator<Item = QuantileAggregate<S>>>(iter: T) -> Self {
+        iter.into_iter().fold(Self::default(), |acc, x| acc + x)
+    }
+}
+
+impl<S: RobustStatistic> std::iter::FromIterator<(f32, f32)> for QuantileAggregate<S> {
+    fn from_iter<T: IntoIterator<Item = (f32, f32)>>(iter: T) -> Self {
+        let mut aggregate = Self::default();
+        for val in iter {
+            aggregate.add_measurement(val);
+        }
+        aggregate
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Median,QuantileAggregate,TrimmedMean,TukeyMean};
+    use crate::aggregate::{Aggregate,ParticleAggregate};
+
+    /// Nine plausible measurements and one of a broken sensor
+    fn measurements() -> Vec<(f32, f32)> {
+        (1..=9)
+            .map(|x| (x as f32 * 2.0, x as f32))
+            .chain(std::iter::once((999.0, 999.0)))
+            .collect()
+    }
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!((actual - expected).abs() <= expected * 0.02, "{} is not close to {}", actual, expected);
+    }
+
+    #[test]
+    fn robust_statistics_ignore_outliers() {
+        let mean = measurements().into_iter().collect::<ParticleAggregate>();
+        assert!(mean.p2() > 100.0);
+
+        let median = measurements().into_iter().collect::<QuantileAggregate<Median>>();
+        assert_eq!(median.count(), 10);
+        // the rank 4.5 is rounded to 5, i.e. the sixth value
+        assert_close(median.p1(), 12.0);
+        assert_close(median.p2(), 6.0);
+
+        // ignores the smallest and the largest value
+        let trimmed = measurements().into_iter().collect::<QuantileAggregate<TrimmedMean>>();
+        assert_close(trimmed.p2(), 5.5);
+
+        // 999 is outside of the fences, all other values are used
+        let tukey = measurements().into_iter().collect::<QuantileAggregate<TukeyMean>>();
+        assert_close(tukey.p2(), 5.0);
+        assert_close(tukey.p1(), 10.0);
+    }
+
+    #[test]
+    fn quantile_aggregate_supports_removal() {
+        let first = measurements()[..5].iter().copied().collect::<QuantileAggregate<Median>>();
+        let second = measurements()[5..].iter().copied().collect::<QuantileAggregate<Median>>();
+        let mut window = vec![first.clone(), second.clone()].into_iter().collect::<QuantileAggregate<Median>>();
+        assert_eq!(window, measurements().into_iter().collect());
+        window -= first;
+        assert_eq!(window, second);
+        assert_close(window.p2(), 8.0);
+        window -= second;
+        assert_eq!(window, QuantileAggregate::default());
+        assert_eq!(window.p1(), 0.0);
+    }
+}
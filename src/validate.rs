@@ -0,0 +1,300 @@
+use crate::aggregate::{SensorId,sensor_id};
+use crate::gen::challenger::Measurement;
+
+use std::collections::HashMap;
+
+/// The reasons for which a measurement can be rejected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RejectReason {
+    /// p1 or p2 is NaN or infinite
+    NotFinite,
+    /// p1 or p2 is negative (the only check required by the DEBS challenge)
+    Negative,
+    /// p1 or p2 is above the measuring range of the sensor
+    AboveRange,
+    /// p2 (particles < 2.5µm) is larger than p1 (particles < 10µm),
+    /// which is impossible as p1 includes p2
+    P2AboveP1,
+    /// The timestamp is missing or far outside of the range of the batch
+    Timestamp,
+    /// The sensor reported identical values too many times in a row
+    Stuck,
+}
+
+impl RejectReason {
+    /// All reasons, in the order in which they are checked.
+    pub const ALL: [RejectReason; 6] = [
+        RejectReason::NotFinite,
+        RejectReason::Negative,
+        RejectReason::AboveRange,
+        RejectReason::P2AboveP1,
+        RejectReason::Timestamp,
+        RejectReason::Stuck,
+    ];
+
+    /// Returns the name of the reason as used in the quarantine output.
+    pub fn name(self) -> &'static str {
+        match self {
+            RejectReason::NotFinite => "not_finite",
+            RejectReason::Negative => "negative",
+            RejectReason::AboveRange => "above_range",
+            RejectReason::P2AboveP1 => "p2_above_p1",
+            RejectReason::Timestamp => "timestamp",
+            RejectReason::Stuck => "stuck",
+        }
+    }
+}
+
+/// Options for the plausibility checks of [Validator].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ValidationOptions {
+    /// The largest plausible p1 value in µg/m³
+    pub max_p1: f32,
+    /// The largest plausible p2 value in µg/m³
+    pub max_p2: f32,
+    /// The maximum difference (in seconds) between the timestamp of
+    /// a measurement and the median timestamp of its batch
+    pub max_time_deviation: i64,
+    /// The number of identical measurements in a row after which
+    /// further identical measurements of a sensor are rejected (0 disables the check)
+    pub max_identical: u32,
+}
+
+impl Default for ValidationOptions {
+    /// Uses the measuring range of the SDS011 sensor (used by most sensors in the dataset),
+    /// allows timestamps up to one day from the median and rejects
+    /// sensors reporting the same values for more than an hour (at one measurement every 5 minutes).
+    fn default() -> Self {
+        Self {
+            max_p1: 999.9,
+            max_p2: 999.9,
+            max_time_deviation: 24 * 60 * 60,
+            max_identical: 12,
+        }
+    }
+}
+
+/// A measurement rejected by a [Validator].
+#[derive(Debug, Clone, PartialEq)]
+pub struct RejectedMeasurement {
+    /// Why the measurement was rejected
+    pub reason: RejectReason,
+    /// The batch containing the measurement
+    pub batch_seq_id: i64,
+    /// Whether the measurement is part of the measurements of last year
+    pub lastyear: bool,
+    /// The rejected measurement
+    pub measurement: Measurement,
+}
+
+/// The header of the CSV rows created by formatting [RejectedMeasurement]s.
+pub const QUARANTINE_HEADER: &str = "reason,batch,lastyear,timestamp,latitude,longitude,p1,p2";
+
+impl std::fmt::Display for RejectedMeasurement {
+    /// Formats the measurement as CSV row, see [QUARANTINE_HEADER].
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let timestamp = self.measurement.timestamp
+            .as_ref()
+            .map(|timestamp| timestamp.seconds.to_string())
+            .unwrap_or_default();
+        write!(f, "{},{},{},{},{},{},{},{}",
+            self.reason.name(),
+            self.batch_seq_id,
+            self.lastyear,
+            timestamp,
+            self.measurement.latitude,
+            self.measurement.longitude,
+            self.measurement.p1,
+            self.measurement.p2)
+    }
+}
+
+/// Rejects implausible measurements before they are localized and aggregated.
+/// Without [ValidationOptions], only negative values (and NaN) are rejected, as required by the DEBS challenge.
+/// As stuck sensors are detected using the previous measurements of each sensor,
+/// the batches need to be validated in order.
+#[derive(Debug, Default)]
+pub struct Validator {
+    /// The enabled plausibility checks
+    options: Option<ValidationOptions>,
+    /// The last values of each sensor (separately for the current and last year)
+    /// and how often they have been reported in a row
+    last_values: HashMap<(bool, SensorId), ((f32, f32), u32)>,
+    /// The number of rejected measurements per reason, in the order of [RejectReason::ALL]
+    rejected: [usize; RejectReason::ALL.len()],
+}
+
+impl Validator {
+    /// Creates a new Validator, performing the checks of options (if any).
+    pub fn new(options: Option<ValidationOptions>) -> Self {
+        Self { options, ..Self::default() }
+    }
+
+    /// Returns the number of measurements rejected because of reason.
+    pub fn rejected(&self, reason: RejectReason) -> usize {
+        self.rejected[reason as usize]
+    }
+
+    /// Returns the total number of rejected measurements.
+    pub fn total_rejected(&self) -> usize {
+        self.rejected.iter().sum()
+    }
+
+    /// Checks a single measurement, returning the first reason to reject it (if any).
+    fn check(&mut self, measurement: &Measurement, median_timestamp: i64, lastyear: bool) -> Option<RejectReason> {
+        let (p1, p2) = (measurement.p1, measurement.p2);
+        let options = match self.options {
+            Some(options) => options,
+            // equivalent to the filter p1 >= 0.0 && p2 >= 0.0
+            None if p1 >= 0.0 && p2 >= 0.0 => return None,
+            None if p1.is_nan() || p2.is_nan() => return Some(RejectReason::NotFinite),
+            None => return Some(RejectReason::Negative),
+        };
+        if !p1.is_finite() || !p2.is_finite() {
+            return Some(RejectReason::NotFinite);
+        }
+        if p1 < 0.0 || p2 < 0.0 {
+            return Some(RejectReason::Negative);
+        }
+        if p1 > options.max_p1 || p2 > options.max_p2 {
+            return Some(RejectReason::AboveRange);
+        }
+        if p2 > p1 {
+            return Some(RejectReason::P2AboveP1);
+        }
+        match &measurement.timestamp {
+            Some(timestamp) if (timestamp.seconds - median_timestamp).abs() <= options.max_time_deviation => {},
+            _ => return Some(RejectReason::Timestamp),
+        }
+        if options.max_identical > 0 {
+            let (values, count) = self.last_values
+                .entry((lastyear, sensor_id(measurement.latitude, measurement.longitude)))
+                .or_insert(((p1, p2), 0));
+            if *values == (p1, p2) {
+                *count += 1;
+            } else {
+                *values = (p1, p2);
+                *count = 1;
+            }
+            if *count > options.max_identical {
+                return Some(RejectReason::Stuck);
+            }
+        }
+        None
+    }
+
+    /// Returns the plausible measurements of a batch, passing
+    /// all rejected measurements to on_reject.
+    /// lastyear needs to be set for the measurements of last year.
+    /// Without [ValidationOptions], the measurements are filtered in parallel
+    /// (as they do not depend on previous measurements).
+    pub fn validate(&mut self, measurements: Vec<Measurement>, batch_seq_id: i64, lastyear: bool, mut on_reject: impl FnMut(RejectedMeasurement)) -> Vec<Measurement> {
+        if self.options.is_none() {
+            use rayon::prelude::*;
+            let (valid, rejected): (Vec<_>, Vec<_>) = measurements
+                .into_par_iter()
+                .partition(|measurement| measurement.p1 >= 0.0 && measurement.p2 >= 0.0);
+            for measurement in rejected {
+                let reason = self.check(&measurement, 0, lastyear).expect("Filtered measurement should be rejected");
+                self.rejected[reason as usize] += 1;
+                on_reject(RejectedMeasurement { reason, batch_seq_id, lastyear, measurement });
+            }
+            return valid;
+        }
+        let mut timestamps = measurements
+            .iter()
+            .filter_map(|measurement| measurement.timestamp.as_ref().map(|timestamp| timestamp.seconds))
+            .collect::<Vec<_>>();
+        let median_timestamp = if timestamps.is_empty() {
+            0
+        } else {
+            let middle = timestamps.len() / 2;
+            *timestamps.select_nth_unstable(middle).1
+        };
+        measurements
+            .into_iter()
+            .filter_map(|measurement| match self.check(&measurement, median_timestamp, lastyear) {
+                None => Some(measurement),
+                Some(reason) => {
+                    self.rejected[reason as usize] += 1;
+                    on_reject(RejectedMeasurement { reason, batch_seq_id, lastyear, measurement });
+                    None
+                },
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RejectReason,ValidationOptions,Validator};
+    use crate::gen::challenger::Measurement;
+
+    fn measurement(seconds: i64, latitude: f32, p1: f32, p2: f32) -> Measurement {
+        Measurement {
+            timestamp: Some(prost_types::Timestamp { seconds, nanos: 0 }),
+            latitude,
+            longitude: 8.0,
+            p1,
+            p2,
+            humidity: None,
+            temperature: None,
+        }
+    }
+
+    #[test]
+    fn implausible_measurements_are_rejected() {
+        let batch = vec![
+            measurement(1000, 48.0, 10.0, 5.0),
+            measurement(1010, 48.1, f32::NAN, 5.0),
+            measurement(1020, 48.2, 10.0, f32::INFINITY),
+            measurement(1030, 48.3, -1.0, 5.0),
+            measurement(1040, 48.4, 2000.0, 5.0),
+            measurement(1050, 48.5, 5.0, 10.0),
+            measurement(1000 + 2 * 24 * 60 * 60, 48.6, 10.0, 5.0),
+        ];
+        let mut rejected = vec![];
+        let mut validator = Validator::new(Some(ValidationOptions::default()));
+        let valid = validator.validate(batch.clone(), 7, false, |rejection| rejected.push(rejection));
+        assert_eq!(valid, vec![batch[0].clone()]);
+        assert_eq!(rejected.iter().map(|rejection| rejection.reason).collect::<Vec<_>>(), vec![
+            RejectReason::NotFinite,
+            RejectReason::NotFinite,
+            RejectReason::Negative,
+            RejectReason::AboveRange,
+            RejectReason::P2AboveP1,
+            RejectReason::Timestamp,
+        ]);
+        assert_eq!(rejected[2].to_string(), "negative,7,false,1030,48.3,8,-1,5");
+        assert_eq!(validator.rejected(RejectReason::NotFinite), 2);
+        assert_eq!(validator.total_rejected(), 6);
+
+        // without options, only negative values (and NaN) are rejected, keeping the order
+        let mut validator = Validator::new(None);
+        let valid = validator.validate(batch.clone(), 7, false, |_| {});
+        assert_eq!(valid, batch.iter().filter(|m| m.p1 >= 0.0 && m.p2 >= 0.0).cloned().collect::<Vec<_>>());
+        assert_eq!(valid.len(), 5);
+        assert_eq!(validator.rejected(RejectReason::NotFinite), 1);
+        assert_eq!(validator.rejected(RejectReason::Negative), 1);
+    }
+
+    #[test]
+    fn stuck_sensors_are_rejected() {
+        let options = ValidationOptions { max_identical: 2, ..ValidationOptions::default() };
+        let mut validator = Validator::new(Some(options));
+        let batch = vec![
+            measurement(1000, 48.0, 10.0, 5.0),
+            measurement(1000, 48.1, 10.0, 5.0),
+            measurement(1300, 48.0, 10.0, 5.0),
+        ];
+        assert_eq!(validator.validate(batch.clone(), 0, false, |_| {}).len(), 3);
+        // the third identical measurement of the first sensor is rejected, also in the next batch
+        assert_eq!(validator.validate(batch[..1].to_vec(), 1, false, |_| {}).len(), 0);
+        assert_eq!(validator.validate(batch[..1].to_vec(), 2, false, |_| {}).len(), 0);
+        // the measurements of last year are checked separately
+        assert_eq!(validator.validate(batch[..1].to_vec(), 2, true, |_| {}).len(), 1);
+        // a different value resets the count
+        assert_eq!(validator.validate(vec![measurement(1600, 48.0, 11.0, 5.0)], 3, false, |_| {}).len(), 1);
+        assert_eq!(validator.rejected(RejectReason::Stuck), 2);
+    }
+}